@@ -15,6 +15,8 @@ def op_str_to_byoo(op):
         return "lt"
     elif op == "<=":
         return "lte"
+    elif op == "!=":
+        return "neq"
     elif op == "contains":
         return "contains"
 
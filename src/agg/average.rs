@@ -18,19 +18,27 @@
 // 
 // < end copyright > 
 use agg::Aggregate;
-use data::{DataType, Data};
+use agg::sum::{Summation, SumMode};
+use data::{DataType, Data, DECIMAL_DIV_MIN_SCALE};
+use decimal;
 
+/// Averages are a sum (see `Summation`) divided by the count once the
+/// group is produced.
 pub struct AverageAggregate {
     row_idx: usize,
-    curr_avg: Option<Data>,
+    sum: Summation,
     curr_count: usize
 }
 
 impl AverageAggregate {
     pub fn new(row_idx: usize) -> AverageAggregate {
+        return AverageAggregate::with_mode(row_idx, SumMode::Exact);
+    }
+
+    pub fn with_mode(row_idx: usize, mode: SumMode) -> AverageAggregate {
         return AverageAggregate {
             row_idx,
-            curr_avg: None,
+            sum: Summation::for_avg(mode),
             curr_count: 0
         };
     }
@@ -39,29 +47,53 @@ impl AverageAggregate {
 impl Aggregate for AverageAggregate {
     fn consume(&mut self, row: &[Data]) {
         let nxt = &row[self.row_idx];
-        let curr = self.curr_avg.take();
+        if nxt.is_null() {
+            return;
+        }
+
         self.curr_count += 1;
-        self.curr_avg = match curr {
-            None => Some(nxt.clone()),
-            Some(ref d) => Some(d.clone() + (nxt.clone() - d.clone())
-                                / self.curr_count)
-        };
+        self.sum.add(nxt);
     }
 
     fn produce(&mut self) -> Data {
+        let count = self.curr_count;
         self.curr_count = 0;
-        return self.curr_avg.take().unwrap_or(Data::Real(0.0));
+        return self.sum.produce_avg(count);
     }
 
-    fn out_type(&self, _in_type: &DataType) -> DataType {
+    fn out_type(&self, in_types: &[DataType]) -> DataType {
+        if let DataType::DECIMAL(_, s) = &in_types[self.row_idx] {
+            return DataType::DECIMAL(decimal::MAX_PRECISION,
+                                     (*s).max(DECIMAL_DIV_MIN_SCALE));
+        }
+        
         return DataType::REAL;
     }
+
+    fn partial_state(&mut self) -> Vec<Data> {
+        let mut state = self.sum.partial_state();
+        state.push(Data::Integer(self.curr_count as i64));
+        self.curr_count = 0;
+        return state;
+    }
+
+    fn merge(&mut self, state: &[Data]) {
+        self.sum.merge(&state[0..2]);
+        self.curr_count += state[2].as_i64() as usize;
+    }
+
+    fn state_types(&self, in_types: &[DataType]) -> Vec<DataType> {
+        let mut types = Summation::state_types(&in_types[self.row_idx]);
+        types.push(DataType::INTEGER);
+        return types;
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use agg::{Aggregate};
     use agg::average::AverageAggregate;
+    use agg::sum::SumMode;
     use data::Data;
     
     #[test]
@@ -121,4 +153,43 @@ mod tests {
         assert_eq!(avg_agg.produce(), Data::Real(-12.0 / 3.0));
 
     }
+
+    #[test]
+    fn decimal_test() {
+        let mut avg_agg = AverageAggregate::new(0);
+
+        avg_agg.consume(&[Data::Decimal(1000, 2)]);
+        avg_agg.consume(&[Data::Decimal(0, 2)]);
+        avg_agg.consume(&[Data::Decimal(0, 2)]);
+
+        // 10.00 / 3 at the minimum division scale
+        assert_eq!(avg_agg.produce(), Data::Decimal(3_333_333, 6));
+    }
+
+    #[test]
+    fn long_input_test() {
+        let mut exact = AverageAggregate::new(0);
+        let mut fast = AverageAggregate::with_mode(0, SumMode::Fast);
+
+        for _ in 0..1_000_000 {
+            exact.consume(&[Data::Real(0.1)]);
+            fast.consume(&[Data::Real(0.1)]);
+        }
+
+        assert_eq!(exact.produce(), Data::Real(0.1));
+        assert_ne!(fast.produce(), Data::Real(0.1));
+
+        // integer averages are reals, even for a single value
+        exact.consume(&[Data::Integer(i64::MAX)]);
+        exact.consume(&[Data::Integer(i64::MAX)]);
+        assert_eq!(exact.produce(), Data::Real(i64::MAX as f64));
+        
+        exact.consume(&[Data::Integer(3)]);
+        assert_eq!(exact.produce(), Data::Real(3.0));
+
+        // unlike a fast sum, a fast average does not overflow
+        fast.consume(&[Data::Integer(i64::MAX)]);
+        fast.consume(&[Data::Integer(i64::MAX)]);
+        assert_eq!(fast.produce(), Data::Real(i64::MAX as f64));
+    }
 }
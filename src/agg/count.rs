@@ -21,19 +21,26 @@ use agg::Aggregate;
 use data::{Data, DataType};
 
 pub struct CountAggregate {
+    row_idx: usize,
     curr_count: usize
 }
 
 impl CountAggregate {
-    pub fn new(_row_idx: usize) -> CountAggregate {
+    pub fn new(row_idx: usize) -> CountAggregate {
         return CountAggregate {
+            row_idx,
             curr_count: 0
         };
     }
 }
 
 impl Aggregate for CountAggregate {
-    fn consume(&mut self, _row: &[Data]) {
+    fn consume(&mut self, row: &[Data]) {
+        // like SQL's COUNT(col), NULL values are not counted
+        if row[self.row_idx].is_null() {
+            return;
+        }
+        
         self.curr_count += 1;
     }
 
@@ -43,9 +50,21 @@ impl Aggregate for CountAggregate {
         return Data::Integer(to_r as i64);
     }
 
-    fn out_type(&self, _in_type: &DataType) -> DataType {
+    fn out_type(&self, _in_types: &[DataType]) -> DataType {
         return DataType::INTEGER;
     }
+
+    fn partial_state(&mut self) -> Vec<Data> {
+        return vec![self.produce()];
+    }
+
+    fn merge(&mut self, state: &[Data]) {
+        self.curr_count += state[0].as_i64() as usize;
+    }
+
+    fn state_types(&self, _in_types: &[DataType]) -> Vec<DataType> {
+        return vec![DataType::INTEGER];
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,320 @@
+// < begin copyright > 
+// Copyright Ryan Marcus 2018
+// 
+// This file is part of byoo.
+// 
+// byoo is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// byoo is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with byoo.  If not, see <http://www.gnu.org/licenses/>.
+// 
+// < end copyright > 
+use agg::{Aggregate, SpilledChunks, pack_values, unpack_values, value_size};
+use data::{Data, DataType};
+use spillable_store::WritableSpillableStore;
+use hash_partition_store::{ReadableHashPartitionStore, MAX_FILES};
+use operator_buffer::OperatorReadBuffer;
+use std::collections::HashSet;
+
+// the number of distinct values a group keeps in memory before spilling
+const DEFAULT_MAX_IN_MEMORY: usize = 1 << 16;
+
+// how many times a partition of spilled values with too many distinct
+// values is split up before it is de-duplicated in memory regardless
+const MAX_REPARTITION_DEPTH: u64 = 8;
+
+/// Applies another aggregate (e.g., count or sum) to the distinct
+/// non-NULL values of a column. Values are collected in a hash set, which
+/// is spilled to disk when it grows too large. The spilled values may
+/// contain duplicates, so they are hash partitioned and de-duplicated one
+/// partition at a time when the group is produced, recursively splitting
+/// up any partition with too many distinct values.
+pub struct DistinctAggregate {
+    row_idx: usize,
+    inner: Box<dyn Aggregate + Send>,
+    seen: HashSet<Data>,
+    seen_size: usize,
+    spilled: Option<WritableSpillableStore>,
+    spilled_chunks: Option<SpilledChunks>,
+    max_in_memory: usize
+}
+
+impl DistinctAggregate {
+    pub fn new(row_idx: usize, inner: Box<dyn Aggregate + Send>) -> DistinctAggregate {
+        return DistinctAggregate::with_max_in_memory(row_idx, inner,
+                                                     DEFAULT_MAX_IN_MEMORY);
+    }
+
+    pub fn with_max_in_memory(row_idx: usize, inner: Box<dyn Aggregate + Send>,
+                              max_in_memory: usize) -> DistinctAggregate {
+        return DistinctAggregate {
+            row_idx, inner, max_in_memory,
+            seen: HashSet::new(),
+            seen_size: 0,
+            spilled: None,
+            spilled_chunks: None
+        };
+    }
+
+    fn spill(&mut self) {
+        if self.spilled.is_none() {
+            let dt = self.seen.iter().next().unwrap().data_type().unwrap();
+            self.spilled = Some(WritableSpillableStore::new(4096, vec![dt]));
+        }
+
+        let store = self.spilled.as_mut().unwrap();
+        for d in self.seen.drain() {
+            store.push_row(&[d]);
+        }
+    }
+
+    fn update(&mut self, nxt: &Data) {
+        if nxt.is_null() || self.seen.contains(nxt) {
+            return;
+        }
+
+        if self.seen.is_empty() {
+            self.seen_size = 0;
+        }
+
+        self.seen_size += value_size(nxt);
+        self.seen.insert(nxt.clone());
+
+        if self.seen.len() >= self.max_in_memory {
+            self.spill();
+        }
+    }
+
+    // Passes the distinct values of a partition of spilled values to the
+    // inner aggregate. Once the partition has more than `max_in_memory`
+    // distinct values, the rest of it is re-partitioned with a different
+    // seed and each of those partitions is de-duplicated on its own.
+    fn consume_partition(&mut self, mut part: OperatorReadBuffer, depth: u64) {
+        let types = part.types().to_vec();
+        let mut distinct = HashSet::new();
+        let mut rest: Option<WritableSpillableStore> = None;
+        
+        iterate_buffer!(part, row, {
+            if let Some(ref mut store) = rest {
+                store.push_row(row);
+                continue;
+            }
+
+            distinct.insert(row[0].clone());
+            if distinct.len() > self.max_in_memory && depth < MAX_REPARTITION_DEPTH {
+                let mut store = WritableSpillableStore::new(4096, types.clone());
+                for d in distinct.drain() {
+                    store.push_row(&[d]);
+                }
+                rest = Some(store);
+            }
+        });
+
+        let store = match rest {
+            Some(store) => store,
+            None => {
+                for d in distinct {
+                    self.inner.consume(&[d]);
+                }
+                return;
+            }
+        };
+
+        let (stats, buf) = store.into_read_buffer();
+        let num_partitions = (stats.rows / self.max_in_memory + 1).clamp(2, MAX_FILES);
+        let mut partitions = ReadableHashPartitionStore::with_seeded_partitions(
+            num_partitions, 4096, buf, &[0], depth + 1);
+
+        while let Some(part) = partitions.next_buf() {
+            self.consume_partition(part, depth + 1);
+        }
+    }
+}
+
+impl Aggregate for DistinctAggregate {
+    fn consume(&mut self, row: &[Data]) {
+        self.update(&row[self.row_idx]);
+    }
+
+    fn produce(&mut self) -> Data {
+        let mut store = match self.spilled.take() {
+            Some(store) => store,
+            None => {
+                for d in self.seen.drain() {
+                    self.inner.consume(&[d]);
+                }
+                return self.inner.produce();
+            }
+        };
+
+        for d in self.seen.drain() {
+            store.push_row(&[d]);
+        }
+
+        // equal values end up in the same partition
+        let (_, buf) = store.into_read_buffer();
+        let mut partitions = ReadableHashPartitionStore::new(
+            self.max_in_memory, buf, &[0]);
+
+        while let Some(part) = partitions.next_buf() {
+            self.consume_partition(part, 0);
+        }
+
+        return self.inner.produce();
+    }
+
+    fn out_type(&self, in_types: &[DataType]) -> DataType {
+        // the inner aggregate sees a single column of distinct values
+        return self.inner.out_type(&in_types[self.row_idx..=self.row_idx]);
+    }
+
+    fn partial_state(&mut self) -> Vec<Data> {
+        // the state is every distinct value seen (spilled values may
+        // repeat, but merging removes them again). Unless they were
+        // already exported by spilled_state, spilled values are included.
+        let mut values: Vec<Data> = self.seen.drain().collect();
+        if let Some(store) = self.spilled.take() {
+            let (_, mut buf) = store.into_read_buffer();
+            iterate_buffer!(buf, row, {
+                values.push(row[0].clone());
+            });
+        }
+
+        return vec![pack_values(&values)];
+    }
+
+    fn spilled_state(&mut self) -> Option<Vec<Data>> {
+        if let Some(store) = self.spilled.take() {
+            self.spilled_chunks = Some(SpilledChunks::new(store, self.max_in_memory));
+        }
+
+        let chunk = self.spilled_chunks.as_mut()?.next_chunk(&[0]);
+        if chunk.is_none() {
+            self.spilled_chunks = None;
+        }
+
+        return chunk.map(|values| vec![values]);
+    }
+
+    fn merge(&mut self, state: &[Data]) {
+        for d in unpack_values(&state[0]) {
+            self.update(&d);
+        }
+    }
+
+    fn state_types(&self, _in_types: &[DataType]) -> Vec<DataType> {
+        return vec![DataType::BLOB];
+    }
+
+    fn state_size(&self) -> usize {
+        // the size of the values seen since the set was last emptied
+        let seen_size = if self.seen.is_empty() { 0 } else { self.seen_size };
+        return seen_size + self.inner.state_size();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use agg::{Aggregate, new, unpack_values};
+    use agg::distinct::DistinctAggregate;
+    use agg::count::CountAggregate;
+    use agg::sum::SumAggregate;
+    use data::Data;
+
+    #[test]
+    fn simple_test() {
+        let mut aggs = vec![new("count distinct", 0),
+                            new("sum distinct", 0),
+                            new("avg distinct", 0)];
+
+        for v in &[5, 10, 5, 5, 15] {
+            for agg in aggs.iter_mut() {
+                agg.consume(&[Data::Integer(*v)]);
+                agg.consume(&[Data::Null]);
+            }
+        }
+
+        assert_eq!(aggs[0].produce(), Data::Integer(3));
+        assert_eq!(aggs[1].produce(), Data::Integer(30));
+        assert_eq!(aggs[2].produce(), Data::Real(10.0));
+
+        // the state resets between groups
+        aggs[0].consume(&[Data::Integer(1)]);
+        assert_eq!(aggs[0].produce(), Data::Integer(1));
+        assert_eq!(aggs[1].produce(), Data::Null);
+    }
+
+    #[test]
+    fn spill_test() {
+        let mut count = DistinctAggregate::with_max_in_memory(
+            0, Box::new(CountAggregate::new(0)), 100);
+        let mut sum = DistinctAggregate::with_max_in_memory(
+            0, Box::new(SumAggregate::new(0)), 100);
+
+        for _ in 0..3 {
+            for i in 0..5000 {
+                count.consume(&[Data::Integer(i)]);
+                sum.consume(&[Data::Integer(i)]);
+            }
+        }
+
+        assert!(count.spilled.is_some());
+        assert_eq!(count.produce(), Data::Integer(5000));
+        assert_eq!(sum.produce(), Data::Integer(4999 * 5000 / 2));
+
+        count.consume(&[Data::Integer(7)]);
+        assert!(count.spilled.is_none());
+        assert_eq!(count.produce(), Data::Integer(1));
+    }
+
+    #[test]
+    fn repartition_test() {
+        // every partition of the spilled values has more than 10 distinct
+        // values, so they all have to be split up again
+        let mut count = DistinctAggregate::with_max_in_memory(
+            0, Box::new(CountAggregate::new(0)), 10);
+
+        for _ in 0..2 {
+            for i in 0..20_000 {
+                count.consume(&[Data::Integer(i)]);
+            }
+        }
+
+        assert_eq!(count.produce(), Data::Integer(20_000));
+    }
+
+    #[test]
+    fn spilled_state_test() {
+        let new_count = || DistinctAggregate::with_max_in_memory(
+            0, Box::new(CountAggregate::new(0)), 100);
+
+        let mut count = new_count();
+        for _ in 0..3 {
+            for i in 0..5000 {
+                count.consume(&[Data::Integer(i)]);
+            }
+        }
+
+        // spilled values are exported in chunks of at most 100 values,
+        // rather than all at once
+        let mut merged = new_count();
+        let mut num_chunks = 0;
+        while let Some(state) = count.spilled_state() {
+            assert!(unpack_values(&state[0]).len() <= 100);
+            merged.merge(&state);
+            num_chunks += 1;
+        }
+        assert!(num_chunks > 1);
+        
+        merged.merge(&count.partial_state());
+        assert_eq!(merged.produce(), Data::Integer(5000));
+    }
+}
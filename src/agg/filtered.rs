@@ -0,0 +1,104 @@
+// < begin copyright > 
+// Copyright Ryan Marcus 2018
+// 
+// This file is part of byoo.
+// 
+// byoo is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// byoo is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with byoo.  If not, see <http://www.gnu.org/licenses/>.
+// 
+// < end copyright > 
+use agg::Aggregate;
+use data::{Data, DataType};
+use predicate::Predicate;
+use std::sync::Arc;
+
+/// Applies another aggregate only to the rows where a predicate holds,
+/// like SQL's `agg(...) FILTER (WHERE ...)`. The predicate refers to the
+/// columns of the group by's input, and is shared by the aggregates of
+/// every group.
+pub struct FilteredAggregate {
+    filter: Arc<Predicate>,
+    inner: Box<dyn Aggregate + Send>
+}
+
+impl FilteredAggregate {
+    pub fn new(filter: Arc<Predicate>, inner: Box<dyn Aggregate + Send>) -> FilteredAggregate {
+        return FilteredAggregate { filter, inner };
+    }
+}
+
+impl Aggregate for FilteredAggregate {
+    fn consume(&mut self, row: &[Data]) {
+        if self.filter.eval(row) {
+            self.inner.consume(row);
+        }
+    }
+
+    fn produce(&mut self) -> Data {
+        return self.inner.produce();
+    }
+
+    fn out_type(&self, in_types: &[DataType]) -> DataType {
+        return self.inner.out_type(in_types);
+    }
+
+    // rows are filtered before they reach the inner aggregate, so its
+    // state can be merged as is
+    fn partial_state(&mut self) -> Vec<Data> {
+        return self.inner.partial_state();
+    }
+
+    fn spilled_state(&mut self) -> Option<Vec<Data>> {
+        return self.inner.spilled_state();
+    }
+
+    fn merge(&mut self, state: &[Data]) {
+        self.inner.merge(state);
+    }
+
+    fn state_types(&self, in_types: &[DataType]) -> Vec<DataType> {
+        return self.inner.state_types(in_types);
+    }
+
+    fn state_size(&self) -> usize {
+        return self.inner.state_size();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use agg::{Aggregate, from_json};
+    use data::{Data, DataType};
+
+    #[test]
+    fn filtered_count_test() {
+        let mut kind1 = from_json(&json!({"op": "count", "col": 0,
+                                          "filter": {"op": "eq", "col": 1, "val": 1}}));
+        let mut kind2 = from_json(&json!({"op": "sum", "col": 0,
+                                          "filter": "t.1 = 2"}));
+        let mut all = from_json(&json!({"op": "count", "col": 0}));
+
+        for i in 0..30 {
+            let row = [Data::Integer(i), Data::Integer(i % 3)];
+            kind1.consume(&row);
+            kind2.consume(&row);
+            all.consume(&row);
+        }
+
+        assert_eq!(kind1.produce(), Data::Integer(10));
+        assert_eq!(kind2.produce(), Data::Integer((0..30).filter(|i| i % 3 == 2).sum()));
+        assert_eq!(all.produce(), Data::Integer(30));
+        assert_matches!(kind2.out_type(&[DataType::INTEGER, DataType::INTEGER]),
+                        DataType::INTEGER);
+    }
+}
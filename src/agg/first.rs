@@ -0,0 +1,225 @@
+// < begin copyright > 
+// Copyright Ryan Marcus 2018
+// 
+// This file is part of byoo.
+// 
+// byoo is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// byoo is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with byoo.  If not, see <http://www.gnu.org/licenses/>.
+// 
+// < end copyright > 
+use agg::Aggregate;
+use data::{Data, DataType};
+use std::cmp::Ordering;
+
+#[derive(Clone, Copy)]
+pub enum PickKind {
+    // the value of the first row of the group, even if it is NULL
+    First,
+
+    // the value of the last row of the group, even if it is NULL
+    Last,
+
+    // some non-NULL value of the group (currently, the first one)
+    AnyValue
+}
+
+/// Picks a single value of a column from each group.
+pub struct PickAggregate {
+    row_idx: usize,
+    kind: PickKind,
+    curr: Option<Data>
+}
+
+impl PickAggregate {
+    pub fn new(row_idx: usize, kind: PickKind) -> PickAggregate {
+        return PickAggregate {
+            row_idx, kind,
+            curr: None
+        };
+    }
+
+    fn update(&mut self, nxt: &Data) {
+        match self.kind {
+            PickKind::First if self.curr.is_some() => {},
+            PickKind::AnyValue if self.curr.is_some() || nxt.is_null() => {},
+            _ => { self.curr = Some(nxt.clone()); }
+        };
+    }
+}
+
+impl Aggregate for PickAggregate {
+    fn consume(&mut self, row: &[Data]) {
+        self.update(&row[self.row_idx]);
+    }
+
+    fn produce(&mut self) -> Data {
+        return self.curr.take().unwrap_or(Data::Null);
+    }
+
+    fn out_type(&self, in_types: &[DataType]) -> DataType {
+        return in_types[self.row_idx].clone();
+    }
+
+    fn partial_state(&mut self) -> Vec<Data> {
+        // the picked value may be NULL, so whether or not there is one is
+        // part of the state
+        return match self.curr.take() {
+            Some(d) => vec![Data::Integer(1), d],
+            None => vec![Data::Integer(0), Data::Null]
+        };
+    }
+
+    fn merge(&mut self, state: &[Data]) {
+        if state[0].as_i64() != 0 {
+            self.update(&state[1]);
+        }
+    }
+
+    fn state_types(&self, in_types: &[DataType]) -> Vec<DataType> {
+        return vec![DataType::INTEGER, in_types[self.row_idx].clone()];
+    }
+}
+
+/// Produces the value of one column at the row where another column is
+/// smallest (`arg_min`) or largest (`arg_max`). Rows where the compared
+/// column is NULL are skipped, and ties keep the first row.
+pub struct ArgAggregate {
+    cmp_idx: usize,
+    val_idx: usize,
+    keep: Ordering,
+    curr: Option<(Data, Data)>
+}
+
+impl ArgAggregate {
+    pub fn arg_min(cmp_idx: usize, val_idx: usize) -> ArgAggregate {
+        return ArgAggregate {
+            cmp_idx, val_idx,
+            keep: Ordering::Less,
+            curr: None
+        };
+    }
+
+    pub fn arg_max(cmp_idx: usize, val_idx: usize) -> ArgAggregate {
+        return ArgAggregate {
+            cmp_idx, val_idx,
+            keep: Ordering::Greater,
+            curr: None
+        };
+    }
+
+    fn update(&mut self, nxt: &Data, val: &Data) {
+        if nxt.is_null() {
+            return;
+        }
+
+        let replace = match self.curr {
+            None => true,
+            Some((ref best, _)) => nxt.partial_cmp(best).unwrap() == self.keep
+        };
+
+        if replace {
+            self.curr = Some((nxt.clone(), val.clone()));
+        }
+    }
+}
+
+impl Aggregate for ArgAggregate {
+    fn consume(&mut self, row: &[Data]) {
+        self.update(&row[self.cmp_idx], &row[self.val_idx]);
+    }
+
+    fn produce(&mut self) -> Data {
+        return self.curr.take().map(|(_, v)| v).unwrap_or(Data::Null);
+    }
+
+    fn out_type(&self, in_types: &[DataType]) -> DataType {
+        return in_types[self.val_idx].clone();
+    }
+
+    fn partial_state(&mut self) -> Vec<Data> {
+        let (best, val) = self.curr.take().unwrap_or((Data::Null, Data::Null));
+        return vec![best, val];
+    }
+
+    fn merge(&mut self, state: &[Data]) {
+        self.update(&state[0], &state[1]);
+    }
+
+    fn state_types(&self, in_types: &[DataType]) -> Vec<DataType> {
+        return vec![in_types[self.cmp_idx].clone(), in_types[self.val_idx].clone()];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use agg::{Aggregate, new, new_with_cols};
+    use data::{Data, DataType};
+
+    fn text(s: &str) -> Data {
+        return Data::Text(String::from(s));
+    }
+
+    #[test]
+    fn pick_test() {
+        let mut aggs = vec![new("first", 0), new("last", 0), new("any_value", 0)];
+
+        let data = vec![Data::Null, Data::Integer(5), Data::Integer(-2), Data::Null];
+        for agg in aggs.iter_mut() {
+            for d in data.iter() {
+                agg.consume(&[d.clone()]);
+            }
+        }
+
+        assert_eq!(aggs[0].produce(), Data::Null);
+        assert_eq!(aggs[1].produce(), Data::Null);
+        assert_eq!(aggs[2].produce(), Data::Integer(5));
+
+        // the state resets between groups
+        for agg in aggs.iter_mut() {
+            agg.consume(&[Data::Integer(1)]);
+            agg.consume(&[Data::Integer(2)]);
+        }
+        
+        assert_eq!(aggs[0].produce(), Data::Integer(1));
+        assert_eq!(aggs[1].produce(), Data::Integer(2));
+        assert_eq!(aggs[2].produce(), Data::Integer(1));
+        assert_eq!(aggs[2].produce(), Data::Null);
+    }
+
+    #[test]
+    fn arg_test() {
+        let mut arg_min = new_with_cols("arg_min", &[0, 1]);
+        let mut arg_max = new_with_cols("arg_max", &[0, 1]);
+
+        let data = vec![
+            vec![Data::Integer(5), text("five")],
+            vec![Data::Null, text("null")],
+            vec![Data::Integer(-2), text("negative two")],
+            vec![Data::Integer(9), Data::Null],
+            vec![Data::Integer(-2), text("negative two again")],
+        ];
+
+        for row in data.iter() {
+            arg_min.consume(row);
+            arg_max.consume(row);
+        }
+
+        assert_eq!(arg_min.produce(), text("negative two"));
+        assert_eq!(arg_max.produce(), Data::Null);
+        assert_eq!(arg_min.produce(), Data::Null);
+
+        let types = [DataType::INTEGER, DataType::TEXT];
+        assert_matches!(arg_min.out_type(&types), DataType::TEXT);
+        assert_matches!(new("last", 0).out_type(&types), DataType::INTEGER);
+    }
+}
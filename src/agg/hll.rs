@@ -0,0 +1,540 @@
+// < begin copyright > 
+// Copyright Ryan Marcus 2018
+// 
+// This file is part of byoo.
+// 
+// byoo is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// byoo is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with byoo.  If not, see <http://www.gnu.org/licenses/>.
+// 
+// < end copyright > 
+use agg::Aggregate;
+use data::{Data, DataType};
+use decimal;
+use fnv::FnvHasher;
+use std::hash::Hasher;
+
+pub const DEFAULT_PRECISION: u8 = 14;
+const MIN_PRECISION: u8 = 4;
+const MAX_PRECISION: u8 = 16;
+
+/// A HyperLogLog sketch estimating the number of distinct values inserted
+/// into it. The sketch has 2^precision one byte registers, and the
+/// relative error of the estimate is about 1.04 / sqrt(2^precision).
+///
+/// Values are hashed with FNV over a canonical little-endian encoding (and
+/// then mixed, since FNV's high bits are poor for small integers), so
+/// sketches built by different runs or on different platforms agree and
+/// can be merged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HyperLogLog {
+    precision: u8,
+    registers: Vec<u8>
+}
+
+impl HyperLogLog {
+    pub fn new(precision: u8) -> HyperLogLog {
+        assert!((MIN_PRECISION..=MAX_PRECISION).contains(&precision),
+                "HyperLogLog precision must be between {} and {}, got {}",
+                MIN_PRECISION, MAX_PRECISION, precision);
+        
+        return HyperLogLog {
+            precision,
+            registers: vec![0 ; 1 << precision]
+        };
+    }
+
+    /// The memory used by the registers.
+    pub fn num_bytes(&self) -> usize {
+        return self.registers.len();
+    }
+
+    /// Reads a sketch written by `to_bytes`: the precision, followed by
+    /// the registers. Describes why the bytes are not a valid sketch,
+    /// e.g. a BLOB that was not written by `hll_sketch`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<HyperLogLog, String> {
+        let precision = *bytes.first()
+            .ok_or_else(|| String::from("empty HyperLogLog sketch"))?;
+        if !(MIN_PRECISION..=MAX_PRECISION).contains(&precision) {
+            return Err(format!("HyperLogLog sketch has precision {}, expected {} to {}",
+                               precision, MIN_PRECISION, MAX_PRECISION));
+        }
+
+        let mut hll = HyperLogLog::new(precision);
+        if bytes.len() - 1 != hll.registers.len() {
+            return Err(format!("HyperLogLog sketch of precision {} has {} registers, expected {}",
+                               precision, bytes.len() - 1, hll.registers.len()));
+        }
+
+        // a rank counts the bits after the register index, plus one
+        let max_rank = 64 - precision + 1;
+        if let Some(r) = bytes[1..].iter().find(|&&r| r > max_rank) {
+            return Err(format!("HyperLogLog sketch of precision {} has a register of {}, \
+                                but registers are at most {}", precision, r, max_rank));
+        }
+        
+        hll.registers.copy_from_slice(&bytes[1..]);
+        return Ok(hll);
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.registers.len() + 1);
+        bytes.push(self.precision);
+        bytes.extend_from_slice(&self.registers);
+        return bytes;
+    }
+
+    pub fn insert(&mut self, d: &Data) {
+        self.insert_hash(mix(hash_value(d)));
+    }
+
+    fn insert_hash(&mut self, hash: u64) {
+        // the top bits pick the register, and the register keeps the
+        // largest position of the first one bit among the rest
+        let idx = (hash >> (64 - self.precision)) as usize;
+        let rest = (hash << self.precision) | (1 << (self.precision - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+
+        if rank > self.registers[idx] {
+            self.registers[idx] = rank;
+        }
+    }
+
+    /// Merges another sketch into this one. Sketches with different
+    /// precisions are merged at the lower of the two.
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        if other.precision < self.precision {
+            *self = self.fold(other.precision);
+        }
+
+        if other.precision > self.precision {
+            self.merge(&other.fold(self.precision));
+            return;
+        }
+
+        for (r, o) in self.registers.iter_mut().zip(other.registers.iter()) {
+            if *o > *r {
+                *r = *o;
+            }
+        }
+    }
+
+    // Reduces the precision of the sketch. The index bits that are
+    // dropped become the leading bits of the rest of the hash, so the
+    // ranks can be recomputed exactly.
+    fn fold(&self, precision: u8) -> HyperLogLog {
+        assert!(precision <= self.precision);
+        let dropped_bits = self.precision - precision;
+        let mask = (1 << dropped_bits) - 1;
+        
+        let mut folded = HyperLogLog::new(precision);
+        for (idx, &rank) in self.registers.iter().enumerate() {
+            if rank == 0 {
+                continue;
+            }
+
+            let dropped = (idx & mask) as u64;
+            let new_rank = if dropped == 0 {
+                rank + dropped_bits
+            } else {
+                (dropped << (64 - dropped_bits)).leading_zeros() as u8 + 1
+            };
+
+            let new_idx = idx >> dropped_bits;
+            if new_rank > folded.registers[new_idx] {
+                folded.registers[new_idx] = new_rank;
+            }
+        }
+
+        return folded;
+    }
+
+    pub fn estimate(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m)
+        };
+
+        let mut sum = 0.0;
+        let mut zeros = 0;
+        for &r in self.registers.iter() {
+            sum += 2.0f64.powi(-(r as i32));
+            if r == 0 {
+                zeros += 1;
+            }
+        }
+
+        let raw = alpha * m * m / sum;
+
+        // small cardinalities are estimated better by linear counting
+        if raw <= 2.5 * m && zeros > 0 {
+            return m * (m / zeros as f64).ln();
+        }
+
+        return raw;
+    }
+}
+
+// Hashes a type tag followed by a fixed byte encoding of the value. Unlike
+// `Data`'s `Hash` impl, which hashes REALs by their integer part, every
+// distinct value gets its own encoding. -0.0 is hashed as 0.0 and every NaN alike, since they
+// compare equal as data.
+fn hash_value(d: &Data) -> u64 {
+    let mut hasher = FnvHasher::default();
+    match d {
+        Data::Integer(i) => {
+            hasher.write_u8(1);
+            hasher.write(&i.to_le_bytes());
+        },
+        Data::Real(f) => {
+            let f = if *f == 0.0 {
+                0.0
+            } else if f.is_nan() {
+                f64::NAN
+            } else {
+                *f
+            };
+            
+            hasher.write_u8(2);
+            hasher.write(&f.to_bits().to_le_bytes());
+        },
+        Data::Text(t) => {
+            hasher.write_u8(3);
+            hasher.write(t.as_bytes());
+        },
+        Data::Blob(b) => {
+            hasher.write_u8(4);
+            hasher.write(b);
+        },
+        Data::Date(d) => {
+            hasher.write_u8(5);
+            hasher.write(&d.to_le_bytes());
+        },
+        Data::Timestamp(t) => {
+            hasher.write_u8(6);
+            hasher.write(&t.to_le_bytes());
+        },
+        Data::Decimal(v, s) => {
+            let (v, s) = decimal::normalize(*v, *s);
+            hasher.write_u8(7);
+            hasher.write(&v.to_le_bytes());
+            hasher.write_u8(s);
+        },
+        Data::Null => {
+            hasher.write_u8(0);
+        }
+    };
+    
+    return hasher.finish();
+}
+
+// the finalizer from MurmurHash3
+fn mix(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^= h >> 33;
+    return h;
+}
+
+/// Builds a sketch of the non-NULL values of a column, producing either
+/// the estimated number of distinct values (`approx_count_distinct`) or
+/// the sketch itself as a BLOB (`hll_sketch`).
+pub struct HllAggregate {
+    row_idx: usize,
+    precision: u8,
+    emit_sketch: bool,
+    sketch: HyperLogLog
+}
+
+impl HllAggregate {
+    pub fn new(row_idx: usize, precision: u8, emit_sketch: bool) -> HllAggregate {
+        return HllAggregate {
+            row_idx, precision, emit_sketch,
+            sketch: HyperLogLog::new(precision)
+        };
+    }
+}
+
+impl Aggregate for HllAggregate {
+    fn consume(&mut self, row: &[Data]) {
+        let nxt = &row[self.row_idx];
+        if nxt.is_null() {
+            return;
+        }
+
+        self.sketch.insert(nxt);
+    }
+
+    fn produce(&mut self) -> Data {
+        let sketch = std::mem::replace(&mut self.sketch,
+                                       HyperLogLog::new(self.precision));
+        if self.emit_sketch {
+            return Data::Blob(sketch.to_bytes());
+        }
+
+        return Data::Integer(sketch.estimate().round() as i64);
+    }
+
+    fn out_type(&self, _in_types: &[DataType]) -> DataType {
+        if self.emit_sketch {
+            return DataType::BLOB;
+        }
+        
+        return DataType::INTEGER;
+    }
+
+    fn partial_state(&mut self) -> Vec<Data> {
+        let sketch = std::mem::replace(&mut self.sketch,
+                                       HyperLogLog::new(self.precision));
+        return vec![Data::Blob(sketch.to_bytes())];
+    }
+
+    fn merge(&mut self, state: &[Data]) {
+        if let Data::Blob(ref b) = state[0] {
+            let other = HyperLogLog::from_bytes(b)
+                .unwrap_or_else(|e| panic!("Invalid approx_count_distinct state: {}", e));
+            self.sketch.merge(&other);
+        }
+    }
+
+    fn state_types(&self, _in_types: &[DataType]) -> Vec<DataType> {
+        return vec![DataType::BLOB];
+    }
+
+    fn state_size(&self) -> usize {
+        return self.sketch.num_bytes();
+    }
+}
+
+/// Merges a BLOB column of sketches (e.g., from `hll_sketch`), producing
+/// either the merged sketch (`merge_hll`) or its estimate
+/// (`merge_hll_count`). A group with no sketches produces NULL.
+pub struct MergeHllAggregate {
+    row_idx: usize,
+    emit_sketch: bool,
+    sketch: Option<HyperLogLog>
+}
+
+impl MergeHllAggregate {
+    pub fn new(row_idx: usize, emit_sketch: bool) -> MergeHllAggregate {
+        return MergeHllAggregate {
+            row_idx, emit_sketch,
+            sketch: None
+        };
+    }
+
+    fn update(&mut self, nxt: &Data) {
+        let other = match nxt {
+            Data::Blob(b) => HyperLogLog::from_bytes(b).unwrap_or_else(
+                |e| panic!("merge_hll on column {} got an invalid sketch: {}", self.row_idx, e)),
+            Data::Null => { return; },
+            d => panic!("merge_hll requires a BLOB column, got {:?}", d)
+        };
+
+        match self.sketch {
+            Some(ref mut sketch) => sketch.merge(&other),
+            None => { self.sketch = Some(other); }
+        };
+    }
+}
+
+impl Aggregate for MergeHllAggregate {
+    fn consume(&mut self, row: &[Data]) {
+        self.update(&row[self.row_idx]);
+    }
+
+    fn produce(&mut self) -> Data {
+        return match self.sketch.take() {
+            None => Data::Null,
+            Some(sketch) if self.emit_sketch => Data::Blob(sketch.to_bytes()),
+            Some(sketch) => Data::Integer(sketch.estimate().round() as i64)
+        };
+    }
+
+    fn out_type(&self, in_types: &[DataType]) -> DataType {
+        if let DataType::BLOB = in_types[self.row_idx] {
+        } else {
+            panic!("merge_hll requires a BLOB column, got {:?}", in_types[self.row_idx]);
+        }
+        
+        if self.emit_sketch {
+            return DataType::BLOB;
+        }
+        
+        return DataType::INTEGER;
+    }
+
+    fn partial_state(&mut self) -> Vec<Data> {
+        let state = self.sketch.take()
+            .map(|sketch| Data::Blob(sketch.to_bytes()))
+            .unwrap_or(Data::Null);
+        return vec![state];
+    }
+
+    fn merge(&mut self, state: &[Data]) {
+        self.update(&state[0]);
+    }
+
+    fn state_types(&self, _in_types: &[DataType]) -> Vec<DataType> {
+        return vec![DataType::BLOB];
+    }
+
+    fn state_size(&self) -> usize {
+        return self.sketch.as_ref().map(|s| s.num_bytes()).unwrap_or(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use agg::{Aggregate, new, from_json};
+    use agg::hll::HyperLogLog;
+    use data::{Data, DataType};
+
+    fn sketch_of(precision: u8, vals: &[i64]) -> HyperLogLog {
+        let mut hll = HyperLogLog::new(precision);
+        for v in vals {
+            hll.insert(&Data::Integer(*v));
+        }
+        return hll;
+    }
+
+    #[test]
+    fn estimate_test() {
+        let vals: Vec<i64> = (0..100_000).collect();
+        let hll = sketch_of(14, &vals);
+        assert_relative_eq!(hll.estimate(), 100_000.0, max_relative = 0.03);
+
+        // duplicates do not change the sketch
+        let mut dup = hll.clone();
+        for v in 0..1000 {
+            dup.insert(&Data::Integer(v));
+        }
+        assert_eq!(dup, hll);
+
+        // small cardinalities are (almost) exact
+        let small = sketch_of(14, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        assert_eq!(small.estimate().round(), 10.0);
+    }
+
+    #[test]
+    fn real_estimate_test() {
+        // REALs that share an integer part are still distinct values
+        let mut hll = HyperLogLog::new(14);
+        for v in 0..50_000 {
+            hll.insert(&Data::Real(v as f64 / 50_000.0));
+        }
+        assert_relative_eq!(hll.estimate(), 50_000.0, max_relative = 0.03);
+
+        let mut zeros = HyperLogLog::new(14);
+        zeros.insert(&Data::Real(0.0));
+        zeros.insert(&Data::Real(-0.0));
+        zeros.insert(&Data::Real(f64::NAN));
+        zeros.insert(&Data::Real(-f64::NAN));
+        assert_eq!(zeros.estimate().round(), 2.0);
+    }
+
+    #[test]
+    fn merge_test() {
+        let evens: Vec<i64> = (0..20_000).filter(|v| v % 2 == 0).collect();
+        let odds: Vec<i64> = (0..20_000).filter(|v| v % 2 == 1).collect();
+        let all: Vec<i64> = (0..20_000).collect();
+
+        let mut merged = sketch_of(12, &evens);
+        merged.merge(&sketch_of(12, &odds));
+        assert_eq!(merged, sketch_of(12, &all));
+
+        // merging with a more precise sketch folds it down
+        let mut merged = sketch_of(12, &evens);
+        merged.merge(&sketch_of(14, &odds));
+        assert_eq!(merged, sketch_of(12, &all));
+
+        let mut merged = sketch_of(14, &evens);
+        merged.merge(&sketch_of(10, &odds));
+        assert_eq!(merged.precision, 10);
+        assert_eq!(merged, sketch_of(10, &all));
+    }
+
+    #[test]
+    fn bytes_test() {
+        let hll = sketch_of(8, &[5, 6, 7]);
+        let bytes = hll.to_bytes();
+        assert_eq!(bytes.len(), 257);
+        assert_eq!(HyperLogLog::from_bytes(&bytes), Ok(hll));
+
+        assert_eq!(HyperLogLog::from_bytes(&[]).err().unwrap(), "empty HyperLogLog sketch");
+        assert_eq!(HyperLogLog::from_bytes(&[40, 1, 2]).err().unwrap(),
+                   "HyperLogLog sketch has precision 40, expected 4 to 16");
+        assert_eq!(HyperLogLog::from_bytes(&bytes[..100]).err().unwrap(),
+                   "HyperLogLog sketch of precision 8 has 99 registers, expected 256");
+
+        let mut bad_rank = bytes.clone();
+        bad_rank[1] = 58;
+        assert_eq!(HyperLogLog::from_bytes(&bad_rank).err().unwrap(),
+                   "HyperLogLog sketch of precision 8 has a register of 58, \
+                    but registers are at most 57");
+    }
+
+    #[test]
+    #[should_panic(expected = "merge_hll on column 0 got an invalid sketch: empty HyperLogLog sketch")]
+    fn bad_sketch_test() {
+        let mut merge = new("merge_hll", 0);
+        merge.consume(&[Data::Blob(Vec::new())]);
+    }
+
+    #[test]
+    fn aggregate_test() {
+        let mut count = new("approx_count_distinct", 0);
+        let mut sketch = from_json(&json!({"op": "hll_sketch", "col": 0, "precision": 10}));
+        let mut merge = new("merge_hll", 0);
+        let mut merge_count = new("merge_hll_count", 0);
+
+        assert_matches!(count.out_type(&[DataType::TEXT]), DataType::INTEGER);
+        assert_matches!(sketch.out_type(&[DataType::TEXT]), DataType::BLOB);
+
+        // two partitions of overlapping values
+        let mut sketches = Vec::new();
+        for part in 0..2 {
+            for v in 0..500 {
+                let d = Data::Text(format!("value {}", v + part * 250));
+                count.consume(&[d.clone()]);
+                sketch.consume(&[d]);
+            }
+            count.consume(&[Data::Null]);
+            sketches.push(sketch.produce());
+        }
+
+        let estimate = count.produce().as_i64();
+        assert!((estimate - 750).abs() < 20, "estimate was {}", estimate);
+
+        for s in sketches {
+            merge.consume(&[s.clone()]);
+            merge_count.consume(&[s]);
+        }
+        merge.consume(&[Data::Null]);
+
+        let merged = merge_count.produce().as_i64();
+        assert!((merged - 750).abs() < 50, "estimate was {}", merged);
+
+        if let Data::Blob(b) = merge.produce() {
+            assert_eq!(HyperLogLog::from_bytes(&b).unwrap().precision, 10);
+        } else {
+            panic!("merge_hll should produce a BLOB");
+        }
+
+        assert_eq!(merge.produce(), Data::Null);
+    }
+}
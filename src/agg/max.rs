@@ -33,11 +33,12 @@ impl MaxAggregate {
             curr_max: None
         };
     }
-}
 
-impl Aggregate for MaxAggregate {
-    fn consume(&mut self, row: &[Data]) {
-        let nxt = &row[self.row_idx];
+    fn update(&mut self, nxt: &Data) {
+        if nxt.is_null() {
+            return;
+        }
+
         let curr = self.curr_max.take();
         self.curr_max = match curr {
             None => Some(nxt.clone()),
@@ -50,13 +51,31 @@ impl Aggregate for MaxAggregate {
             }
         };
     }
+}
+
+impl Aggregate for MaxAggregate {
+    fn consume(&mut self, row: &[Data]) {
+        self.update(&row[self.row_idx]);
+    }
 
     fn produce(&mut self) -> Data {
-        return self.curr_max.take().unwrap();
+        return self.curr_max.take().unwrap_or(Data::Null);
+    }
+
+    fn out_type(&self, in_types: &[DataType]) -> DataType {
+        return in_types[self.row_idx].clone();
+    }
+
+    fn partial_state(&mut self) -> Vec<Data> {
+        return vec![self.produce()];
+    }
+
+    fn merge(&mut self, state: &[Data]) {
+        self.update(&state[0]);
     }
 
-    fn out_type(&self, in_type: &DataType) -> DataType {
-        return in_type.clone();
+    fn state_types(&self, in_types: &[DataType]) -> Vec<DataType> {
+        return vec![self.out_type(in_types)];
     }
 }
 
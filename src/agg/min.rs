@@ -33,11 +33,12 @@ impl MinAggregate {
             curr_min: None
         };
     }
-}
 
-impl Aggregate for MinAggregate {
-    fn consume(&mut self, row: &[Data]) {
-        let nxt = &row[self.row_idx];
+    fn update(&mut self, nxt: &Data) {
+        if nxt.is_null() {
+            return;
+        }
+
         let curr = self.curr_min.take();
         self.curr_min = match curr {
             None => Some(nxt.clone()),
@@ -50,13 +51,31 @@ impl Aggregate for MinAggregate {
             }
         };
     }
+}
+
+impl Aggregate for MinAggregate {
+    fn consume(&mut self, row: &[Data]) {
+        self.update(&row[self.row_idx]);
+    }
 
     fn produce(&mut self) -> Data {
-        return self.curr_min.take().unwrap();
+        return self.curr_min.take().unwrap_or(Data::Null);
+    }
+
+    fn out_type(&self, in_types: &[DataType]) -> DataType {
+        return in_types[self.row_idx].clone();
+    }
+
+    fn partial_state(&mut self) -> Vec<Data> {
+        return vec![self.produce()];
+    }
+
+    fn merge(&mut self, state: &[Data]) {
+        self.update(&state[0]);
     }
 
-    fn out_type(&self, in_type: &DataType) -> DataType {
-        return in_type.clone();
+    fn state_types(&self, in_types: &[DataType]) -> Vec<DataType> {
+        return vec![self.out_type(in_types)];
     }
 }
 
@@ -17,12 +17,119 @@
 // along with byoo.  If not, see <http://www.gnu.org/licenses/>.
 // 
 // < end copyright > 
-use data::{Data, DataType};
+use data::{Data, DataType, WriteByooDataExt, ReadByooDataExt};
+use byteorder::{ReadBytesExt, WriteBytesExt, LittleEndian};
+use serde_json;
+use std::mem;
+use operator_buffer::PeekableOperatorReadBuffer;
+use spillable_store::WritableSpillableStore;
 
 pub trait Aggregate {
     fn consume(&mut self, row: &[Data]);
     fn produce(&mut self) -> Data;
-    fn out_type(&self, in_type: &DataType) -> DataType;
+
+    /// The type of the produced values, given the types of the input rows.
+    fn out_type(&self, in_types: &[DataType]) -> DataType;
+
+    /// Exports the state of the aggregate (as values of `state_types`)
+    /// and resets it, like `produce`. The state of a group can be merged
+    /// into another aggregate built from the same JSON, so groups can be
+    /// partially aggregated before spilling or on several threads.
+    fn partial_state(&mut self) -> Vec<Data>;
+
+    /// Exports the part of the state that was spilled to disk one chunk
+    /// at a time, returning `None` once all of it has been exported.
+    /// Merging the chunks in order followed by `partial_state` is the same
+    /// as merging `partial_state` alone, but the spilled values are never
+    /// all read back into memory. Once called, it must be called until it
+    /// returns `None` before calling `partial_state`.
+    fn spilled_state(&mut self) -> Option<Vec<Data>> {
+        return None;
+    }
+
+    /// Combines a state exported by `partial_state` (or `spilled_state`)
+    /// into this aggregate.
+    fn merge(&mut self, state: &[Data]);
+
+    /// The types of the values exported by `partial_state`, given the
+    /// types of the input rows.
+    fn state_types(&self, in_types: &[DataType]) -> Vec<DataType>;
+
+    /// An estimate of the memory (in bytes) used by the values this
+    /// aggregate has collected, beyond its fixed size, so that a group by
+    /// can keep its groups within a memory budget. Most aggregates have a
+    /// fixed size state.
+    fn state_size(&self) -> usize {
+        return 0;
+    }
+}
+
+/// The estimated memory used by a value an aggregate keeps.
+fn value_size(d: &Data) -> usize {
+    return mem::size_of::<Data>() + d.num_bytes();
+}
+
+/// Packs a list of values into a BLOB, for aggregates whose state is a
+/// collection of values. Each value is prefixed by its type code.
+fn pack_values<'a, I>(values: I) -> Data
+where I: IntoIterator<Item = &'a Data> {
+    let mut bytes = Vec::new();
+    for d in values {
+        let code = d.data_type().unwrap_or(DataType::INTEGER).to_code();
+        bytes.write_u16::<LittleEndian>(code).unwrap();
+        bytes.write_data(d).unwrap();
+    }
+
+    return Data::Blob(bytes);
+}
+
+/// The rows an aggregate spilled to disk, read back a bounded number at a
+/// time so that they can be exported as several partial states.
+struct SpilledChunks {
+    rows: PeekableOperatorReadBuffer,
+    chunk_size: usize
+}
+
+impl SpilledChunks {
+    fn new(store: WritableSpillableStore, chunk_size: usize) -> SpilledChunks {
+        let (_, buf) = store.into_read_buffer();
+        return SpilledChunks {
+            rows: PeekableOperatorReadBuffer::new(buf),
+            chunk_size
+        };
+    }
+
+    // packs the given columns of (up to) the next chunk_size rows
+    fn next_chunk(&mut self, cols: &[usize]) -> Option<Data> {
+        let mut values = Vec::new();
+        for _ in 0..self.chunk_size {
+            match self.rows.pop() {
+                Some(row) => values.extend(cols.iter().map(|&c| row[c].clone())),
+                None => { break; }
+            };
+        }
+
+        if values.is_empty() {
+            return None;
+        }
+
+        return Some(pack_values(&values));
+    }
+}
+
+fn unpack_values(packed: &Data) -> Vec<Data> {
+    let mut bytes: &[u8] = match packed {
+        Data::Blob(b) => b,
+        _ => panic!("Aggregate state should be a BLOB, got {:?}", packed)
+    };
+
+    let mut values = Vec::new();
+    while !bytes.is_empty() {
+        let dt = DataType::from_code(bytes.read_u16::<LittleEndian>().unwrap());
+        values.push(bytes.read_data(&dt).unwrap());
+    }
+
+    return values;
 }
 
 mod min;
@@ -30,6 +137,26 @@ mod max;
 mod count;
 mod sum;
 mod average;
+mod distinct;
+mod variance;
+mod percentile;
+mod hll;
+mod tdigest;
+mod first;
+mod string_agg;
+mod filtered;
+
+use agg::variance::{VarianceAggregate, VarianceKind,
+                    CovarianceAggregate, CovarianceKind};
+use agg::percentile::{PercentileAggregate, PercentileKind};
+use agg::hll::{HllAggregate, MergeHllAggregate};
+use agg::tdigest::{TDigestAggregate, MergeTDigestAggregate};
+use agg::first::{PickAggregate, PickKind, ArgAggregate};
+use agg::string_agg::StringAggAggregate;
+use agg::sum::SumMode;
+use agg::filtered::FilteredAggregate;
+use predicate::Predicate;
+use std::sync::Arc;
 
 pub fn new(agg_type: &str, row_idx: usize) -> Box<Aggregate + Send> {
     return match agg_type {
@@ -38,15 +165,233 @@ pub fn new(agg_type: &str, row_idx: usize) -> Box<Aggregate + Send> {
         "avg" => Box::new(average::AverageAggregate::new(row_idx)),
         "count" => Box::new(count::CountAggregate::new(row_idx)),
         "sum" => Box::new(sum::SumAggregate::new(row_idx)),
+        "first" => Box::new(PickAggregate::new(row_idx, PickKind::First)),
+        "last" => Box::new(PickAggregate::new(row_idx, PickKind::Last)),
+        "any_value" => Box::new(PickAggregate::new(row_idx, PickKind::AnyValue)),
+        "string_agg" | "group_concat" => Box::new(StringAggAggregate::new(
+            row_idx, string_agg::DEFAULT_SEPARATOR, None)),
+        "count distinct" => Box::new(distinct::DistinctAggregate::new(
+            row_idx, Box::new(count::CountAggregate::new(0)))),
+        "sum distinct" => Box::new(distinct::DistinctAggregate::new(
+            row_idx, Box::new(sum::SumAggregate::new(0)))),
+        "avg distinct" => Box::new(distinct::DistinctAggregate::new(
+            row_idx, Box::new(average::AverageAggregate::new(0)))),
+        "var_samp" | "variance" => Box::new(VarianceAggregate::new(
+            row_idx, VarianceKind::VarSamp)),
+        "var_pop" => Box::new(VarianceAggregate::new(row_idx, VarianceKind::VarPop)),
+        "stddev_samp" | "stddev" => Box::new(VarianceAggregate::new(
+            row_idx, VarianceKind::StddevSamp)),
+        "stddev_pop" => Box::new(VarianceAggregate::new(row_idx, VarianceKind::StddevPop)),
+        "median" => Box::new(PercentileAggregate::new(
+            row_idx, 0.5, PercentileKind::Continuous)),
+        "percentile" | "percentile_cont" | "percentile_disc" => {
+            panic!("Aggregate {} requires a fraction (p)", agg_type)
+        },
+        "approx_count_distinct" | "hll_sketch" => new_with_precision(
+            agg_type, row_idx, hll::DEFAULT_PRECISION),
+        "merge_hll" => Box::new(MergeHllAggregate::new(row_idx, true)),
+        "merge_hll_count" => Box::new(MergeHllAggregate::new(row_idx, false)),
+        "approx_quantiles" | "merge_tdigest" => new_quantile_sketch(
+            agg_type, row_idx, tdigest::DEFAULT_COMPRESSION, None),
+        "covar" | "covar_samp" | "covar_pop" | "corr" | "arg_min" | "arg_max" => {
+            panic!("Aggregate {} requires two columns (cols)", agg_type)
+        },
         _ => panic!("Got unknown aggregate type {}", agg_type)
     }
 }
 
+/// Creates an aggregate over multiple columns, such as `corr`. Single
+/// column aggregates may also be given a one element list. For `arg_min`
+/// and `arg_max`, the first column is compared and the second is
+/// produced.
+pub fn new_with_cols(agg_type: &str, cols: &[usize]) -> Box<dyn Aggregate + Send> {
+    if cols.len() == 1 {
+        return new(agg_type, cols[0]);
+    }
+
+    assert_eq!(cols.len(), 2, "Aggregate {} given {} columns", agg_type, cols.len());
+    match agg_type {
+        "arg_min" => { return Box::new(ArgAggregate::arg_min(cols[0], cols[1])); },
+        "arg_max" => { return Box::new(ArgAggregate::arg_max(cols[0], cols[1])); },
+        _ => {}
+    };
+
+    let kind = match agg_type {
+        "covar" | "covar_samp" => CovarianceKind::CovarSamp,
+        "covar_pop" => CovarianceKind::CovarPop,
+        "corr" => CovarianceKind::Corr,
+        _ => panic!("Aggregate {} does not take two columns", agg_type)
+    };
+
+    return Box::new(CovarianceAggregate::new(cols[0], cols[1], kind));
+}
+
+/// Creates an aggregate from its plan JSON, such as `{"op": "sum", "col":
+/// 1}` or `{"op": "corr", "cols": [1, 2]}`.
+pub fn from_json(json: &serde_json::Value) -> Box<dyn Aggregate + Send> {
+    return AggregateSpec::from_json(json).build();
+}
+
+/// An aggregate's plan JSON with its filter already parsed, for creating
+/// many instances of the same aggregate (e.g., one for every group)
+/// without parsing the filter again each time.
+#[derive(Clone)]
+pub struct AggregateSpec {
+    json: serde_json::Value,
+    filter: Option<Arc<Predicate>>
+}
+
+impl AggregateSpec {
+    pub fn from_json(json: &serde_json::Value) -> AggregateSpec {
+        let filter = filter_from_json(json).map(|filter| {
+            return Arc::new(filter.unwrap_or_else(
+                |e| panic!("Invalid aggregate filter: {}", e)));
+        });
+
+        let mut json = json.clone();
+        if filter.is_some() {
+            json.as_object_mut().unwrap().remove("filter");
+        }
+
+        return AggregateSpec { json, filter };
+    }
+
+    pub fn build(&self) -> Box<dyn Aggregate + Send> {
+        let agg = unfiltered_from_json(&self.json);
+        return match self.filter {
+            Some(ref filter) => Box::new(FilteredAggregate::new(filter.clone(), agg)),
+            None => agg
+        };
+    }
+}
+
+fn unfiltered_from_json(json: &serde_json::Value) -> Box<dyn Aggregate + Send> {
+    let op = json["op"].as_str().expect("Aggregate requires an op");
+
+    if let Some(cols) = json.get("cols") {
+        let cols: Vec<usize> = cols.as_array().expect("Aggregate cols must be an array")
+            .iter()
+            .map(|v| v.as_i64().unwrap() as usize)
+            .collect();
+        return new_with_cols(op, &cols);
+    }
+
+    let col = json["col"].as_i64().expect("Aggregate requires a col or cols") as usize;
+    if let Some(mode) = mode_from_json(json).unwrap_or_else(|e| panic!("{}", e)) {
+        return match op {
+            "sum" => Box::new(sum::SumAggregate::with_mode(col, mode)),
+            "avg" => Box::new(average::AverageAggregate::with_mode(col, mode)),
+            "sum distinct" => Box::new(distinct::DistinctAggregate::new(
+                col, Box::new(sum::SumAggregate::with_mode(0, mode)))),
+            _ => Box::new(distinct::DistinctAggregate::new(
+                col, Box::new(average::AverageAggregate::with_mode(0, mode))))
+        };
+    }
+
+    if let "string_agg" | "group_concat" = op {
+        let separator = json.get("separator")
+            .map(|s| s.as_str().expect("Aggregate separator must be a string"))
+            .unwrap_or(string_agg::DEFAULT_SEPARATOR);
+        let order_by = json.get("order_by")
+            .map(|c| c.as_i64().expect("Aggregate order_by must be a column index") as usize);
+        return Box::new(StringAggAggregate::new(col, separator, order_by));
+    }
+
+    if let "approx_quantiles" | "merge_tdigest" = op {
+        let compression = json.get("compression")
+            .map(|c| c.as_f64().expect("Aggregate compression must be a number"))
+            .unwrap_or(tdigest::DEFAULT_COMPRESSION);
+        let p = json.get("p")
+            .map(|p| p.as_f64().expect("Aggregate p must be a number"));
+        return new_quantile_sketch(op, col, compression, p);
+    }
+    
+    if let Some(p) = json.get("p") {
+        let p = p.as_f64().expect("Aggregate p must be a number");
+        return new_with_fraction(op, col, p);
+    }
+
+    if let Some(precision) = json.get("precision") {
+        let precision = precision.as_u64().expect("Aggregate precision must be an integer");
+        return new_with_precision(op, col, precision as u8);
+    }
+
+    return new(op, col);
+}
+
+/// Reads the optional `"mode"` of a sum or average aggregate (with or
+/// without distinct), which is either `"exact"` or `"fast"`.
+pub fn mode_from_json(json: &serde_json::Value) -> Result<Option<SumMode>, String> {
+    let mode = match json.get("mode") {
+        Some(mode) => mode,
+        None => { return Ok(None); }
+    };
+
+    let op = json["op"].as_str().unwrap_or("");
+    if let "sum" | "avg" | "sum distinct" | "avg distinct" = op {
+        let mode = mode.as_str()
+            .ok_or_else(|| format!("Aggregate mode must be a string, got {}", mode))?;
+        return SumMode::from_str(mode).map(Some);
+    }
+
+    return Err(format!("Aggregate {} does not take a mode", op));
+}
+
+/// Reads the optional `"filter"` predicate of an aggregate (as a JSON
+/// tree or as text).
+pub fn filter_from_json(json: &serde_json::Value) -> Option<Result<Predicate, String>> {
+    return json.get("filter")
+        .map(|filter| Predicate::try_from_value(filter, None));
+}
+
+/// Creates a percentile aggregate, where `fraction` is between 0 and 1.
+pub fn new_with_fraction(agg_type: &str, row_idx: usize,
+                         fraction: f64) -> Box<dyn Aggregate + Send> {
+    let kind = match agg_type {
+        "percentile" | "percentile_cont" => PercentileKind::Continuous,
+        "percentile_disc" => PercentileKind::Discrete,
+        "approx_quantiles" | "merge_tdigest" => {
+            return new_quantile_sketch(agg_type, row_idx, tdigest::DEFAULT_COMPRESSION,
+                                       Some(fraction));
+        },
+        _ => panic!("Aggregate {} does not take a fraction", agg_type)
+    };
+
+    return Box::new(PercentileAggregate::new(row_idx, fraction, kind));
+}
+
+/// Creates a t-digest aggregate, which produces the estimated value at
+/// `quantile`, or the digest itself as a BLOB if no quantile is given.
+pub fn new_quantile_sketch(agg_type: &str, row_idx: usize, compression: f64,
+                           quantile: Option<f64>) -> Box<dyn Aggregate + Send> {
+    if let Some(q) = quantile {
+        assert!((0.0..=1.0).contains(&q), "Quantile must be between 0 and 1, got {}", q);
+    }
+    
+    return match agg_type {
+        "approx_quantiles" => Box::new(TDigestAggregate::new(row_idx, compression, quantile)),
+        "merge_tdigest" => Box::new(MergeTDigestAggregate::new(row_idx, quantile)),
+        _ => panic!("Aggregate {} is not a quantile sketch", agg_type)
+    };
+}
+
+/// Creates a HyperLogLog aggregate using 2^precision registers.
+pub fn new_with_precision(agg_type: &str, row_idx: usize,
+                          precision: u8) -> Box<dyn Aggregate + Send> {
+    return match agg_type {
+        "approx_count_distinct" => Box::new(HllAggregate::new(row_idx, precision, false)),
+        "hll_sketch" => Box::new(HllAggregate::new(row_idx, precision, true)),
+        _ => panic!("Aggregate {} does not take a precision", agg_type)
+    };
+}
+
 #[cfg(test)]
 mod tests {
 
-    use agg::new;
-    use data::Data;
+    use agg::{new, from_json, mode_from_json, pack_values, unpack_values, AggregateSpec};
+    use agg::sum::SumMode;
+    use std::sync::Arc;
+    use data::{Data, DataType};
     
     #[test]
     fn create_new_test() {
@@ -79,4 +424,203 @@ mod tests {
         assert_eq!(aggs[4].produce(), Data::Integer(312));
 
     }
+
+    #[test]
+    fn skips_nulls_test() {
+        let mut aggs = vec![
+            new("min", 0),
+            new("max", 0),
+            new("avg", 0),
+            new("count", 0),
+            new("sum", 0)
+        ];
+
+        let data = vec![
+            vec![Data::Integer(500)],
+            vec![Data::Null],
+            vec![Data::Integer(-200)]
+        ];
+
+        for agg in aggs.iter_mut() {
+            for row in data.iter() {
+                agg.consume(row);
+            }
+        }
+
+        assert_eq!(aggs[0].produce(), Data::Integer(-200));
+        assert_eq!(aggs[1].produce(), Data::Integer(500));
+        assert_eq!(aggs[2].produce(), Data::Real(150.0));
+        assert_eq!(aggs[3].produce(), Data::Integer(2));
+        assert_eq!(aggs[4].produce(), Data::Integer(300));
+
+        // a group containing only NULLs aggregates to NULL (or zero for count)
+        for agg in aggs.iter_mut() {
+            agg.consume(&data[1]);
+        }
+
+        assert!(aggs[0].produce().is_null());
+        assert!(aggs[1].produce().is_null());
+        assert!(aggs[2].produce().is_null());
+        assert_eq!(aggs[3].produce(), Data::Integer(0));
+        assert!(aggs[4].produce().is_null());
+    }
+
+    #[test]
+    fn date_min_max_test() {
+        let mut aggs = vec![new("min", 0), new("max", 0)];
+        let d = |s: &str| DataType::DATE.from_string(String::from(s)).unwrap();
+
+        for date in &["1995-03-15", "1992-01-02", "1998-12-01"] {
+            for agg in aggs.iter_mut() {
+                agg.consume(&[d(date)]);
+            }
+        }
+
+        assert_eq!(aggs[0].produce(), d("1992-01-02"));
+        assert_eq!(aggs[1].produce(), d("1998-12-01"));
+        assert_eq!(aggs[0].out_type(&[DataType::DATE]).to_code(),
+                   DataType::DATE.to_code());
+    }
+
+    #[test]
+    fn from_json_test() {
+        let mut corr = from_json(&json!({"op": "corr", "cols": [0, 1]}));
+        let mut var = from_json(&json!({"op": "var_pop", "col": 1}));
+        let mut sum = from_json(&json!({"op": "sum", "cols": [0]}));
+
+        for i in 0..10 {
+            let row = [Data::Integer(i), Data::Integer(3 * i + 1)];
+            corr.consume(&row);
+            var.consume(&row);
+            sum.consume(&row);
+        }
+
+        assert_relative_eq!(corr.produce().as_f64(), 1.0);
+        assert_relative_eq!(var.produce().as_f64(), 74.25);
+        assert_eq!(sum.produce(), Data::Integer(45));
+
+        let types = [DataType::INTEGER, DataType::INTEGER];
+        assert_matches!(corr.out_type(&types), DataType::REAL);
+        assert_matches!(sum.out_type(&types), DataType::INTEGER);
+    }
+
+    #[test]
+    fn pack_values_test() {
+        let values = vec![Data::Integer(5), Data::Null, Data::Text(String::from("hi")),
+                          Data::Decimal(1234, 2), Data::Blob(vec![1, 2])];
+        assert_eq!(unpack_values(&pack_values(&values)), values);
+        assert!(unpack_values(&pack_values(&[])).is_empty());
+    }
+
+    #[test]
+    fn spec_test() {
+        // the filter is parsed once and shared by every aggregate built
+        let spec = AggregateSpec::from_json(&json!({"op": "count", "col": 0,
+                                                    "filter": "t.1 = 2"}));
+        let mut aggs: Vec<_> = (0..3).map(|_| spec.build()).collect();
+        assert_eq!(Arc::strong_count(spec.filter.as_ref().unwrap()), 4);
+
+        for (i, agg) in aggs.iter_mut().enumerate() {
+            for j in 0..=i {
+                agg.consume(&[Data::Integer(1), Data::Integer(j as i64 % 2 + 1)]);
+            }
+        }
+
+        let counts: Vec<Data> = aggs.iter_mut().map(|agg| agg.produce()).collect();
+        assert_eq!(counts, vec![Data::Integer(0), Data::Integer(1), Data::Integer(1)]);
+    }
+
+    #[test]
+    fn mode_test() {
+        assert_matches!(mode_from_json(&json!({"op": "sum", "col": 0})), Ok(None));
+        assert_matches!(mode_from_json(&json!({"op": "avg distinct", "col": 0, "mode": "fast"})),
+                        Ok(Some(SumMode::Fast)));
+        assert_eq!(mode_from_json(&json!({"op": "sum distinct", "col": 0, "mode": "quick"}))
+                   .err().unwrap(),
+                   "Unknown sum mode quick (expected exact or fast)");
+        assert_eq!(mode_from_json(&json!({"op": "count", "col": 0, "mode": "fast"}))
+                   .err().unwrap(),
+                   "Aggregate count does not take a mode");
+    }
+
+    #[test]
+    fn partial_state_test() {
+        let types = [DataType::INTEGER, DataType::REAL, DataType::TEXT,
+                     DataType::INTEGER, DataType::DECIMAL(10, 2)];
+        let rows: Vec<Vec<Data>> = (0..300).map(|i| {
+            vec![if i % 10 == 0 { Data::Null } else { Data::Integer(i % 17) },
+                 Data::Real(i as f64 * 0.5),
+                 Data::Text(format!("v{}", i % 23)),
+                 Data::Integer((i * 7) % 50),
+                 Data::Decimal(i as i128 * 3, 2)]
+        }).collect();
+
+        let specs = vec![
+            json!({"op": "min", "col": 0}),
+            json!({"op": "max", "col": 2}),
+            json!({"op": "count", "col": 0}),
+            json!({"op": "sum", "col": 0}),
+            json!({"op": "sum", "col": 4}),
+            json!({"op": "avg", "col": 1}),
+            json!({"op": "avg", "col": 0}),
+            json!({"op": "avg", "col": 4}),
+            json!({"op": "sum", "col": 1, "mode": "fast"}),
+            json!({"op": "avg", "col": 0, "mode": "fast"}),
+            json!({"op": "count distinct", "col": 2}),
+            json!({"op": "sum distinct", "col": 0}),
+            json!({"op": "sum distinct", "col": 1, "mode": "fast"}),
+            json!({"op": "avg distinct", "col": 0, "mode": "exact"}),
+            json!({"op": "var_samp", "col": 1}),
+            json!({"op": "stddev_pop", "col": 0}),
+            json!({"op": "corr", "cols": [0, 1]}),
+            json!({"op": "covar_samp", "cols": [1, 3]}),
+            json!({"op": "median", "col": 1}),
+            json!({"op": "percentile_disc", "col": 0, "p": 0.3}),
+            json!({"op": "approx_count_distinct", "col": 2}),
+            json!({"op": "hll_sketch", "col": 2, "precision": 8}),
+            json!({"op": "approx_quantiles", "col": 1}),
+            json!({"op": "first", "col": 0}),
+            json!({"op": "last", "col": 0}),
+            json!({"op": "any_value", "col": 0}),
+            json!({"op": "arg_min", "cols": [3, 2]}),
+            json!({"op": "arg_max", "cols": [3, 2]}),
+            json!({"op": "string_agg", "col": 2}),
+            json!({"op": "string_agg", "col": 2, "order_by": 3, "separator": "/"})
+        ];
+
+        for spec in specs {
+            let mut direct = from_json(&spec);
+            for row in rows.iter() {
+                direct.consume(row);
+            }
+            let expected = direct.produce();
+
+            // aggregate three chunks separately, then merge their states.
+            // The state of an empty aggregate merges as a no-op, since a
+            // group by uses it to pad the rows of spilled states.
+            let mut merged = from_json(&spec);
+            merged.merge(&from_json(&spec).partial_state());
+            for chunk in rows.chunks(100) {
+                let mut partial = from_json(&spec);
+                for row in chunk {
+                    partial.consume(row);
+                }
+
+                let state = partial.partial_state();
+                assert_eq!(state.len(), partial.state_types(&types).len());
+                merged.merge(&state);
+            }
+            let actual = merged.produce();
+
+            match (&expected, &actual) {
+                (Data::Real(e), Data::Real(a)) => {
+                    assert_relative_eq!(e, a, max_relative = 1e-9);
+                },
+                (Data::Blob(_), Data::Blob(_)) if spec["op"] == "approx_quantiles" => {
+                    // t-digests depend on the order values are merged
+                },
+                _ => assert_eq!(expected, actual, "{}", spec)
+            };
+        }
+    }
 }
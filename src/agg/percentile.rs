@@ -0,0 +1,371 @@
+// < begin copyright > 
+// Copyright Ryan Marcus 2018
+// 
+// This file is part of byoo.
+// 
+// byoo is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// byoo is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with byoo.  If not, see <http://www.gnu.org/licenses/>.
+// 
+// < end copyright > 
+use agg::{Aggregate, SpilledChunks, pack_values, unpack_values, value_size};
+use data::{Data, DataType};
+use spillable_store::WritableSpillableStore;
+use operator_buffer::make_buffer_pair;
+use operator::Sort;
+use std::cmp::Ordering;
+use std::thread;
+
+// the number of values a group keeps in memory before spilling
+const DEFAULT_MAX_IN_MEMORY: usize = 1 << 16;
+
+#[derive(Clone, Copy)]
+pub enum PercentileKind {
+    // interpolates between the two nearest values (percentile_cont)
+    Continuous,
+
+    // picks the first value at or above the fraction (percentile_disc)
+    Discrete
+}
+
+/// Computes an exact percentile of the non-NULL values of a column. Every
+/// value has to be kept until the group is produced, so values are
+/// buffered in memory and spilled to disk when there are too many of
+/// them. Spilled groups are sorted with the external sort operator.
+pub struct PercentileAggregate {
+    row_idx: usize,
+    fraction: f64,
+    kind: PercentileKind,
+    count: usize,
+    values: Vec<Data>,
+    values_size: usize,
+    spilled: Option<WritableSpillableStore>,
+    spilled_chunks: Option<SpilledChunks>,
+    max_in_memory: usize
+}
+
+impl PercentileAggregate {
+    pub fn new(row_idx: usize, fraction: f64, kind: PercentileKind) -> PercentileAggregate {
+        return PercentileAggregate::with_max_in_memory(row_idx, fraction, kind,
+                                                       DEFAULT_MAX_IN_MEMORY);
+    }
+
+    pub fn with_max_in_memory(row_idx: usize, fraction: f64, kind: PercentileKind,
+                              max_in_memory: usize) -> PercentileAggregate {
+        assert!((0.0..=1.0).contains(&fraction),
+                "Percentile must be between 0 and 1, got {}", fraction);
+        
+        return PercentileAggregate {
+            row_idx, fraction, kind, max_in_memory,
+            count: 0,
+            values: Vec::new(),
+            values_size: 0,
+            spilled: None,
+            spilled_chunks: None
+        };
+    }
+
+    fn spill(&mut self) {
+        if self.spilled.is_none() {
+            let dt = self.values[0].data_type().unwrap();
+            self.spilled = Some(WritableSpillableStore::new(4096, vec![dt]));
+        }
+
+        let store = self.spilled.as_mut().unwrap();
+        for d in self.values.drain(..) {
+            store.push_row(&[d]);
+        }
+    }
+
+    // the positions (in sorted order) of the values the result depends on
+    fn positions(&self, n: usize) -> (usize, usize) {
+        return match self.kind {
+            PercentileKind::Continuous => {
+                let pos = self.fraction * (n - 1) as f64;
+                (pos.floor() as usize, pos.ceil() as usize)
+            },
+            PercentileKind::Discrete => {
+                let pos = ((self.fraction * n as f64).ceil() as usize).max(1) - 1;
+                (pos, pos)
+            }
+        };
+    }
+
+    fn select(&self, n: usize, lo: Data, hi: Data) -> Data {
+        if let PercentileKind::Discrete = self.kind {
+            return lo;
+        }
+
+        let pos = self.fraction * (n - 1) as f64;
+        let lo = numeric_value(&lo);
+        let hi = numeric_value(&hi);
+        return Data::Real(lo + (pos - pos.floor()) * (hi - lo));
+    }
+
+    fn select_spilled(&mut self, mut store: WritableSpillableStore,
+                      lo_pos: usize, hi_pos: usize) -> (Data, Data) {
+        for d in self.values.drain(..) {
+            store.push_row(&[d]);
+        }
+
+        let (stats, buf) = store.into_read_buffer();
+        let (mut sorted, w) = make_buffer_pair(5, 4096, stats.types);
+        let sort = Sort::new(buf, w, vec![0], self.max_in_memory);
+        let jh = thread::spawn(move || {
+            sort.start();
+        });
+
+        let mut lo = Data::Null;
+        let mut hi = Data::Null;
+        iterate_buffer!(sorted, idx, row, {
+            if idx == lo_pos {
+                lo = row[0].clone();
+            }
+
+            if idx == hi_pos {
+                hi = row[0].clone();
+            }
+        });
+        jh.join().unwrap();
+
+        return (lo, hi);
+    }
+
+    fn update(&mut self, nxt: &Data) {
+        if nxt.is_null() {
+            return;
+        }
+
+        if self.values.is_empty() {
+            self.values_size = 0;
+        }
+
+        self.count += 1;
+        self.values_size += value_size(nxt);
+        self.values.push(nxt.clone());
+
+        if self.values.len() >= self.max_in_memory {
+            self.spill();
+        }
+    }
+}
+
+fn numeric_value(d: &Data) -> f64 {
+    return match d {
+        Data::Integer(i) => *i as f64,
+        _ => d.as_f64()
+    };
+}
+
+impl Aggregate for PercentileAggregate {
+    fn consume(&mut self, row: &[Data]) {
+        self.update(&row[self.row_idx]);
+    }
+
+    fn produce(&mut self) -> Data {
+        let n = self.count;
+        self.count = 0;
+
+        if n == 0 {
+            return Data::Null;
+        }
+
+        let (lo_pos, hi_pos) = self.positions(n);
+        let (lo, hi) = match self.spilled.take() {
+            Some(store) => self.select_spilled(store, lo_pos, hi_pos),
+            None => {
+                self.values.sort_unstable_by(
+                    |a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+                let lo = self.values[lo_pos].clone();
+                let hi = self.values[hi_pos].clone();
+                self.values.clear();
+                (lo, hi)
+            }
+        };
+
+        return self.select(n, lo, hi);
+    }
+
+    fn out_type(&self, in_types: &[DataType]) -> DataType {
+        let in_type = &in_types[self.row_idx];
+        return match self.kind {
+            PercentileKind::Discrete => in_type.clone(),
+            PercentileKind::Continuous => {
+                match in_type {
+                    DataType::INTEGER | DataType::REAL | DataType::DECIMAL(_, _)
+                        => DataType::REAL,
+                    _ => panic!("Continuous percentiles require a numeric column, got {:?}",
+                                in_type)
+                }
+            }
+        };
+    }
+
+    fn partial_state(&mut self) -> Vec<Data> {
+        // every value is needed, so the state is as large as the group
+        // (less any values already exported by spilled_state)
+        let mut values: Vec<Data> = self.values.drain(..).collect();
+        if let Some(store) = self.spilled.take() {
+            let (_, mut buf) = store.into_read_buffer();
+            iterate_buffer!(buf, row, {
+                values.push(row[0].clone());
+            });
+        }
+        self.count = 0;
+
+        return vec![pack_values(&values)];
+    }
+
+    fn spilled_state(&mut self) -> Option<Vec<Data>> {
+        if let Some(store) = self.spilled.take() {
+            self.spilled_chunks = Some(SpilledChunks::new(store, self.max_in_memory));
+        }
+
+        let chunk = self.spilled_chunks.as_mut()?.next_chunk(&[0]);
+        if chunk.is_none() {
+            self.spilled_chunks = None;
+        }
+
+        return chunk.map(|values| vec![values]);
+    }
+
+    fn merge(&mut self, state: &[Data]) {
+        for d in unpack_values(&state[0]) {
+            self.update(&d);
+        }
+    }
+
+    fn state_types(&self, _in_types: &[DataType]) -> Vec<DataType> {
+        return vec![DataType::BLOB];
+    }
+
+    fn state_size(&self) -> usize {
+        // the size of the values buffered since they were last emptied
+        return if self.values.is_empty() { 0 } else { self.values_size };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use agg::{Aggregate, new, from_json, unpack_values};
+    use agg::percentile::{PercentileAggregate, PercentileKind};
+    use data::{Data, DataType};
+    use rand::prelude::*;
+
+    #[test]
+    fn median_test() {
+        let mut agg = new("median", 0);
+
+        for v in &[7, 1, 5, 3, 9] {
+            agg.consume(&[Data::Integer(*v)]);
+            agg.consume(&[Data::Null]);
+        }
+        assert_eq!(agg.produce(), Data::Real(5.0));
+
+        // even groups average the two middle values
+        for v in &[7, 1, 5, 3] {
+            agg.consume(&[Data::Integer(*v)]);
+        }
+        assert_eq!(agg.produce(), Data::Real(4.0));
+
+        assert_eq!(agg.produce(), Data::Null);
+        assert_matches!(agg.out_type(&[DataType::INTEGER]), DataType::REAL);
+    }
+
+    #[test]
+    fn percentile_test() {
+        let mut cont = from_json(&json!({"op": "percentile_cont", "col": 0, "p": 0.25}));
+        let mut disc = from_json(&json!({"op": "percentile_disc", "col": 0, "p": 0.25}));
+
+        for v in &[40, 10, 30, 20, 50] {
+            cont.consume(&[Data::Integer(*v)]);
+            disc.consume(&[Data::Integer(*v)]);
+        }
+
+        assert_eq!(cont.produce(), Data::Real(20.0));
+        assert_eq!(disc.produce(), Data::Integer(20));
+
+        for v in &[40, 10, 30, 20] {
+            cont.consume(&[Data::Integer(*v)]);
+            disc.consume(&[Data::Integer(*v)]);
+        }
+
+        assert_eq!(cont.produce(), Data::Real(17.5));
+        assert_eq!(disc.produce(), Data::Integer(10));
+        assert_matches!(disc.out_type(&[DataType::TEXT]), DataType::TEXT);
+    }
+
+    #[test]
+    fn extremes_test() {
+        let mut min = PercentileAggregate::new(0, 0.0, PercentileKind::Discrete);
+        let mut max = PercentileAggregate::new(0, 1.0, PercentileKind::Continuous);
+
+        for v in &[2.5, -1.0, 8.0] {
+            min.consume(&[Data::Real(*v)]);
+            max.consume(&[Data::Real(*v)]);
+        }
+
+        assert_eq!(min.produce(), Data::Real(-1.0));
+        assert_eq!(max.produce(), Data::Real(8.0));
+    }
+
+    #[test]
+    fn spill_test() {
+        let mut values: Vec<i64> = (0..5001).collect();
+        thread_rng().shuffle(&mut values);
+
+        let mut median = PercentileAggregate::with_max_in_memory(
+            0, 0.5, PercentileKind::Continuous, 100);
+        let mut disc = PercentileAggregate::with_max_in_memory(
+            0, 0.9, PercentileKind::Discrete, 100);
+
+        for v in values {
+            median.consume(&[Data::Integer(v)]);
+            disc.consume(&[Data::Integer(v)]);
+        }
+
+        assert!(median.spilled.is_some());
+        assert_eq!(median.produce(), Data::Real(2500.0));
+        assert_eq!(disc.produce(), Data::Integer(4500));
+
+        // the state resets between groups
+        median.consume(&[Data::Integer(7)]);
+        assert!(median.spilled.is_none());
+        assert_eq!(median.produce(), Data::Real(7.0));
+    }
+
+    #[test]
+    fn spilled_state_test() {
+        let new_median = || PercentileAggregate::with_max_in_memory(
+            0, 0.5, PercentileKind::Continuous, 100);
+
+        let mut values: Vec<i64> = (0..5001).collect();
+        thread_rng().shuffle(&mut values);
+
+        let mut median = new_median();
+        for v in values {
+            median.consume(&[Data::Integer(v)]);
+        }
+
+        let mut merged = new_median();
+        let mut num_chunks = 0;
+        while let Some(state) = median.spilled_state() {
+            assert!(unpack_values(&state[0]).len() <= 100);
+            merged.merge(&state);
+            num_chunks += 1;
+        }
+        assert!(num_chunks > 1);
+
+        merged.merge(&median.partial_state());
+        assert_eq!(merged.produce(), Data::Real(2500.0));
+    }
+}
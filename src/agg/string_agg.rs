@@ -0,0 +1,359 @@
+// < begin copyright > 
+// Copyright Ryan Marcus 2018
+// 
+// This file is part of byoo.
+// 
+// byoo is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// byoo is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with byoo.  If not, see <http://www.gnu.org/licenses/>.
+// 
+// < end copyright > 
+use agg::{Aggregate, SpilledChunks, pack_values, unpack_values, value_size};
+use data::{Data, DataType};
+use spillable_store::WritableSpillableStore;
+use operator_buffer::make_buffer_pair;
+use operator::Sort;
+use std::cmp::Ordering;
+use std::thread;
+use std::mem;
+
+// the number of values a group keeps in memory before spilling
+const DEFAULT_MAX_IN_MEMORY: usize = 1 << 16;
+
+pub const DEFAULT_SEPARATOR: &str = ",";
+
+/// Concatenates the non-NULL values of a column (as text) with a
+/// separator, optionally ordered by another column. Unordered values are
+/// appended as they arrive. Ordered values are buffered, and spilled to
+/// disk and sorted with the external sort operator when there are too
+/// many of them. Ties keep their input order, and values with a NULL
+/// order key come last.
+pub struct StringAggAggregate {
+    row_idx: usize,
+    separator: String,
+    order_by: Option<usize>,
+    result: Option<String>,
+    null_keys: Option<String>,
+    rows: Vec<(Data, String)>,
+    rows_size: usize,
+    spilled: Option<WritableSpillableStore>,
+    spilled_chunks: Option<SpilledChunks>,
+    num_spilled: i64,
+    max_in_memory: usize
+}
+
+impl StringAggAggregate {
+    pub fn new(row_idx: usize, separator: &str,
+               order_by: Option<usize>) -> StringAggAggregate {
+        return StringAggAggregate::with_max_in_memory(row_idx, separator, order_by,
+                                                      DEFAULT_MAX_IN_MEMORY);
+    }
+
+    pub fn with_max_in_memory(row_idx: usize, separator: &str, order_by: Option<usize>,
+                              max_in_memory: usize) -> StringAggAggregate {
+        return StringAggAggregate {
+            row_idx, order_by, max_in_memory,
+            separator: String::from(separator),
+            result: None,
+            null_keys: None,
+            rows: Vec::new(),
+            rows_size: 0,
+            spilled: None,
+            spilled_chunks: None,
+            num_spilled: 0
+        };
+    }
+
+    fn append(&self, acc: &mut Option<String>, val: &str) {
+        match acc {
+            Some(s) => {
+                s.push_str(&self.separator);
+                s.push_str(val);
+            },
+            None => { *acc = Some(String::from(val)); }
+        };
+    }
+
+    fn push_row(&mut self, key: Data, val: String) {
+        if self.rows.is_empty() {
+            self.rows_size = 0;
+        }
+
+        self.rows_size += value_size(&key) + mem::size_of::<String>() + val.len();
+        self.rows.push((key, val));
+        if self.rows.len() >= self.max_in_memory {
+            self.spill();
+        }
+    }
+
+    fn spill(&mut self) {
+        if self.spilled.is_none() {
+            let dt = self.rows[0].0.data_type().unwrap();
+            self.spilled = Some(WritableSpillableStore::new(
+                4096, vec![dt, DataType::INTEGER, DataType::TEXT]));
+        }
+
+        // the input position breaks ties between equal keys when sorting
+        let store = self.spilled.as_mut().unwrap();
+        for (key, val) in self.rows.drain(..) {
+            store.push_row(&[key, Data::Integer(self.num_spilled), Data::Text(val)]);
+            self.num_spilled += 1;
+        }
+    }
+
+    fn produce_spilled(&mut self, mut store: WritableSpillableStore) -> Option<String> {
+        for (key, val) in self.rows.drain(..) {
+            store.push_row(&[key, Data::Integer(self.num_spilled), Data::Text(val)]);
+            self.num_spilled += 1;
+        }
+        self.num_spilled = 0;
+
+        let (stats, buf) = store.into_read_buffer();
+        let (mut sorted, w) = make_buffer_pair(5, 4096, stats.types);
+        let sort = Sort::new(buf, w, vec![0, 1], self.max_in_memory);
+        let jh = thread::spawn(move || {
+            sort.start();
+        });
+
+        let mut result = None;
+        iterate_buffer!(sorted, row, {
+            if let Data::Text(ref val) = row[2] {
+                self.append(&mut result, val);
+            }
+        });
+        jh.join().unwrap();
+
+        return result;
+    }
+}
+
+impl Aggregate for StringAggAggregate {
+    fn consume(&mut self, row: &[Data]) {
+        let nxt = &row[self.row_idx];
+        if nxt.is_null() {
+            return;
+        }
+
+        let val = nxt.as_string();
+        let key = match self.order_by {
+            Some(idx) => &row[idx],
+            None => {
+                let mut result = self.result.take();
+                self.append(&mut result, &val);
+                self.result = result;
+                return;
+            }
+        };
+
+        if key.is_null() {
+            let mut null_keys = self.null_keys.take();
+            self.append(&mut null_keys, &val);
+            self.null_keys = null_keys;
+            return;
+        }
+
+        self.push_row(key.clone(), val);
+    }
+
+    fn produce(&mut self) -> Data {
+        let mut result = match self.spilled.take() {
+            Some(store) => self.produce_spilled(store),
+            None => {
+                // a stable sort, so ties keep their input order
+                let mut rows: Vec<(Data, String)> = self.rows.drain(..).collect();
+                rows.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+                
+                let mut result = self.result.take();
+                for (_, val) in rows {
+                    self.append(&mut result, &val);
+                }
+                result
+            }
+        };
+
+        if let Some(null_keys) = self.null_keys.take() {
+            self.append(&mut result, &null_keys);
+        }
+
+        return result.map(Data::Text).unwrap_or(Data::Null);
+    }
+
+    fn out_type(&self, _in_types: &[DataType]) -> DataType {
+        return DataType::TEXT;
+    }
+
+    fn partial_state(&mut self) -> Vec<Data> {
+        // ordered values are exported unsorted, as key and value pairs in
+        // their input order
+        let mut pairs = Vec::new();
+        if let Some(store) = self.spilled.take() {
+            let (_, mut buf) = store.into_read_buffer();
+            iterate_buffer!(buf, row, {
+                pairs.push(row[0].clone());
+                pairs.push(row[2].clone());
+            });
+        }
+        self.num_spilled = 0;
+
+        for (key, val) in self.rows.drain(..) {
+            pairs.push(key);
+            pairs.push(Data::Text(val));
+        }
+
+        return vec![self.result.take().map(Data::Text).unwrap_or(Data::Null),
+                    pack_values(&pairs),
+                    self.null_keys.take().map(Data::Text).unwrap_or(Data::Null)];
+    }
+
+    fn spilled_state(&mut self) -> Option<Vec<Data>> {
+        if let Some(store) = self.spilled.take() {
+            self.spilled_chunks = Some(SpilledChunks::new(store, self.max_in_memory));
+        }
+
+        // spilled pairs are exported in their input order, before the
+        // ones still in memory
+        let chunk = self.spilled_chunks.as_mut()?.next_chunk(&[0, 2]);
+        if chunk.is_none() {
+            self.spilled_chunks = None;
+        }
+
+        return chunk.map(|pairs| vec![Data::Null, pairs, Data::Null]);
+    }
+
+    fn merge(&mut self, state: &[Data]) {
+        if let Data::Text(ref val) = state[0] {
+            let mut result = self.result.take();
+            self.append(&mut result, val);
+            self.result = result;
+        }
+
+        let mut pairs = unpack_values(&state[1]).into_iter();
+        while let (Some(key), Some(val)) = (pairs.next(), pairs.next()) {
+            self.push_row(key, val.into_string());
+        }
+
+        if let Data::Text(ref val) = state[2] {
+            let mut null_keys = self.null_keys.take();
+            self.append(&mut null_keys, val);
+            self.null_keys = null_keys;
+        }
+    }
+
+    fn state_types(&self, _in_types: &[DataType]) -> Vec<DataType> {
+        return vec![DataType::TEXT, DataType::BLOB, DataType::TEXT];
+    }
+
+    fn state_size(&self) -> usize {
+        // the concatenated strings, and the ordered rows buffered since
+        // they were last emptied
+        let strings = [&self.result, &self.null_keys].iter()
+            .map(|s| s.as_ref().map(|s| s.capacity()).unwrap_or(0))
+            .sum::<usize>();
+        return strings + if self.rows.is_empty() { 0 } else { self.rows_size };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use agg::{Aggregate, new, from_json, unpack_values};
+    use agg::string_agg::StringAggAggregate;
+    use data::{Data, DataType};
+    use rand::prelude::*;
+
+    fn text(s: &str) -> Data {
+        return Data::Text(String::from(s));
+    }
+
+    #[test]
+    fn unordered_test() {
+        let mut agg = new("string_agg", 0);
+
+        for d in &[text("a"), Data::Null, text("b"), Data::Integer(5)] {
+            agg.consume(&[d.clone()]);
+        }
+        
+        assert_eq!(agg.produce(), text("a,b,5"));
+        assert_eq!(agg.produce(), Data::Null);
+        assert_matches!(agg.out_type(&[DataType::INTEGER]), DataType::TEXT);
+    }
+
+    #[test]
+    fn ordered_test() {
+        let mut agg = from_json(&json!({"op": "string_agg", "col": 0,
+                                        "separator": " | ", "order_by": 1}));
+
+        let data = vec![
+            vec![text("c"), Data::Integer(3)],
+            vec![text("null key"), Data::Null],
+            vec![text("a"), Data::Integer(1)],
+            vec![Data::Null, Data::Integer(0)],
+            vec![text("b1"), Data::Integer(2)],
+            vec![text("b2"), Data::Integer(2)],
+        ];
+
+        for row in data.iter() {
+            agg.consume(row);
+        }
+
+        assert_eq!(agg.produce(), text("a | b1 | b2 | c | null key"));
+    }
+
+    #[test]
+    fn spill_test() {
+        let mut keys: Vec<i64> = (0..5000).collect();
+        thread_rng().shuffle(&mut keys);
+
+        let mut agg = StringAggAggregate::with_max_in_memory(0, "", Some(1), 100);
+        for k in keys.iter() {
+            // two values per key, which should stay in input order
+            agg.consume(&[text(&format!("{}a ", k)), Data::Integer(*k)]);
+            agg.consume(&[text(&format!("{}b ", k)), Data::Integer(*k)]);
+        }
+        agg.consume(&[text("last"), Data::Null]);
+
+        assert!(agg.spilled.is_some());
+        let expected: String = (0..5000).map(|k| format!("{}a {}b ", k, k)).collect();
+        assert_eq!(agg.produce(), Data::Text(expected + "last"));
+
+        // the state resets between groups
+        agg.consume(&[text("x"), Data::Integer(1)]);
+        assert!(agg.spilled.is_none());
+        assert_eq!(agg.produce(), text("x"));
+    }
+
+    #[test]
+    fn spilled_state_test() {
+        let mut keys: Vec<i64> = (0..5000).collect();
+        thread_rng().shuffle(&mut keys);
+
+        let mut agg = StringAggAggregate::with_max_in_memory(0, "", Some(1), 100);
+        for k in keys.iter() {
+            agg.consume(&[text(&format!("{}a ", k)), Data::Integer(*k)]);
+            agg.consume(&[text(&format!("{}b ", k)), Data::Integer(*k)]);
+        }
+        agg.consume(&[text("last"), Data::Null]);
+
+        // ties still keep their input order once the chunks are merged
+        let mut merged = StringAggAggregate::with_max_in_memory(0, "", Some(1), 100);
+        let mut num_chunks = 0;
+        while let Some(state) = agg.spilled_state() {
+            assert!(unpack_values(&state[1]).len() <= 2 * 100);
+            merged.merge(&state);
+            num_chunks += 1;
+        }
+        assert!(num_chunks > 1);
+
+        merged.merge(&agg.partial_state());
+        let expected: String = (0..5000).map(|k| format!("{}a {}b ", k, k)).collect();
+        assert_eq!(merged.produce(), Data::Text(expected + "last"));
+    }
+}
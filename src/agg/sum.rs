@@ -19,45 +19,247 @@
 // < end copyright > 
 use agg::Aggregate;
 use data::{DataType, Data};
+use decimal;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SumMode {
+    // integers are summed as i128 (so only the final sum has to fit in
+    // an INTEGER), and reals with Neumaier's compensated summation
+    Exact,
+
+    // integers are summed as (checked) i64, and reals naively. An
+    // average only checks that its final value fits.
+    Fast
+}
+
+impl SumMode {
+    pub fn from_str(mode: &str) -> Result<SumMode, String> {
+        return match mode {
+            "exact" => Ok(SumMode::Exact),
+            "fast" => Ok(SumMode::Fast),
+            _ => Err(format!("Unknown sum mode {} (expected exact or fast)", mode))
+        };
+    }
+}
+
+enum SumState {
+    Empty,
+    Integer(i128),
+    Real { sum: f64, compensation: f64 },
+    Other(Data)
+}
+
+/// A running sum, shared by SUM and AVG.
+pub struct Summation {
+    mode: SumMode,
+    state: SumState,
+    checked: bool
+}
+
+impl Summation {
+    pub fn new(mode: SumMode) -> Summation {
+        return Summation {
+            mode,
+            state: SumState::Empty,
+            checked: true
+        };
+    }
+
+    /// A running sum for an average, whose INTEGER sum never has to fit
+    /// in an INTEGER (even in fast mode), since only the average does.
+    pub fn for_avg(mode: SumMode) -> Summation {
+        return Summation {
+            checked: false,
+            ..Summation::new(mode)
+        };
+    }
+
+    pub fn add(&mut self, nxt: &Data) {
+        let state = std::mem::replace(&mut self.state, SumState::Empty);
+        self.state = match (state, nxt) {
+            (state, Data::Null) => state,
+            (SumState::Empty, Data::Integer(i)) => SumState::Integer(i128::from(*i)),
+            (SumState::Integer(sum), Data::Integer(i)) => self.add_integer(sum, i128::from(*i)),
+            (SumState::Empty, Data::Real(f)) => SumState::Real { sum: *f, compensation: 0.0 },
+            (SumState::Real { sum, compensation }, Data::Real(f)) => {
+                self.add_real(sum, compensation, *f)
+            },
+            (SumState::Empty, d) => SumState::Other(d.clone()),
+            (SumState::Other(sum), d) => SumState::Other(sum + d.clone()),
+            (_, d) => panic!("Cannot sum mixed types, got {:?}", d)
+        };
+    }
+
+    fn add_integer(&self, sum: i128, i: i128) -> SumState {
+        let sum = sum + i;
+        if self.mode == SumMode::Fast && self.checked && i64_from(sum).is_none() {
+            panic!("Integer overflow in SUM");
+        }
+        
+        return SumState::Integer(sum);
+    }
+
+    fn add_real(&self, sum: f64, mut compensation: f64, f: f64) -> SumState {
+        if self.mode == SumMode::Fast {
+            return SumState::Real { sum: sum + f, compensation };
+        }
+
+        // Neumaier's variant of Kahan summation keeps the low order bits
+        // lost by each addition in the compensation term
+        let t = sum + f;
+        if sum.abs() >= f.abs() {
+            compensation += (sum - t) + f;
+        } else {
+            compensation += (f - t) + sum;
+        }
+        
+        return SumState::Real { sum: t, compensation };
+    }
+
+    /// Takes the sum (or NULL if nothing was added), resetting it. Sums of
+    /// INTEGERs are widened, so they may not fit back in an INTEGER.
+    fn take(&mut self) -> SumState {
+        return std::mem::replace(&mut self.state, SumState::Empty);
+    }
+
+    /// Produces the sum, resetting it. Panics if an INTEGER sum overflows.
+    pub fn produce(&mut self) -> Data {
+        return match self.take() {
+            SumState::Empty => Data::Null,
+            SumState::Integer(sum) => Data::Integer(
+                i64_from(sum).unwrap_or_else(|| panic!("Integer overflow in SUM: {}", sum))),
+            SumState::Real { sum, compensation } => Data::Real(sum + compensation),
+            SumState::Other(d) => d
+        };
+    }
+
+    /// Produces the sum divided by `count`, resetting it.
+    pub fn produce_avg(&mut self, count: usize) -> Data {
+        return match self.take() {
+            SumState::Empty => Data::Null,
+            SumState::Integer(sum) => Data::Real(sum as f64 / count as f64),
+            SumState::Real { sum, compensation } => {
+                Data::Real((sum + compensation) / count as f64)
+            },
+            SumState::Other(d) => d / count
+        };
+    }
+
+    /// Exports the sum as two values: the sum itself (INTEGER sums are
+    /// widened to a DECIMAL with scale 0), and the compensation term for
+    /// REAL sums, which is an INTEGER 0 for INTEGER sums.
+    pub fn partial_state(&mut self) -> Vec<Data> {
+        return match self.take() {
+            SumState::Empty => vec![Data::Null, Data::Null],
+            SumState::Integer(sum) => vec![Data::Decimal(sum, 0), Data::Integer(0)],
+            SumState::Real { sum, compensation } => {
+                vec![Data::Real(sum), Data::Real(compensation)]
+            },
+            SumState::Other(d) => vec![d, Data::Null]
+        };
+    }
+
+    pub fn merge(&mut self, state: &[Data]) {
+        match (&state[0], &state[1]) {
+            (Data::Decimal(sum, 0), Data::Integer(_)) => {
+                let other = *sum;
+                self.state = match self.take() {
+                    SumState::Empty => SumState::Integer(other),
+                    SumState::Integer(sum) => self.add_integer(sum, other),
+                    _ => panic!("Cannot merge an INTEGER sum into another type")
+                };
+            },
+            (Data::Real(sum), Data::Real(compensation)) => {
+                let (other, other_compensation) = (*sum, *compensation);
+                self.state = match self.take() {
+                    SumState::Empty => SumState::Real {
+                        sum: other, compensation: other_compensation
+                    },
+                    SumState::Real { sum, compensation } => {
+                        self.add_real(sum, compensation + other_compensation, other)
+                    },
+                    _ => panic!("Cannot merge a REAL sum into another type")
+                };
+            },
+            (d, _) => self.add(d)
+        };
+    }
+
+    pub fn state_types(in_type: &DataType) -> Vec<DataType> {
+        return match in_type {
+            DataType::INTEGER => vec![DataType::DECIMAL(decimal::MAX_PRECISION, 0),
+                                      DataType::INTEGER],
+            DataType::REAL => vec![DataType::REAL, DataType::REAL],
+            DataType::DECIMAL(_, s) => vec![DataType::DECIMAL(decimal::MAX_PRECISION, *s),
+                                            DataType::REAL],
+            dt => vec![dt.clone(), DataType::REAL]
+        };
+    }
+}
+
+fn i64_from(v: i128) -> Option<i64> {
+    if v > i128::from(i64::MAX) || v < i128::from(i64::MIN) {
+        return None;
+    }
+
+    return Some(v as i64);
+}
 
 pub struct SumAggregate {
     row_idx: usize,
-    curr_sum: Option<Data>
+    sum: Summation
 }
 
 impl SumAggregate {
     pub fn new(row_idx: usize) -> SumAggregate {
+        return SumAggregate::with_mode(row_idx, SumMode::Exact);
+    }
+
+    pub fn with_mode(row_idx: usize, mode: SumMode) -> SumAggregate {
         return SumAggregate {
             row_idx,
-            curr_sum: None
+            sum: Summation::new(mode)
         };
     }
 }
 
 impl Aggregate for SumAggregate {
     fn consume(&mut self, row: &[Data]) {
-        let nxt = &row[self.row_idx];
-        let curr = self.curr_sum.take();
-        self.curr_sum = match curr {
-            None => Some(nxt.clone()),
-            Some(d) => Some(d + nxt.clone())
-        };
+        self.sum.add(&row[self.row_idx]);
     }
 
     fn produce(&mut self) -> Data {
-        return self.curr_sum.take().unwrap();
+        return self.sum.produce();
     }
 
-    fn out_type(&self, in_type: &DataType) -> DataType {
+    fn out_type(&self, in_types: &[DataType]) -> DataType {
+        // sums of decimals keep their scale, but may need more precision
+        let in_type = &in_types[self.row_idx];
+        if let DataType::DECIMAL(_, s) = in_type {
+            return DataType::DECIMAL(decimal::MAX_PRECISION, *s);
+        }
+        
         return in_type.clone();
     }
+
+    fn partial_state(&mut self) -> Vec<Data> {
+        return self.sum.partial_state();
+    }
+
+    fn merge(&mut self, state: &[Data]) {
+        self.sum.merge(state);
+    }
+
+    fn state_types(&self, in_types: &[DataType]) -> Vec<DataType> {
+        return Summation::state_types(&in_types[self.row_idx]);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use agg::{Aggregate};
-    use data::Data;
-    use agg::sum::SumAggregate;
+    use data::{Data, DataType};
+    use agg::sum::{SumAggregate, SumMode};
     
     #[test]
     fn simple_test() {
@@ -116,4 +318,59 @@ mod tests {
         assert_eq!(sum_agg.produce(), Data::Integer(-10));
 
     }
+
+    #[test]
+    fn decimal_test() {
+        let mut sum_agg = SumAggregate::new(0);
+        let dt = DataType::DECIMAL(15, 2);
+
+        for _ in 0..10 {
+            sum_agg.consume(&[dt.from_string(String::from("0.10")).unwrap()]);
+        }
+
+        assert_eq!(sum_agg.produce(), Data::Decimal(100, 2));
+        assert_eq!(sum_agg.out_type(&[dt]).to_code(),
+                   DataType::DECIMAL(38, 2).to_code());
+    }
+
+    #[test]
+    fn wide_integer_test() {
+        // only the final sum has to fit in an INTEGER
+        let mut sum_agg = SumAggregate::new(0);
+        for v in &[i64::MAX, 10, i64::MAX, -i64::MAX, -20] {
+            sum_agg.consume(&[Data::Integer(*v)]);
+        }
+        assert_eq!(sum_agg.produce(), Data::Integer(i64::MAX - 10));
+    }
+
+    #[test]
+    #[should_panic(expected = "Integer overflow in SUM")]
+    fn overflow_test() {
+        let mut sum_agg = SumAggregate::new(0);
+        sum_agg.consume(&[Data::Integer(i64::MAX)]);
+        sum_agg.consume(&[Data::Integer(1)]);
+        sum_agg.produce();
+    }
+
+    #[test]
+    #[should_panic(expected = "Integer overflow in SUM")]
+    fn fast_overflow_test() {
+        let mut sum_agg = SumAggregate::with_mode(0, SumMode::Fast);
+        sum_agg.consume(&[Data::Integer(i64::MAX)]);
+        sum_agg.consume(&[Data::Integer(1)]);
+    }
+
+    #[test]
+    fn compensated_test() {
+        let mut exact = SumAggregate::new(0);
+        let mut fast = SumAggregate::with_mode(0, SumMode::Fast);
+
+        for v in &[1.0, 1e100, 1.0, -1e100] {
+            exact.consume(&[Data::Real(*v)]);
+            fast.consume(&[Data::Real(*v)]);
+        }
+
+        assert_eq!(exact.produce(), Data::Real(2.0));
+        assert_eq!(fast.produce(), Data::Real(0.0));
+    }
 }
@@ -0,0 +1,518 @@
+// < begin copyright > 
+// Copyright Ryan Marcus 2018
+// 
+// This file is part of byoo.
+// 
+// byoo is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// byoo is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with byoo.  If not, see <http://www.gnu.org/licenses/>.
+// 
+// < end copyright > 
+use agg::Aggregate;
+use data::{Data, DataType};
+use byteorder::{ReadBytesExt, WriteBytesExt, LittleEndian};
+use std::cmp::Ordering;
+use std::f64::consts::PI;
+use std::mem;
+
+pub const DEFAULT_COMPRESSION: f64 = 100.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Centroid {
+    mean: f64,
+    weight: f64
+}
+
+/// A merging t-digest (Dunning & Ertl), which estimates quantiles using
+/// a bounded number of centroids. Centroids near the tails are kept
+/// small, so extreme quantiles are more accurate than the median. Larger
+/// compressions use more centroids (about `compression`) and are more
+/// accurate.
+#[derive(Debug, Clone)]
+pub struct TDigest {
+    compression: f64,
+    centroids: Vec<Centroid>,
+    buffer: Vec<Centroid>,
+    min: f64,
+    max: f64
+}
+
+impl TDigest {
+    pub fn new(compression: f64) -> TDigest {
+        assert!(compression >= 10.0,
+                "t-digest compression must be at least 10, got {}", compression);
+        
+        return TDigest {
+            compression,
+            centroids: Vec::new(),
+            buffer: Vec::new(),
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY
+        };
+    }
+
+    /// The memory used by the centroids and the buffer.
+    pub fn num_bytes(&self) -> usize {
+        return (self.centroids.capacity() + self.buffer.capacity())
+            * mem::size_of::<Centroid>();
+    }
+
+    /// Reads a digest written by `into_bytes`: the compression, min and
+    /// max, followed by the number of centroids and their means and
+    /// weights. Describes why the bytes are not a valid digest, e.g. a
+    /// BLOB that was not written by `approx_quantiles`.
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<TDigest, String> {
+        let truncated = |_| String::from("t-digest is truncated");
+        let compression = bytes.read_f64::<LittleEndian>().map_err(truncated)?;
+        if !(compression >= 10.0 && compression.is_finite()) {
+            return Err(format!("t-digest has compression {}, expected at least 10",
+                               compression));
+        }
+
+        let mut digest = TDigest::new(compression);
+        digest.min = bytes.read_f64::<LittleEndian>().map_err(truncated)?;
+        digest.max = bytes.read_f64::<LittleEndian>().map_err(truncated)?;
+
+        let num_centroids = bytes.read_u32::<LittleEndian>().map_err(truncated)? as usize;
+        if bytes.len() != 16 * num_centroids {
+            return Err(format!("t-digest with {} centroids has {} bytes of centroids, expected {}",
+                               num_centroids, bytes.len(), 16 * num_centroids));
+        }
+        
+        for _ in 0..num_centroids {
+            let mean = bytes.read_f64::<LittleEndian>().map_err(truncated)?;
+            let weight = bytes.read_f64::<LittleEndian>().map_err(truncated)?;
+            if !(mean.is_finite() && weight > 0.0 && weight.is_finite()) {
+                return Err(format!("t-digest has an invalid centroid of mean {} and weight {}",
+                                   mean, weight));
+            }
+            
+            digest.centroids.push(Centroid { mean, weight });
+        }
+
+        return Ok(digest);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.centroids.is_empty() && self.buffer.is_empty();
+    }
+
+    pub fn into_bytes(mut self) -> Vec<u8> {
+        self.compress();
+        
+        let mut bytes = Vec::with_capacity(28 + 16 * self.centroids.len());
+        bytes.write_f64::<LittleEndian>(self.compression).unwrap();
+        bytes.write_f64::<LittleEndian>(self.min).unwrap();
+        bytes.write_f64::<LittleEndian>(self.max).unwrap();
+        bytes.write_u32::<LittleEndian>(self.centroids.len() as u32).unwrap();
+        for c in self.centroids.iter() {
+            bytes.write_f64::<LittleEndian>(c.mean).unwrap();
+            bytes.write_f64::<LittleEndian>(c.weight).unwrap();
+        }
+
+        return bytes;
+    }
+
+    pub fn insert(&mut self, x: f64) {
+        self.insert_centroid(Centroid { mean: x, weight: 1.0 });
+        self.min = self.min.min(x);
+        self.max = self.max.max(x);
+    }
+
+    fn insert_centroid(&mut self, c: Centroid) {
+        self.buffer.push(c);
+        if self.buffer.len() as f64 >= 5.0 * self.compression {
+            self.compress();
+        }
+    }
+
+    pub fn merge(&mut self, other: &TDigest) {
+        for c in other.centroids.iter().chain(other.buffer.iter()) {
+            self.insert_centroid(*c);
+        }
+        
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    // the k1 scale function, and its inverse
+    fn k(&self, q: f64) -> f64 {
+        return self.compression / (2.0 * PI) * (2.0 * q - 1.0).asin();
+    }
+
+    fn q(&self, k: f64) -> f64 {
+        return ((2.0 * PI * k / self.compression).sin() + 1.0) / 2.0;
+    }
+
+    // Merges the buffered points into the centroids. Neighboring points
+    // are combined as long as the combined centroid spans at most one
+    // unit of the scale function.
+    fn compress(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        let mut points: Vec<Centroid> = self.centroids.drain(..)
+            .chain(self.buffer.drain(..))
+            .collect();
+        points.sort_by(|a, b| a.mean.partial_cmp(&b.mean).unwrap_or(Ordering::Equal));
+
+        let total: f64 = points.iter().map(|c| c.weight).sum();
+        let mut weight_before = 0.0;
+        let mut q_limit = self.q(self.k(0.0) + 1.0);
+        let mut curr = points[0];
+        
+        for p in points.into_iter().skip(1) {
+            if (weight_before + curr.weight + p.weight) / total <= q_limit {
+                let weight = curr.weight + p.weight;
+                curr.mean += (p.mean - curr.mean) * p.weight / weight;
+                curr.weight = weight;
+                continue;
+            }
+
+            weight_before += curr.weight;
+            self.centroids.push(curr);
+            q_limit = self.q(self.k(weight_before / total) + 1.0);
+            curr = p;
+        }
+
+        self.centroids.push(curr);
+    }
+
+    /// Estimates the value at quantile `q` (between 0 and 1), or None if
+    /// the digest is empty.
+    pub fn quantile(&mut self, q: f64) -> Option<f64> {
+        self.compress();
+        if self.centroids.is_empty() {
+            return None;
+        }
+
+        // each centroid is centered at the middle of its weight, and
+        // values in between are interpolated. The min and max sit at
+        // either end.
+        let total: f64 = self.centroids.iter().map(|c| c.weight).sum();
+        let target = q * total;
+
+        let mut prev_pos = 0.0;
+        let mut prev_mean = self.min;
+        let mut weight_before = 0.0;
+        for c in self.centroids.iter() {
+            let pos = weight_before + c.weight / 2.0;
+            if target < pos {
+                return Some(interpolate(prev_pos, prev_mean, pos, c.mean, target));
+            }
+
+            prev_pos = pos;
+            prev_mean = c.mean;
+            weight_before += c.weight;
+        }
+
+        return Some(interpolate(prev_pos, prev_mean, total, self.max, target));
+    }
+}
+
+fn interpolate(x0: f64, y0: f64, x1: f64, y1: f64, x: f64) -> f64 {
+    if x1 <= x0 {
+        return y1;
+    }
+    
+    return y0 + (y1 - y0) * (x - x0) / (x1 - x0);
+}
+
+fn numeric_value(d: &Data) -> Option<f64> {
+    return match d {
+        Data::Integer(i) => Some(*i as f64),
+        Data::Real(_) | Data::Decimal(_, _) => Some(d.as_f64()),
+        Data::Null => None,
+        _ => panic!("approx_quantiles requires a numeric column, got {:?}", d)
+    };
+}
+
+/// Builds a t-digest of the non-NULL values of a column, producing the
+/// estimated value at a quantile, or the digest itself as a BLOB when no
+/// quantile is given. A group with no values produces NULL.
+pub struct TDigestAggregate {
+    row_idx: usize,
+    compression: f64,
+    quantile: Option<f64>,
+    digest: TDigest
+}
+
+impl TDigestAggregate {
+    pub fn new(row_idx: usize, compression: f64, quantile: Option<f64>) -> TDigestAggregate {
+        return TDigestAggregate {
+            row_idx, compression, quantile,
+            digest: TDigest::new(compression)
+        };
+    }
+}
+
+impl Aggregate for TDigestAggregate {
+    fn consume(&mut self, row: &[Data]) {
+        if let Some(x) = numeric_value(&row[self.row_idx]) {
+            self.digest.insert(x);
+        }
+    }
+
+    fn produce(&mut self) -> Data {
+        let mut digest = std::mem::replace(&mut self.digest,
+                                           TDigest::new(self.compression));
+
+        // like merge_tdigest, a group without values produces NULL
+        return match self.quantile {
+            _ if digest.is_empty() => Data::Null,
+            Some(q) => digest.quantile(q).map(Data::Real).unwrap_or(Data::Null),
+            None => Data::Blob(digest.into_bytes())
+        };
+    }
+
+    fn out_type(&self, in_types: &[DataType]) -> DataType {
+        match in_types[self.row_idx] {
+            DataType::INTEGER | DataType::REAL | DataType::DECIMAL(_, _) => {},
+            ref dt => panic!("approx_quantiles requires a numeric column, got {:?}", dt)
+        };
+
+        return match self.quantile {
+            Some(_) => DataType::REAL,
+            None => DataType::BLOB
+        };
+    }
+
+    fn partial_state(&mut self) -> Vec<Data> {
+        let digest = std::mem::replace(&mut self.digest,
+                                       TDigest::new(self.compression));
+        return vec![Data::Blob(digest.into_bytes())];
+    }
+
+    fn merge(&mut self, state: &[Data]) {
+        if let Data::Blob(ref b) = state[0] {
+            let other = TDigest::from_bytes(b)
+                .unwrap_or_else(|e| panic!("Invalid approx_quantiles state: {}", e));
+            self.digest.merge(&other);
+        }
+    }
+
+    fn state_types(&self, _in_types: &[DataType]) -> Vec<DataType> {
+        return vec![DataType::BLOB];
+    }
+
+    fn state_size(&self) -> usize {
+        return self.digest.num_bytes();
+    }
+}
+
+/// Merges a BLOB column of t-digests, producing the merged digest or the
+/// estimated value at a quantile. A group with no digests produces NULL.
+pub struct MergeTDigestAggregate {
+    row_idx: usize,
+    quantile: Option<f64>,
+    digest: Option<TDigest>
+}
+
+impl MergeTDigestAggregate {
+    pub fn new(row_idx: usize, quantile: Option<f64>) -> MergeTDigestAggregate {
+        return MergeTDigestAggregate {
+            row_idx, quantile,
+            digest: None
+        };
+    }
+
+    fn update(&mut self, nxt: &Data) {
+        let other = match nxt {
+            Data::Blob(b) => TDigest::from_bytes(b).unwrap_or_else(
+                |e| panic!("merge_tdigest on column {} got an invalid digest: {}", self.row_idx, e)),
+            Data::Null => { return; },
+            d => panic!("merge_tdigest requires a BLOB column, got {:?}", d)
+        };
+
+        match self.digest {
+            Some(ref mut digest) => digest.merge(&other),
+            None => { self.digest = Some(other); }
+        };
+    }
+}
+
+impl Aggregate for MergeTDigestAggregate {
+    fn consume(&mut self, row: &[Data]) {
+        self.update(&row[self.row_idx]);
+    }
+
+    fn produce(&mut self) -> Data {
+        let mut digest = match self.digest.take() {
+            Some(digest) if !digest.is_empty() => digest,
+            _ => { return Data::Null; }
+        };
+        
+        return match self.quantile {
+            Some(q) => digest.quantile(q).map(Data::Real).unwrap_or(Data::Null),
+            None => Data::Blob(digest.into_bytes())
+        };
+    }
+
+    fn out_type(&self, in_types: &[DataType]) -> DataType {
+        if let DataType::BLOB = in_types[self.row_idx] {
+        } else {
+            panic!("merge_tdigest requires a BLOB column, got {:?}", in_types[self.row_idx]);
+        }
+
+        return match self.quantile {
+            Some(_) => DataType::REAL,
+            None => DataType::BLOB
+        };
+    }
+
+    fn partial_state(&mut self) -> Vec<Data> {
+        let state = self.digest.take()
+            .map(|digest| Data::Blob(digest.into_bytes()))
+            .unwrap_or(Data::Null);
+        return vec![state];
+    }
+
+    fn merge(&mut self, state: &[Data]) {
+        self.update(&state[0]);
+    }
+
+    fn state_types(&self, _in_types: &[DataType]) -> Vec<DataType> {
+        return vec![DataType::BLOB];
+    }
+
+    fn state_size(&self) -> usize {
+        return self.digest.as_ref().map(|d| d.num_bytes()).unwrap_or(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use agg::{Aggregate, new, from_json};
+    use agg::tdigest::TDigest;
+    use data::{Data, DataType};
+    use rand::prelude::*;
+
+    #[test]
+    fn small_test() {
+        let mut digest = TDigest::new(100.0);
+        assert_eq!(digest.quantile(0.5), None);
+        
+        for v in &[3.0, 1.0, 5.0, 2.0, 4.0] {
+            digest.insert(*v);
+        }
+
+        // few enough values that every one is its own centroid
+        assert_eq!(digest.quantile(0.0), Some(1.0));
+        assert_eq!(digest.quantile(0.5), Some(3.0));
+        assert_eq!(digest.quantile(1.0), Some(5.0));
+    }
+
+    #[test]
+    fn uniform_test() {
+        let mut digest = TDigest::new(100.0);
+        let mut values: Vec<f64> = (0..100_000).map(|v| v as f64).collect();
+        thread_rng().shuffle(&mut values);
+
+        for v in values.iter() {
+            digest.insert(*v);
+        }
+
+        // memory is bounded by the compression, not the number of values
+        digest.compress();
+        assert!(digest.centroids.len() < 200);
+
+        for &q in &[0.001, 0.01, 0.25, 0.5, 0.75, 0.99, 0.999] {
+            let est = digest.quantile(q).unwrap();
+            assert_abs_diff_eq!(est, q * 100_000.0, epsilon = 500.0);
+        }
+    }
+
+    #[test]
+    fn merge_test() {
+        let mut digests = vec![TDigest::new(100.0), TDigest::new(100.0)];
+        for v in 0..50_000 {
+            digests[v % 2].insert(v as f64);
+        }
+
+        // merging serialized digests
+        let bytes = digests[1].clone().into_bytes();
+        let mut merged = digests[0].clone();
+        merged.merge(&TDigest::from_bytes(&bytes).unwrap());
+
+        assert_eq!(merged.quantile(0.0), Some(0.0));
+        assert_eq!(merged.quantile(1.0), Some(49_999.0));
+        assert_abs_diff_eq!(merged.quantile(0.5).unwrap(), 25_000.0, epsilon = 250.0);
+        assert_abs_diff_eq!(merged.quantile(0.9).unwrap(), 45_000.0, epsilon = 250.0);
+    }
+
+    #[test]
+    fn bad_bytes_test() {
+        let err = |b: &[u8]| TDigest::from_bytes(b).err().unwrap();
+        let mut digest = TDigest::new(100.0);
+        digest.insert(1.0);
+        digest.insert(2.0);
+        let bytes = digest.into_bytes();
+
+        assert_eq!(err(&bytes[..10]), "t-digest is truncated");
+        assert_eq!(err(&bytes[..bytes.len() - 1]),
+                   "t-digest with 2 centroids has 31 bytes of centroids, expected 32");
+        assert_eq!(err(&[0; 28]), "t-digest has compression 0, expected at least 10");
+
+        let mut bad_weight = bytes.clone();
+        let len = bad_weight.len();
+        bad_weight[len - 8..].copy_from_slice(&(-1.0f64).to_le_bytes());
+        assert_eq!(err(&bad_weight),
+                   "t-digest has an invalid centroid of mean 2 and weight -1");
+    }
+
+    #[test]
+    #[should_panic(expected = "merge_tdigest on column 0 got an invalid digest: t-digest is truncated")]
+    fn bad_digest_test() {
+        let mut merge = new("merge_tdigest", 0);
+        merge.consume(&[Data::Blob(vec![1, 2, 3])]);
+    }
+
+    #[test]
+    fn aggregate_test() {
+        let mut p90 = from_json(&json!({"op": "approx_quantiles", "col": 0, "p": 0.9}));
+        let mut sketch = from_json(&json!({"op": "approx_quantiles", "col": 0,
+                                           "compression": 50}));
+        let mut merge = from_json(&json!({"op": "merge_tdigest", "col": 0, "p": 0.9}));
+        let mut merge_sketch = new("merge_tdigest", 0);
+
+        assert_matches!(p90.out_type(&[DataType::INTEGER]), DataType::REAL);
+        assert_matches!(sketch.out_type(&[DataType::INTEGER]), DataType::BLOB);
+        assert_matches!(merge.out_type(&[DataType::BLOB]), DataType::REAL);
+
+        for part in 0..4 {
+            for v in 0..1000 {
+                let d = Data::Integer(part * 1000 + v);
+                p90.consume(&[d.clone()]);
+                sketch.consume(&[d]);
+            }
+            sketch.consume(&[Data::Null]);
+
+            let s = sketch.produce();
+            merge.consume(&[s.clone()]);
+            merge_sketch.consume(&[s]);
+        }
+
+        assert_abs_diff_eq!(p90.produce().as_f64(), 3600.0, epsilon = 40.0);
+        assert_abs_diff_eq!(merge.produce().as_f64(), 3600.0, epsilon = 40.0);
+        assert_matches!(merge_sketch.produce(), Data::Blob(_));
+
+        assert_eq!(p90.produce(), Data::Null);
+        assert_eq!(merge.produce(), Data::Null);
+
+        // empty or all-NULL groups produce NULL, whether or not they
+        // produce a digest
+        sketch.consume(&[Data::Null]);
+        assert_eq!(sketch.produce(), Data::Null);
+        assert_eq!(merge_sketch.produce(), Data::Null);
+    }
+}
@@ -0,0 +1,306 @@
+// < begin copyright > 
+// Copyright Ryan Marcus 2018
+// 
+// This file is part of byoo.
+// 
+// byoo is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// byoo is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with byoo.  If not, see <http://www.gnu.org/licenses/>.
+// 
+// < end copyright > 
+use agg::Aggregate;
+use data::{Data, DataType};
+
+// Variance and covariance are computed with Welford's online algorithm,
+// which updates a running mean and sum of squared differences from the
+// mean, instead of the (numerically unstable) sum of squares.
+
+fn numeric_value(d: &Data) -> Option<f64> {
+    return match d {
+        Data::Integer(i) => Some(*i as f64),
+        Data::Real(_) | Data::Decimal(_, _) => Some(d.as_f64()),
+        Data::Null => None,
+        _ => panic!("Statistical aggregates require numeric columns, got {:?}", d)
+    };
+}
+
+#[derive(Clone, Copy)]
+pub enum VarianceKind {
+    VarSamp, VarPop, StddevSamp, StddevPop
+}
+
+pub struct VarianceAggregate {
+    row_idx: usize,
+    kind: VarianceKind,
+    count: u64,
+    mean: f64,
+    m2: f64
+}
+
+impl VarianceAggregate {
+    pub fn new(row_idx: usize, kind: VarianceKind) -> VarianceAggregate {
+        return VarianceAggregate {
+            row_idx, kind,
+            count: 0,
+            mean: 0.0,
+            m2: 0.0
+        };
+    }
+}
+
+impl Aggregate for VarianceAggregate {
+    fn consume(&mut self, row: &[Data]) {
+        let x = match numeric_value(&row[self.row_idx]) {
+            Some(x) => x,
+            None => { return; }
+        };
+
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    fn produce(&mut self) -> Data {
+        let n = self.count as f64;
+        let m2 = self.m2;
+        self.count = 0;
+        self.mean = 0.0;
+        self.m2 = 0.0;
+
+        // like SQL, the sample variants are NULL for a single value
+        let var = match self.kind {
+            VarianceKind::VarSamp | VarianceKind::StddevSamp if n >= 2.0 => m2 / (n - 1.0),
+            VarianceKind::VarPop | VarianceKind::StddevPop if n >= 1.0 => m2 / n,
+            _ => { return Data::Null; }
+        };
+
+        return match self.kind {
+            VarianceKind::VarSamp | VarianceKind::VarPop => Data::Real(var),
+            VarianceKind::StddevSamp | VarianceKind::StddevPop => Data::Real(var.sqrt())
+        };
+    }
+
+    fn out_type(&self, _in_types: &[DataType]) -> DataType {
+        return DataType::REAL;
+    }
+
+    fn partial_state(&mut self) -> Vec<Data> {
+        let state = vec![Data::Integer(self.count as i64),
+                         Data::Real(self.mean),
+                         Data::Real(self.m2)];
+        *self = VarianceAggregate::new(self.row_idx, self.kind);
+        return state;
+    }
+
+    fn merge(&mut self, state: &[Data]) {
+        // Chan et al.'s parallel form of Welford's algorithm
+        let count = state[0].as_i64() as u64;
+        if count == 0 {
+            return;
+        }
+
+        let total = (self.count + count) as f64;
+        let weight = self.count as f64 * count as f64 / total;
+        let delta = state[1].as_f64() - self.mean;
+
+        self.mean += delta * count as f64 / total;
+        self.m2 += state[2].as_f64() + delta * delta * weight;
+        self.count += count;
+    }
+
+    fn state_types(&self, _in_types: &[DataType]) -> Vec<DataType> {
+        return vec![DataType::INTEGER, DataType::REAL, DataType::REAL];
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum CovarianceKind {
+    CovarSamp, CovarPop, Corr
+}
+
+/// Aggregates over pairs of columns. Rows where either value is NULL
+/// are skipped.
+pub struct CovarianceAggregate {
+    x_idx: usize,
+    y_idx: usize,
+    kind: CovarianceKind,
+    count: u64,
+    mean_x: f64,
+    mean_y: f64,
+    m2_x: f64,
+    m2_y: f64,
+    co_moment: f64
+}
+
+impl CovarianceAggregate {
+    pub fn new(x_idx: usize, y_idx: usize, kind: CovarianceKind) -> CovarianceAggregate {
+        return CovarianceAggregate {
+            x_idx, y_idx, kind,
+            count: 0,
+            mean_x: 0.0,
+            mean_y: 0.0,
+            m2_x: 0.0,
+            m2_y: 0.0,
+            co_moment: 0.0
+        };
+    }
+}
+
+impl Aggregate for CovarianceAggregate {
+    fn consume(&mut self, row: &[Data]) {
+        let (x, y) = match (numeric_value(&row[self.x_idx]),
+                            numeric_value(&row[self.y_idx])) {
+            (Some(x), Some(y)) => (x, y),
+            _ => { return; }
+        };
+
+        self.count += 1;
+        let n = self.count as f64;
+        let dx = x - self.mean_x;
+        let dy = y - self.mean_y;
+        self.mean_x += dx / n;
+        self.mean_y += dy / n;
+        self.m2_x += dx * (x - self.mean_x);
+        self.m2_y += dy * (y - self.mean_y);
+        self.co_moment += dx * (y - self.mean_y);
+    }
+
+    fn produce(&mut self) -> Data {
+        let n = self.count as f64;
+        let (m2_x, m2_y, c) = (self.m2_x, self.m2_y, self.co_moment);
+        *self = CovarianceAggregate::new(self.x_idx, self.y_idx, self.kind);
+
+        return match self.kind {
+            CovarianceKind::CovarSamp if n >= 2.0 => Data::Real(c / (n - 1.0)),
+            CovarianceKind::CovarPop if n >= 1.0 => Data::Real(c / n),
+            // the correlation is undefined if either column is constant
+            CovarianceKind::Corr if n >= 1.0 && m2_x != 0.0 && m2_y != 0.0 => {
+                Data::Real(c / (m2_x * m2_y).sqrt())
+            },
+            _ => Data::Null
+        };
+    }
+
+    fn out_type(&self, _in_types: &[DataType]) -> DataType {
+        return DataType::REAL;
+    }
+
+    fn partial_state(&mut self) -> Vec<Data> {
+        let state = vec![Data::Integer(self.count as i64),
+                         Data::Real(self.mean_x),
+                         Data::Real(self.mean_y),
+                         Data::Real(self.m2_x),
+                         Data::Real(self.m2_y),
+                         Data::Real(self.co_moment)];
+        *self = CovarianceAggregate::new(self.x_idx, self.y_idx, self.kind);
+        return state;
+    }
+
+    fn merge(&mut self, state: &[Data]) {
+        let count = state[0].as_i64() as u64;
+        if count == 0 {
+            return;
+        }
+
+        let total = (self.count + count) as f64;
+        let weight = self.count as f64 * count as f64 / total;
+        let dx = state[1].as_f64() - self.mean_x;
+        let dy = state[2].as_f64() - self.mean_y;
+
+        self.mean_x += dx * count as f64 / total;
+        self.mean_y += dy * count as f64 / total;
+        self.m2_x += state[3].as_f64() + dx * dx * weight;
+        self.m2_y += state[4].as_f64() + dy * dy * weight;
+        self.co_moment += state[5].as_f64() + dx * dy * weight;
+        self.count += count;
+    }
+
+    fn state_types(&self, _in_types: &[DataType]) -> Vec<DataType> {
+        return vec![DataType::INTEGER, DataType::REAL, DataType::REAL,
+                    DataType::REAL, DataType::REAL, DataType::REAL];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use agg::Aggregate;
+    use agg::variance::{VarianceAggregate, VarianceKind,
+                        CovarianceAggregate, CovarianceKind};
+    use data::Data;
+
+    fn real(d: Data) -> f64 {
+        return d.as_f64();
+    }
+
+    #[test]
+    fn variance_test() {
+        let data = [2, 4, 4, 4, 5, 5, 7, 9];
+        let mut aggs = vec![VarianceAggregate::new(0, VarianceKind::VarSamp),
+                            VarianceAggregate::new(0, VarianceKind::VarPop),
+                            VarianceAggregate::new(0, VarianceKind::StddevSamp),
+                            VarianceAggregate::new(0, VarianceKind::StddevPop)];
+
+        for agg in aggs.iter_mut() {
+            for v in data.iter() {
+                agg.consume(&[Data::Integer(*v)]);
+            }
+            agg.consume(&[Data::Null]);
+        }
+
+        assert_relative_eq!(real(aggs[0].produce()), 32.0 / 7.0);
+        assert_relative_eq!(real(aggs[1].produce()), 4.0);
+        assert_relative_eq!(real(aggs[2].produce()), (32.0f64 / 7.0).sqrt());
+        assert_relative_eq!(real(aggs[3].produce()), 2.0);
+
+        // a single value has no sample variance
+        aggs[0].consume(&[Data::Real(1.0)]);
+        aggs[1].consume(&[Data::Real(1.0)]);
+        assert!(aggs[0].produce().is_null());
+        assert_eq!(aggs[1].produce(), Data::Real(0.0));
+    }
+
+    #[test]
+    fn stable_variance_test() {
+        // the naive sum of squares loses all precision here
+        let mut agg = VarianceAggregate::new(0, VarianceKind::VarSamp);
+        for v in &[4.0, 7.0, 13.0, 16.0] {
+            agg.consume(&[Data::Real(1e9 + v)]);
+        }
+        assert_relative_eq!(real(agg.produce()), 30.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn covariance_test() {
+        let xs = [1.0, 2.0, 3.0, 4.0, 5.0];
+        let ys = [2.0, 4.0, 5.0, 4.0, 5.0];
+        let mut aggs = vec![CovarianceAggregate::new(0, 1, CovarianceKind::CovarSamp),
+                            CovarianceAggregate::new(0, 1, CovarianceKind::CovarPop),
+                            CovarianceAggregate::new(0, 1, CovarianceKind::Corr)];
+
+        for agg in aggs.iter_mut() {
+            for (x, y) in xs.iter().zip(ys.iter()) {
+                agg.consume(&[Data::Real(*x), Data::Real(*y)]);
+            }
+            agg.consume(&[Data::Real(100.0), Data::Null]);
+        }
+
+        assert_relative_eq!(real(aggs[0].produce()), 1.5);
+        assert_relative_eq!(real(aggs[1].produce()), 1.2);
+        assert_relative_eq!(real(aggs[2].produce()), 6.0 / (10.0f64 * 6.0).sqrt());
+
+        // constant columns have no correlation
+        aggs[2].consume(&[Data::Real(1.0), Data::Real(1.0)]);
+        aggs[2].consume(&[Data::Real(1.0), Data::Real(2.0)]);
+        assert!(aggs[2].produce().is_null());
+    }
+}
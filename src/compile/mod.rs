@@ -22,11 +22,15 @@ use predicate::Predicate;
 use serde_json;
 use std::collections::{VecDeque};
 use operator_buffer::{OperatorReadBuffer, OperatorWriteBuffer, make_buffer_pair};
-use operator::{ConstructableOperator, Sort, ColumnUnion};
+use operator::{ConstructableOperator, Sort, ColumnUnion, Compute, compute_out_types,
+               check_compute_types};
 use operator::output::{CsvOutput, ColumnarOutput};
 use operator::scan::{CsvScan, ColumnarScan};
 use operator::join::{LoopJoin, MergeJoin, HashJoin};
-use operator::groupby::{SortedGroupBy, HashedGroupBy, AllRowsGroupBy};
+use operator::groupby::{SortedGroupBy, HashedGroupBy, AllRowsGroupBy, GroupingSetsGroupBy};
+use operator::groupby::{HashedDistinct, SortedDistinct};
+use operator::groupby::{json_to_key_cols, json_to_grouping_sets, json_to_memory_budget};
+use operator::groupby::{json_to_parallelism, json_to_distinct_cols};
 use agg;
 use std::fs::File;
 use std::fmt;
@@ -38,7 +42,8 @@ use std::thread::JoinHandle;
 enum Operator {
     Union, Project, Filter, LoopJoin, MergeJoin, HashJoin,
     Sort, ColumnarRead, CSVRead, CSVOut, ColumnarOut,
-    SortedGroupBy, HashedGroupBy, AllRowsGroupBy
+    SortedGroupBy, HashedGroupBy, AllRowsGroupBy, GroupingSetsGroupBy,
+    HashedDistinct, SortedDistinct, Compute
 }
 
 static OPERATOR_LIST: &[Operator] = &[
@@ -55,7 +60,11 @@ static OPERATOR_LIST: &[Operator] = &[
     Operator::ColumnarOut,
     Operator::SortedGroupBy,
     Operator::HashedGroupBy,
-    Operator::AllRowsGroupBy
+    Operator::AllRowsGroupBy,
+    Operator::GroupingSetsGroupBy,
+    Operator::HashedDistinct,
+    Operator::SortedDistinct,
+    Operator::Compute
 ];
 
 impl Operator {
@@ -75,6 +84,10 @@ impl Operator {
             "sorted group by" => Operator::SortedGroupBy,
             "hashed group by" => Operator::HashedGroupBy,
             "all rows group by" => Operator::AllRowsGroupBy,
+            "grouping sets group by" => Operator::GroupingSetsGroupBy,
+            "hashed distinct" => Operator::HashedDistinct,
+            "sorted distinct" => Operator::SortedDistinct,
+            "compute" => Operator::Compute,
             _ => panic!("invalid opcode")
         };
     }
@@ -114,7 +127,11 @@ impl fmt::Display for Operator {
             Operator::ColumnarOut => write!(f, "columnar out"),
             Operator::SortedGroupBy => write!(f, "sorted group by"),
             Operator::HashedGroupBy => write!(f, "hashed group by"),
-            Operator::AllRowsGroupBy => write!(f, "all rows group by")
+            Operator::AllRowsGroupBy => write!(f, "all rows group by"),
+            Operator::GroupingSetsGroupBy => write!(f, "grouping sets group by"),
+            Operator::HashedDistinct => write!(f, "hashed distinct"),
+            Operator::SortedDistinct => write!(f, "sorted distinct"),
+            Operator::Compute => write!(f, "compute")
         }
     }
 }
@@ -135,6 +152,10 @@ fn inputs_per_op(op: &str) -> ChildCount {
         "sorted group by" => ChildCount::Specific(1),
         "hashed group by" => ChildCount::Specific(1),
         "all rows group by" => ChildCount::Specific(1),
+        "grouping sets group by" => ChildCount::Specific(1),
+        "hashed distinct" => ChildCount::Specific(1),
+        "sorted distinct" => ChildCount::Specific(1),
+        "compute" => ChildCount::Specific(1),
         _ => panic!("unknown op code")
     };
 }
@@ -190,7 +211,10 @@ fn get_operator_out_type(opcode: &Operator,
                     .map(|idx| in_types[0][idx].clone())
                     .collect());
         },
-        Operator::Sort | Operator::Filter => {
+        Operator::Sort
+            | Operator::Filter
+            | Operator::HashedDistinct
+            | Operator::SortedDistinct => {
             return OutType::Known(in_types[0].clone());
         },
         Operator::ColumnarRead => {
@@ -210,15 +234,27 @@ fn get_operator_out_type(opcode: &Operator,
             | Operator::AllRowsGroupBy => {
             let mut input_types = in_types[0].clone();
 
-            for agg_type in options["aggregates"].as_array().unwrap() {
-                let op = agg_type["op"].as_str().unwrap();
-                let col_idx = agg_type["col"].as_i64().unwrap() as usize;
-
-                let agg = agg::new(op, col_idx);
-                input_types.push(agg.out_type(&in_types[0][col_idx]));
+            for agg_json in options["aggregates"].as_array().unwrap() {
+                let agg = agg::from_json(agg_json);
+                input_types.push(agg.out_type(&in_types[0]));
             }
             
             return OutType::Known(input_types);
+        },
+        Operator::GroupingSetsGroupBy => {
+            let mut input_types = in_types[0].clone();
+
+            for agg_json in options["aggregates"].as_array().unwrap() {
+                let agg = agg::from_json(agg_json);
+                input_types.push(agg.out_type(&in_types[0]));
+            }
+
+            // the grouping id
+            input_types.push(DataType::INTEGER);
+            return OutType::Known(input_types);
+        },
+        Operator::Compute => {
+            return OutType::Known(compute_out_types(options, &in_types[0]));
         }
     };
 }
@@ -264,18 +300,21 @@ impl OperatorNode {
     }
 
 
-    fn derive_types(&mut self) {
+    fn derive_types(&mut self) -> Result<(), String> {
         if let OutType::Known(_) = self.out_type {
             // we have already derived our type
-            return;
+            return Ok(());
         }
         
         // otherwise, I have to derive my output type
         for child in self.children.iter_mut() {
-            child.derive_types();
+            child.derive_types()?;
         }
         
         let my_in = self.get_in_types_from_children();
+        self.check_types(&my_in)
+            .map_err(|e| format!("{} operator {}: {}", self.opcode, self.id, e))?;
+        
         self.out_type = get_operator_out_type(&self.opcode,
                                               &self.options,
                                               &my_in);
@@ -284,6 +323,77 @@ impl OperatorNode {
         } else {
             InType::Known(my_in)
         };
+
+        return Ok(());
+    }
+
+    // validates this operator's options against the types of its inputs
+    fn check_types(&self, in_types: &[Vec<DataType>]) -> Result<(), String> {
+        match self.opcode {
+            Operator::Filter => {
+                return Predicate::try_from_options(&self.options)?
+                    .type_check(&in_types[0]);
+            },
+            Operator::LoopJoin => {
+                // the predicate sees the left columns followed by the right
+                return Predicate::try_from_options(&self.options)?
+                    .type_check(&in_types.concat());
+            },
+            Operator::SortedGroupBy
+                | Operator::HashedGroupBy
+                | Operator::AllRowsGroupBy
+                | Operator::GroupingSetsGroupBy => {
+                let key_cols = match self.opcode {
+                    Operator::AllRowsGroupBy => vec![],
+                    Operator::GroupingSetsGroupBy => json_to_grouping_sets(&self.options)?.0,
+                    _ => json_to_key_cols(&self.options)?
+                };
+
+                for col in key_cols {
+                    if col >= in_types[0].len() {
+                        return Err(format!("group key column {} out of range for {} input columns",
+                                           col, in_types[0].len()));
+                    }
+                }
+
+                if let Operator::HashedGroupBy | Operator::GroupingSetsGroupBy = self.opcode {
+                    json_to_memory_budget(&self.options)?;
+                }
+
+                if let Operator::HashedGroupBy = self.opcode {
+                    json_to_parallelism(&self.options)?;
+                }
+
+                let aggs = self.options["aggregates"].as_array()
+                    .ok_or_else(|| String::from("group by requires an aggregates array"))?;
+                for agg_json in aggs {
+                    agg::mode_from_json(agg_json)?;
+
+                    // aggregate filters see the group by's input rows
+                    if let Some(filter) = agg::filter_from_json(agg_json) {
+                        filter?.type_check(&in_types[0])?;
+                    }
+                }
+                return Ok(());
+            },
+            Operator::HashedDistinct | Operator::SortedDistinct => {
+                for col in json_to_distinct_cols(&self.options, in_types[0].len())? {
+                    if col >= in_types[0].len() {
+                        return Err(format!("distinct column {} out of range for {} input columns",
+                                           col, in_types[0].len()));
+                    }
+                }
+
+                if let Operator::HashedDistinct = self.opcode {
+                    json_to_memory_budget(&self.options)?;
+                }
+                return Ok(());
+            },
+            Operator::Compute => {
+                return check_compute_types(&self.options, &in_types[0]);
+            },
+            _ => return Ok(())
+        };
     }
 
     pub fn start(self) -> JoinHandle<()> {
@@ -356,8 +466,7 @@ impl OperatorNode {
             Operator::Filter => {
                 // we need to add to the output buffer's filters, and then pass
                 // it along to the child.
-                let predicate = Predicate::from_json(
-                    &self.options["predicate"]);
+                let predicate = Predicate::from_options(&self.options);
 
                 output.as_mut().unwrap().add_filter(predicate);
                 assert_eq!(self.children.len(), 1);
@@ -389,7 +498,11 @@ impl OperatorNode {
             Operator::Union => spawn_op!(ColumnUnion, output, read_bufs, f, self.options),
             Operator::SortedGroupBy => spawn_op!(SortedGroupBy, output, read_bufs, f, self.options),
             Operator::HashedGroupBy => spawn_op!(HashedGroupBy, output, read_bufs, f, self.options),
-            Operator::AllRowsGroupBy => spawn_op!(AllRowsGroupBy, output, read_bufs, f, self.options)
+            Operator::AllRowsGroupBy => spawn_op!(AllRowsGroupBy, output, read_bufs, f, self.options),
+            Operator::GroupingSetsGroupBy => spawn_op!(GroupingSetsGroupBy, output, read_bufs, f, self.options),
+            Operator::HashedDistinct => spawn_op!(HashedDistinct, output, read_bufs, f, self.options),
+            Operator::SortedDistinct => spawn_op!(SortedDistinct, output, read_bufs, f, self.options),
+            Operator::Compute => spawn_op!(Compute, output, read_bufs, f, self.options)
         };
 
         //  next, we have to start the children.
@@ -401,16 +514,23 @@ impl OperatorNode {
 }
 
 pub fn compile(json: String) -> OperatorNode {
+    return try_compile(json).unwrap_or_else(|e| panic!("{}", e));
+}
+
+/// Like `compile`, but returns an error describing the problem if the
+/// plan is invalid (e.g., a predicate does not match its input's types).
+pub fn try_compile(json: String) -> Result<OperatorNode, String> {
     let parsed = serde_json::from_str(json.as_str())
-        .unwrap();
+        .map_err(|e| format!("Invalid plan JSON: {}", e))?;
     drop(json);
 
-    return create_op_tree(&parsed, 0).1;
+    return create_op_tree(&parsed, 0).map(|(_, root)| root);
 }
 
 fn create_op_tree(root: &serde_json::Value, nxt_id: usize)
-                  -> (usize, OperatorNode) {
-    let opcode = root["op"].as_str().unwrap();
+                  -> Result<(usize, OperatorNode), String> {
+    let opcode = root["op"].as_str()
+        .ok_or_else(|| format!("Operator {} has no op", nxt_id))?;
 
     let mut to_r = OperatorNode::new(nxt_id,
                                      opcode,
@@ -434,14 +554,14 @@ fn create_op_tree(root: &serde_json::Value, nxt_id: usize)
 
     let mut num_added = 1;
     for v in children {
-        let (nc, c) = create_op_tree(&v, nxt_id + num_added);
+        let (nc, c) = create_op_tree(&v, nxt_id + num_added)?;
         to_r.add_child(c);
         num_added += nc;
     }
 
-    to_r.derive_types();
+    to_r.derive_types()?;
 
-    return (num_added, to_r);
+    return Ok((num_added, to_r));
 }
 
 fn label_for_node(node: &OperatorNode) -> String {
@@ -548,7 +668,7 @@ mod tests {
 }
 ").unwrap();
 
-        let (count, root) = create_op_tree(&json, 0);
+        let (count, root) = create_op_tree(&json, 0).unwrap();
         assert_eq!(count, 3);
         
         let gv = tree_to_gv(&root);
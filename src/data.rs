@@ -24,13 +24,21 @@ use base64;
 use std::cmp::Ordering;
 use std::{fmt, ops};
 use std::hash::{Hash, Hasher};
+use std::convert::TryFrom;
+use datetime;
+use datetime::DatePart;
+use decimal;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, Debug, Hash)]
 pub enum DataType {
     INTEGER,
     REAL,
     TEXT,
-    BLOB
+    BLOB,
+    DATE,
+    TIMESTAMP,
+    DECIMAL(u8, u8)
 }
 
 #[derive(Debug, Clone)]
@@ -38,17 +46,34 @@ pub enum Data {
     Integer(i64),
     Real(f64),
     Text(String),
-    Blob(Vec<u8>)
+    Blob(Vec<u8>),
+    Date(i32),
+    Timestamp(i64),
+    Decimal(i128, u8),
+    Null
 }
 
+/// The smallest scale of a DECIMAL produced by division (e.g., AVG).
+pub const DECIMAL_DIV_MIN_SCALE: u8 = 6;
+
+// DECIMAL type codes store the precision and scale alongside a marker bit
+const DECIMAL_CODE_FLAG: u16 = 0x8000;
+
 
 impl DataType {
     pub fn from_code(code: u16) -> DataType {
+        if code & DECIMAL_CODE_FLAG != 0 {
+            return DataType::DECIMAL(((code >> 7) & 0xFF) as u8,
+                                     (code & 0x7F) as u8);
+        }
+        
         return match code {
             1 => DataType::INTEGER,
             2 => DataType::REAL,
             3 => DataType::TEXT,
             4 => DataType::BLOB,
+            5 => DataType::DATE,
+            6 => DataType::TIMESTAMP,
             _ => { panic!("Unknown datatype code"); }
         };
     }
@@ -58,18 +83,77 @@ impl DataType {
             DataType::INTEGER => 1,
             DataType::REAL => 2,
             DataType::TEXT => 3,
-            DataType::BLOB => 4
+            DataType::BLOB => 4,
+            DataType::DATE => 5,
+            DataType::TIMESTAMP => 6,
+            DataType::DECIMAL(p, s) => DECIMAL_CODE_FLAG
+                | (u16::from(*p) << 7) | u16::from(*s)
         };
     }
 
     pub fn from_string_code(code: &str) -> DataType {
-        match code {
-            "INTEGER" => DataType::INTEGER,
-            "TEXT" => DataType::TEXT,
-            "REAL" => DataType::REAL,
-            "BLOB" => DataType::BLOB,
-            _ => panic!("unknown datatype string")
+        return DataType::try_from_string_code(code).unwrap_or_else(|e| panic!("{}", e));
+    }
+
+    pub fn try_from_string_code(code: &str) -> std::result::Result<DataType, String> {
+        if code.starts_with("DECIMAL") {
+            return DataType::decimal_from_string_code(code);
         }
+        
+        return match code {
+            "INTEGER" => Ok(DataType::INTEGER),
+            "TEXT" => Ok(DataType::TEXT),
+            "REAL" => Ok(DataType::REAL),
+            "BLOB" => Ok(DataType::BLOB),
+            "DATE" => Ok(DataType::DATE),
+            "TIMESTAMP" => Ok(DataType::TIMESTAMP),
+            _ => Err(format!("unknown datatype string {}", code))
+        };
+    }
+
+    /// Whether values of this type can be cast to the given type. NULLs
+    /// and TEXT can be cast to anything, anything can be cast to TEXT,
+    /// and otherwise only numbers and dates can be cast among themselves.
+    pub fn can_cast_to(&self, to: &DataType) -> bool {
+        let is_number = |t: &DataType| matches!(t, DataType::INTEGER | DataType::REAL
+                                                | DataType::DECIMAL(_, _));
+        let is_date = |t: &DataType| matches!(t, DataType::DATE | DataType::TIMESTAMP);
+
+        return match (self, to) {
+            (DataType::TEXT, _) | (_, DataType::TEXT) => true,
+            (DataType::BLOB, DataType::BLOB) => true,
+            (f, t) if is_number(f) && is_number(t) => true,
+            (f, t) => is_date(f) && is_date(t)
+        };
+    }
+
+    // parses DECIMAL, DECIMAL(p) or DECIMAL(p,s)
+    fn decimal_from_string_code(code: &str) -> std::result::Result<DataType, String> {
+        let invalid = || format!("invalid DECIMAL type string {}", code);
+        let params = code["DECIMAL".len()..].trim();
+        if params.is_empty() {
+            return Ok(DataType::DECIMAL(decimal::MAX_PRECISION, 0));
+        }
+
+        if !params.starts_with('(') || !params.ends_with(')') {
+            return Err(invalid());
+        }
+
+        let nums = params[1..params.len()-1].split(',')
+            .map(|v| v.trim().parse::<u8>().map_err(|_| invalid()))
+            .collect::<std::result::Result<Vec<u8>, String>>()?;
+
+        let (p, s) = match nums.len() {
+            1 => (nums[0], 0),
+            2 => (nums[0], nums[1]),
+            _ => { return Err(invalid()); }
+        };
+
+        if !(1..=decimal::MAX_PRECISION).contains(&p) || s > p {
+            return Err(format!("invalid DECIMAL precision or scale in {}", code));
+        }
+
+        return Ok(DataType::DECIMAL(p, s));
     }
 
     pub fn from_string(&self, data: String) -> Option<Data> {
@@ -83,25 +167,33 @@ impl DataType {
                     .map(|f| Data::Real(f))
             }
             DataType::TEXT => Some(Data::Text(data)),
-            DataType::BLOB => Some(Data::Blob(data.into_bytes()))
+            DataType::BLOB => Some(Data::Blob(data.into_bytes())),
+            DataType::DATE => {
+                datetime::parse_date(&data).map(Data::Date)
+            },
+            DataType::TIMESTAMP => {
+                datetime::parse_timestamp(&data).map(Data::Timestamp)
+            },
+            DataType::DECIMAL(p, s) => {
+                decimal::parse(&data, Some(s))
+                    .filter(|&(v, _)| decimal::num_digits(v) <= p)
+                    .map(|(v, s)| Data::Decimal(v, s))
+            }
         }
     }
 
-    pub fn default_value(&self) -> Data {
-        return match *self {
-            DataType::INTEGER => Data::Integer(0),
-            DataType::REAL => Data::Real(0.0),
-            DataType::TEXT => Data::Text("".to_string()),
-            DataType::BLOB => Data::Blob(vec![])
-        }
-    }
 }
 
 impl Data {
 
+    pub fn is_null(&self) -> bool {
+        return matches!(self, Data::Null);
+    }
+
     pub fn as_f64(&self) -> f64 {
         return match &self {
             Data::Real(d) => *d,
+            Data::Decimal(v, s) => decimal::to_f64(*v, *s),
             _ => panic!("as_f64() on non-real data item")
         };
     }
@@ -113,12 +205,103 @@ impl Data {
         };
     }
 
+    pub fn extract(&self, part: DatePart) -> Data {
+        return match &self {
+            Data::Date(d) => Data::Integer(datetime::extract_from_date(*d, part)),
+            Data::Timestamp(t) => Data::Integer(datetime::extract_from_timestamp(*t, part)),
+            Data::Null => Data::Null,
+            _ => panic!("extract() on non-date data item")
+        };
+    }
+
+    /// Returns the type of this value, or `None` for NULL. Decimal
+    /// values are given the maximum precision.
+    pub fn data_type(&self) -> Option<DataType> {
+        return match &self {
+            Data::Integer(_) => Some(DataType::INTEGER),
+            Data::Real(_) => Some(DataType::REAL),
+            Data::Text(_) => Some(DataType::TEXT),
+            Data::Blob(_) => Some(DataType::BLOB),
+            Data::Date(_) => Some(DataType::DATE),
+            Data::Timestamp(_) => Some(DataType::TIMESTAMP),
+            Data::Decimal(_, s) => Some(DataType::DECIMAL(decimal::MAX_PRECISION, *s)),
+            Data::Null => None
+        };
+    }
+
+    /// Converts this value to the given type. NULLs stay NULL, and text
+    /// that cannot be parsed as the target type becomes NULL, as do
+    /// numbers outside of the range of an INTEGER.
+    pub fn cast(self, to: &DataType) -> Data {
+        match (self, to) {
+            (Data::Null, _) => Data::Null,
+            (Data::Text(t), _) => to.from_string(t).unwrap_or(Data::Null),
+            (d, DataType::TEXT) => Data::Text(d.into_string()),
+
+            (Data::Integer(i), DataType::INTEGER) => Data::Integer(i),
+            (Data::Real(f), DataType::INTEGER) => {
+                // NaN fails both comparisons, and 2^63 is the first REAL
+                // past the largest INTEGER
+                let rounded = f.round();
+                if rounded >= i64::MIN as f64 && rounded < -(i64::MIN as f64) {
+                    Data::Integer(rounded as i64)
+                } else {
+                    Data::Null
+                }
+            },
+            (Data::Decimal(v, s), DataType::INTEGER) => {
+                decimal::rescale(v, s, 0)
+                    .and_then(|rounded| i64::try_from(rounded).ok())
+                    .map(Data::Integer)
+                    .unwrap_or(Data::Null)
+            },
+
+            (Data::Integer(i), DataType::REAL) => Data::Real(i as f64),
+            (Data::Real(f), DataType::REAL) => Data::Real(f),
+            (Data::Decimal(v, s), DataType::REAL) => Data::Real(decimal::to_f64(v, s)),
+
+            (Data::Integer(i), DataType::DECIMAL(p, s)) => {
+                decimal_in_precision(decimal::rescale(i128::from(i), 0, *s), *p, *s)
+            },
+            (Data::Real(f), DataType::DECIMAL(p, s)) => {
+                // go through the shortest string representation, so that
+                // e.g. 1.005 rounds to 1.01 rather than 1.00
+                let v = decimal::parse(&f.to_string(), Some(*s)).map(|(v, _)| v);
+                decimal_in_precision(v, *p, *s)
+            },
+            (Data::Decimal(v, from), DataType::DECIMAL(p, s)) => {
+                decimal_in_precision(decimal::rescale(v, from, *s), *p, *s)
+            },
+
+            (Data::Date(d), DataType::DATE) => Data::Date(d),
+            (Data::Timestamp(t), DataType::DATE) => {
+                Data::Date(t.div_euclid(datetime::MICROS_PER_DAY) as i32)
+            },
+            (Data::Date(d), DataType::TIMESTAMP) => {
+                // like other casts that do not fit, the furthest dates
+                // have no timestamp and become NULL
+                i64::from(d).checked_mul(datetime::MICROS_PER_DAY)
+                    .map(Data::Timestamp)
+                    .unwrap_or(Data::Null)
+            },
+            (Data::Timestamp(t), DataType::TIMESTAMP) => Data::Timestamp(t),
+
+            (Data::Blob(b), DataType::BLOB) => Data::Blob(b),
+
+            (d, _) => panic!("Cannot cast {:?} to {:?}", d, to)
+        }
+    }
+
     pub fn num_bytes(&self) -> usize {
         return match &self {
             Data::Integer(_) => 8,
             Data::Real(_) => 8,
             Data::Text(s) => s.as_bytes().len() + 1,
-            Data::Blob(b) => 8 + b.len()
+            Data::Blob(b) => 8 + b.len(),
+            Data::Date(_) => 4,
+            Data::Timestamp(_) => 8,
+            Data::Decimal(_, _) => 16,
+            Data::Null => 0
         };
     }
     
@@ -127,7 +310,11 @@ impl Data {
             Data::Integer(i) => i.to_string(),
             Data::Real(f) => f.to_string(),
             Data::Text(t) => t,
-            Data::Blob(b) => base64::encode(&b)
+            Data::Blob(b) => base64::encode(&b),
+            Data::Date(d) => datetime::format_date(d),
+            Data::Timestamp(t) => datetime::format_timestamp(t),
+            Data::Decimal(v, s) => decimal::format(v, s),
+            Data::Null => String::new()
         }
     }
 
@@ -136,13 +323,34 @@ impl Data {
             Data::Integer(i) => i.to_string(),
             Data::Real(f) => f.to_string(),
             Data::Text(t) => t.clone(),
-            Data::Blob(b) => base64::encode(&b)
+            Data::Blob(b) => base64::encode(&b),
+            Data::Date(d) => datetime::format_date(*d),
+            Data::Timestamp(t) => datetime::format_timestamp(*t),
+            Data::Decimal(v, s) => decimal::format(*v, *s),
+            Data::Null => String::new()
         }
     }
 }
 
+fn decimal_in_precision(v: Option<i128>, precision: u8, scale: u8) -> Data {
+    match v {
+        Some(v) if decimal::num_digits(v) <= precision => Data::Decimal(v, scale),
+        _ => panic!("Decimal overflow casting to DECIMAL({},{})", precision, scale)
+    }
+}
+
+// Every serialized value is prefixed with a single byte marking whether
+// or not it is NULL, so that NULLs survive spilling and columnar files.
+const NULL_MARKER: u8 = 0;
+const VALUE_MARKER: u8 = 1;
+
 pub trait WriteByooDataExt: io::Write {
     fn write_data(&mut self, data: &Data) -> Result<()> {
+        if let Data::Null = *data {
+            return self.write_u8(NULL_MARKER);
+        }
+
+        self.write_u8(VALUE_MARKER)?;
         match *data {
             Data::Integer(i) => self.write_i64::<LittleEndian>(i),
             Data::Real(f) => self.write_f64::<LittleEndian>(f),
@@ -154,7 +362,12 @@ pub trait WriteByooDataExt: io::Write {
             Data::Blob(ref b) => {
                 self.write_u64::<LittleEndian>(b.len() as u64)?;
                 self.write_all(b)
-            }
+            },
+
+            Data::Date(d) => self.write_i32::<LittleEndian>(d),
+            Data::Timestamp(t) => self.write_i64::<LittleEndian>(t),
+            Data::Decimal(v, _) => self.write_i128::<LittleEndian>(v),
+            Data::Null => unreachable!()
         }
     }
 }
@@ -162,6 +375,15 @@ impl<W: io::Write + ?Sized> WriteByooDataExt for W {}
 
 pub trait ReadByooDataExt: io::BufRead {
     fn read_data(&mut self, data_type: &DataType) -> Result<Data> {
+        match self.read_u8()? {
+            NULL_MARKER => { return Ok(Data::Null); },
+            VALUE_MARKER => {},
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData, "Invalid null marker"));
+            }
+        };
+
         match data_type {
             DataType::INTEGER => {
                 return self.read_i64::<LittleEndian>()
@@ -200,6 +422,18 @@ pub trait ReadByooDataExt: io::BufRead {
                 }
 
                 return Ok(Data::Blob(data));
+            },
+            DataType::DATE => {
+                return self.read_i32::<LittleEndian>()
+                    .map(Data::Date);
+            },
+            DataType::TIMESTAMP => {
+                return self.read_i64::<LittleEndian>()
+                    .map(Data::Timestamp);
+            },
+            DataType::DECIMAL(_, s) => {
+                return self.read_i128::<LittleEndian>()
+                    .map(|v| Data::Decimal(v, *s));
             }
         };
         
@@ -209,6 +443,15 @@ impl<R: io::BufRead + ?Sized> ReadByooDataExt for R {}
 
 impl PartialOrd for Data {
     fn partial_cmp(&self, other: &Data) -> Option<Ordering> {
+        // define NULL as greater than all other values, so that NULLs
+        // sort last (like PostgreSQL's default of NULLS LAST)
+        match (self, other) {
+            (Data::Null, Data::Null) => { return Some(Ordering::Equal); },
+            (Data::Null, _) => { return Some(Ordering::Greater); },
+            (_, Data::Null) => { return Some(Ordering::Less); },
+            _ => {}
+        };
+
         match &self {
             Data::Integer(me) => {
                 if let Data::Integer(other) = other {
@@ -254,7 +497,33 @@ impl PartialOrd for Data {
                 } else {
                     return None;
                 }
-            }
+            },
+
+            Data::Date(me) => {
+                if let Data::Date(other) = other {
+                    return Some(me.cmp(other));
+                } else {
+                    return None;
+                }
+            },
+
+            Data::Timestamp(me) => {
+                if let Data::Timestamp(other) = other {
+                    return Some(me.cmp(other));
+                } else {
+                    return None;
+                }
+            },
+
+            Data::Decimal(me, my_scale) => {
+                if let Data::Decimal(other, other_scale) = other {
+                    return Some(decimal::cmp(*me, *my_scale, *other, *other_scale));
+                } else {
+                    return None;
+                }
+            },
+
+            Data::Null => unreachable!()
         }
     }
 }
@@ -283,20 +552,42 @@ impl Hash for Data {
             Data::Integer(me) => me.hash(state),
             Data::Real(me) => (*me as i64).hash(state),
             Data::Text(me) => me.hash(state),
-            Data::Blob(me) => me.hash(state)
+            Data::Blob(me) => me.hash(state),
+            Data::Date(me) => me.hash(state),
+            Data::Timestamp(me) => me.hash(state),
+            Data::Decimal(v, s) => decimal::normalize(*v, *s).hash(state),
+            Data::Null => state.write_u8(0)
         };
     }
 }
 
+// the date a number of days after another, or None if it is out of range
+fn date_plus_days(date: i32, days: i64) -> Option<i32> {
+    return i64::from(date).checked_add(days)
+        .and_then(|d| i32::try_from(d).ok());
+}
+
 impl ops::Add<Data> for Data {
     type Output = Data;
 
     fn add(self, rhs: Data) -> Data {
+        if self.is_null() || rhs.is_null() {
+            return Data::Null;
+        }
+
         match self {
             Data::Integer(me) => {
                 match rhs {
-                    Data::Integer(other) => return Data::Integer(me + other),
+                    Data::Integer(other) => {
+                        return Data::Integer(me.checked_add(other).unwrap_or_else(
+                            || panic!("Integer overflow in sum operator, got: {} + {}",
+                                      me, other)));
+                    },
                     Data::Real(other) => return Data::Real(me as f64 + other),
+                    Data::Decimal(other, scale) => {
+                        let (v, s) = decimal::add(i128::from(me), 0, other, scale);
+                        return Data::Decimal(v, s);
+                    },
                     _ => {}
                 };
             },
@@ -305,6 +596,30 @@ impl ops::Add<Data> for Data {
                 match rhs {
                     Data::Integer(other) => return Data::Real(me + (other as f64)),
                     Data::Real(other) => return Data::Real(me + other),
+                    Data::Decimal(other, scale) => return Data::Real(me + decimal::to_f64(other, scale)),
+                    _ => {}
+                };
+            },
+
+            Data::Date(me) => {
+                if let Data::Integer(days) = rhs {
+                    return Data::Date(date_plus_days(me, days).unwrap_or_else(
+                        || panic!("Date overflow in sum operator, got: {} + {} days",
+                                   Data::Date(me), days)));
+                }
+            },
+
+            Data::Decimal(me, scale) => {
+                match rhs {
+                    Data::Integer(other) => {
+                        let (v, s) = decimal::add(me, scale, i128::from(other), 0);
+                        return Data::Decimal(v, s);
+                    },
+                    Data::Real(other) => return Data::Real(decimal::to_f64(me, scale) + other),
+                    Data::Decimal(other, other_scale) => {
+                        let (v, s) = decimal::add(me, scale, other, other_scale);
+                        return Data::Decimal(v, s);
+                    },
                     _ => {}
                 };
             },
@@ -320,11 +635,23 @@ impl ops::Sub<Data> for Data {
     type Output = Data;
 
     fn sub(self, rhs: Data) -> Data {
-         match self {
+        if self.is_null() || rhs.is_null() {
+            return Data::Null;
+        }
+
+        match self {
             Data::Integer(me) => {
                 match rhs {
-                    Data::Integer(other) => return Data::Integer(me - other),
+                    Data::Integer(other) => {
+                        return Data::Integer(me.checked_sub(other).unwrap_or_else(
+                            || panic!("Integer overflow in sub operator, got: {} - {}",
+                                      me, other)));
+                    },
                     Data::Real(other) => return Data::Real(me as f64 - other),
+                    Data::Decimal(other, scale) => {
+                        let (v, s) = decimal::sub(i128::from(me), 0, other, scale);
+                        return Data::Decimal(v, s);
+                    },
                     _ => {}
                 };
             },
@@ -333,6 +660,43 @@ impl ops::Sub<Data> for Data {
                 match rhs {
                     Data::Integer(other) => return Data::Real(me - (other as f64)),
                     Data::Real(other) => return Data::Real(me - other),
+                    Data::Decimal(other, scale) => return Data::Real(me - decimal::to_f64(other, scale)),
+                    _ => {}
+                };
+            },
+
+            Data::Date(me) => {
+                match rhs {
+                    Data::Integer(days) => {
+                        let date = days.checked_neg().and_then(|d| date_plus_days(me, d));
+                        return Data::Date(date.unwrap_or_else(
+                            || panic!("Date overflow in sub operator, got: {} - {} days",
+                                       Data::Date(me), days)));
+                    },
+                    Data::Date(other) => return Data::Integer(i64::from(me) - i64::from(other)),
+                    _ => {}
+                };
+            },
+
+            Data::Timestamp(me) => {
+                if let Data::Timestamp(other) = rhs {
+                    return Data::Integer(me.checked_sub(other).unwrap_or_else(
+                        || panic!("Timestamp overflow in sub operator, got: {} - {}",
+                                  me, other)));
+                }
+            },
+
+            Data::Decimal(me, scale) => {
+                match rhs {
+                    Data::Integer(other) => {
+                        let (v, s) = decimal::sub(me, scale, i128::from(other), 0);
+                        return Data::Decimal(v, s);
+                    },
+                    Data::Real(other) => return Data::Real(decimal::to_f64(me, scale) - other),
+                    Data::Decimal(other, other_scale) => {
+                        let (v, s) = decimal::sub(me, scale, other, other_scale);
+                        return Data::Decimal(v, s);
+                    },
                     _ => {}
                 };
             },
@@ -358,6 +722,16 @@ impl ops::Div<usize> for Data {
                 return Data::Real(me / rhs as f64);
             },
 
+            Data::Decimal(me, scale) => {
+                let out_scale = scale.max(DECIMAL_DIV_MIN_SCALE);
+                return Data::Decimal(decimal::div(me, scale, rhs as i128, out_scale),
+                                     out_scale);
+            },
+
+            Data::Null => {
+                return Data::Null;
+            },
+
             _ => {}
         };
 
@@ -365,6 +739,107 @@ impl ops::Div<usize> for Data {
     }
 }
 
+impl ops::Div<Data> for Data {
+    type Output = Data;
+
+    fn div(self, rhs: Data) -> Data {
+        if self.is_null() || rhs.is_null() {
+            return Data::Null;
+        }
+
+        // like SQLite, division by zero gives NULL rather than an error
+        let zero = match rhs {
+            Data::Integer(i) => i == 0,
+            Data::Real(f) => f == 0.0,
+            Data::Decimal(v, _) => v == 0,
+            _ => false
+        };
+
+        if zero {
+            return Data::Null;
+        }
+
+        match (&self, &rhs) {
+            (Data::Integer(me), Data::Integer(other)) => {
+                return Data::Integer(me.checked_div(*other)
+                                     .expect("Integer overflow in div operator"));
+            },
+            (Data::Integer(me), Data::Real(other)) => return Data::Real(*me as f64 / other),
+            (Data::Real(me), Data::Integer(other)) => return Data::Real(me / *other as f64),
+            (Data::Real(me), Data::Real(other)) => return Data::Real(me / other),
+
+            (Data::Decimal(me, scale), Data::Real(other)) => {
+                return Data::Real(decimal::to_f64(*me, *scale) / other);
+            },
+            (Data::Real(me), Data::Decimal(other, scale)) => {
+                return Data::Real(me / decimal::to_f64(*other, *scale));
+            },
+            
+            _ => {}
+        };
+
+        let (num, num_scale) = match self {
+            Data::Integer(i) => (i128::from(i), 0),
+            Data::Decimal(v, s) => (v, s),
+            _ => panic!("Incompatible data types for div operator, got: {:?} / {:?}",
+                        self, rhs)
+        };
+
+        let (denom, denom_scale) = match rhs {
+            Data::Integer(i) => (i128::from(i), 0),
+            Data::Decimal(v, s) => (v, s),
+            _ => panic!("Incompatible data types for div operator, got: {:?} / {:?}",
+                        self, rhs)
+        };
+
+        // dividing by an unscaled value with scale s2 is the same as
+        // dividing by the raw value and shifting the result by s2
+        let out_scale = num_scale.max(DECIMAL_DIV_MIN_SCALE);
+        return Data::Decimal(decimal::div(num, num_scale, denom, out_scale + denom_scale),
+                             out_scale);
+    }
+}
+
+impl ops::Mul<Data> for Data {
+    type Output = Data;
+
+    fn mul(self, rhs: Data) -> Data {
+        if self.is_null() || rhs.is_null() {
+            return Data::Null;
+        }
+
+        match (&self, &rhs) {
+            (Data::Integer(me), Data::Integer(other)) => {
+                return Data::Integer(me.checked_mul(*other).unwrap_or_else(
+                    || panic!("Integer overflow in mul operator, got: {} * {}",
+                              me, other)));
+            },
+            (Data::Integer(me), Data::Real(other)) => return Data::Real(*me as f64 * other),
+            (Data::Real(me), Data::Integer(other)) => return Data::Real(me * *other as f64),
+            (Data::Real(me), Data::Real(other)) => return Data::Real(me * other),
+            
+            (Data::Decimal(me, scale), Data::Decimal(other, other_scale)) => {
+                let (v, s) = decimal::mul(*me, *scale, *other, *other_scale);
+                return Data::Decimal(v, s);
+            },
+            (Data::Decimal(me, scale), Data::Integer(other))
+                | (Data::Integer(other), Data::Decimal(me, scale)) => {
+                let (v, s) = decimal::mul(*me, *scale, i128::from(*other), 0);
+                return Data::Decimal(v, s);
+            },
+            (Data::Decimal(me, scale), Data::Real(other))
+                | (Data::Real(other), Data::Decimal(me, scale)) => {
+                return Data::Real(decimal::to_f64(*me, *scale) * other);
+            },
+            
+            _ => {}
+        };
+
+        panic!("Incompatible data types for mul operator, got: {:?} * {:?}",
+               self, rhs);
+    }
+}
+
 
 
 
@@ -388,7 +863,8 @@ pub fn rows_to_string(rows: &[Vec<Data>], sort: bool) -> String {
 
 #[cfg(test)]
 mod tests {
-    use data::Data;
+    use data::{Data, DataType, WriteByooDataExt, ReadByooDataExt};
+    use datetime::DatePart;
 
     #[test]
     fn add_data() {
@@ -404,10 +880,129 @@ mod tests {
         assert_eq!(Data::Real(5.0) - Data::Real(-2.0), Data::Real(7.0));
     }
 
+    #[test]
+    #[should_panic(expected = "Integer overflow in mul operator")]
+    fn mul_overflow_data() {
+        let _ = Data::Integer(i64::MAX / 2) * Data::Integer(3);
+    }
+
+    #[test]
+    #[should_panic(expected = "Integer overflow in sum operator")]
+    fn add_overflow_data() {
+        let _ = Data::Integer(i64::MAX) + Data::Integer(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Integer overflow in sub operator")]
+    fn sub_overflow_data() {
+        let _ = Data::Integer(i64::MIN) - Data::Integer(1);
+    }
+
     #[test]
     fn div_data() {
         assert_eq!(Data::Integer(5) / 2, Data::Real(2.5));
         assert_eq!(Data::Real(5.0) / 2, Data::Real(2.5));
     }
+
+    #[test]
+    fn date_data() {
+        let d1 = DataType::DATE.from_string(String::from("1995-03-15")).unwrap();
+        let d2 = DataType::DATE.from_string(String::from("1998-12-01")).unwrap();
+        assert!(d1 < d2);
+        assert_eq!(d2.clone() - d1.clone(), Data::Integer(1357));
+        assert_eq!(d1.clone() + Data::Integer(1357), d2);
+        assert_eq!(d1.extract(DatePart::Month), Data::Integer(3));
+        assert_eq!(d2.to_string(), "1998-12-01");
+        assert!(DataType::DATE.from_string(String::from("1998-02-30")).is_none());
+        assert_eq!(Data::Date(i32::MIN) - Data::Date(i32::MAX), Data::Integer(-(1 << 32) + 1));
+        assert_eq!(Data::Date(i32::MAX).cast(&DataType::TIMESTAMP), Data::Null);
+        assert!(!DataType::DATE.can_cast_to(&DataType::INTEGER));
+        assert!(DataType::DATE.can_cast_to(&DataType::TIMESTAMP));
+
+        let t1 = DataType::TIMESTAMP.from_string(
+            String::from("1998-12-01 08:30:00")).unwrap();
+        let t2 = DataType::TIMESTAMP.from_string(
+            String::from("1998-12-01T08:30:01")).unwrap();
+        assert!(t1 < t2);
+        assert_eq!(t1.extract(DatePart::Hour), Data::Integer(8));
+        assert_eq!(t1.to_string(), "1998-12-01 08:30:00");
+
+        let mut buf = Vec::new();
+        buf.write_data(&d1).unwrap();
+        buf.write_data(&t2).unwrap();
+        let mut rdr = &buf[..];
+        assert_eq!(rdr.read_data(&DataType::DATE).unwrap(), d1);
+        assert_eq!(rdr.read_data(&DataType::TIMESTAMP).unwrap(), t2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Date overflow in sum operator")]
+    fn date_overflow_data() {
+        let _ = Data::Date(0) + Data::Integer(1 << 40);
+    }
+
+    #[test]
+    fn decimal_data() {
+        let dt = DataType::from_string_code("DECIMAL(15,2)");
+        assert_eq!(DataType::from_code(dt.to_code()).to_code(), dt.to_code());
+        
+        let price = dt.from_string(String::from("901.00")).unwrap();
+        let discount = dt.from_string(String::from("0.05")).unwrap();
+        assert_eq!(price, Data::Decimal(90100, 2));
+        assert!(dt.from_string(String::from("12345678901234.5")).is_none());
+
+        // price * (1 - discount)
+        let disc_price = price.clone() * (Data::Integer(1) - discount.clone());
+        assert_eq!(disc_price, Data::Decimal(855_950, 3));
+        assert_eq!(disc_price.to_string(), "855.9500");
+        assert_eq!(Data::Decimal(15, 1), Data::Decimal(150, 2));
+        assert!(discount < Data::Decimal(6, 2));
+        assert_eq!(Data::Decimal(1000, 2) / 3, Data::Decimal(3_333_333, 6));
+
+        // casts to INTEGER round, and values that do not fit become NULL
+        assert_eq!(Data::Decimal(-250, 2).cast(&DataType::INTEGER), Data::Integer(-3));
+        assert_eq!(Data::Decimal(10i128.pow(30), 0).cast(&DataType::INTEGER), Data::Null);
+        assert_eq!(Data::Real(-2.5).cast(&DataType::INTEGER), Data::Integer(-3));
+        assert_eq!(Data::Real(1e19).cast(&DataType::INTEGER), Data::Null);
+        assert_eq!(Data::Real(f64::NAN).cast(&DataType::INTEGER), Data::Null);
+        assert_eq!(Data::Real(i64::MIN as f64).cast(&DataType::INTEGER),
+                   Data::Integer(i64::MIN));
+
+        let mut buf = Vec::new();
+        buf.write_data(&price).unwrap();
+        let mut rdr = &buf[..];
+        assert_eq!(rdr.read_data(&dt).unwrap(), price);
+    }
+
+    #[test]
+    fn null_data() {
+        assert!((Data::Integer(5) + Data::Null).is_null());
+        assert!((Data::Null - Data::Real(2.0)).is_null());
+        assert!((Data::Null / 2).is_null());
+
+        // NULLs sort after everything else
+        assert!(Data::Null > Data::Integer(i64::MAX));
+        assert!(Data::Text(String::from("zzz")) < Data::Null);
+        assert_eq!(Data::Null, Data::Null);
+    }
+
+    #[test]
+    fn null_roundtrip() {
+        let types = vec![DataType::INTEGER, DataType::TEXT, DataType::BLOB,
+                         DataType::REAL];
+        let values = vec![Data::Integer(-4), Data::Null, Data::Null,
+                          Data::Real(2.5)];
+
+        let mut buf = Vec::new();
+        for d in values.iter() {
+            buf.write_data(d).unwrap();
+        }
+
+        let mut rdr = &buf[..];
+        for (dt, d) in types.iter().zip(values.iter()) {
+            assert_eq!(&rdr.read_data(dt).unwrap(), d);
+        }
+        assert!(rdr.read_data(&DataType::INTEGER).is_err());
+    }
 }
 
@@ -0,0 +1,289 @@
+// < begin copyright > 
+// Copyright Ryan Marcus 2018
+// 
+// This file is part of byoo.
+// 
+// byoo is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// byoo is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with byoo.  If not, see <http://www.gnu.org/licenses/>.
+// 
+// < end copyright > 
+// Calendar arithmetic for the DATE and TIMESTAMP types. Dates are stored
+// as the number of days since 1970-01-01, and timestamps as the number of
+// microseconds since 1970-01-01 00:00:00 (no time zones). The conversions
+// use the proleptic Gregorian calendar, following Howard Hinnant's
+// "chrono-Compatible Low-Level Date Algorithms".
+
+use std::convert::TryFrom;
+
+pub const MICROS_PER_SECOND: i64 = 1_000_000;
+pub const MICROS_PER_DAY: i64 = 86_400 * MICROS_PER_SECOND;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DatePart {
+    Year, Month, Day, Hour, Minute, Second, DayOfWeek, DayOfYear, Epoch
+}
+
+impl DatePart {
+    pub fn from_string_code(code: &str) -> Option<DatePart> {
+        return match code {
+            "year" => Some(DatePart::Year),
+            "month" => Some(DatePart::Month),
+            "day" => Some(DatePart::Day),
+            "hour" => Some(DatePart::Hour),
+            "minute" => Some(DatePart::Minute),
+            "second" => Some(DatePart::Second),
+            "dow" => Some(DatePart::DayOfWeek),
+            "doy" => Some(DatePart::DayOfYear),
+            "epoch" => Some(DatePart::Epoch),
+            _ => None
+        };
+    }
+}
+
+fn is_leap_year(y: i64) -> bool {
+    return (y % 4 == 0 && y % 100 != 0) || y % 400 == 0;
+}
+
+fn days_in_month(y: i64, m: u32) -> u32 {
+    return match m {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 => if is_leap_year(y) { 29 } else { 28 },
+        _ => 0
+    };
+}
+
+pub fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let mp = i64::from((m + 9) % 12);
+    let doy = (153 * mp + 2) / 5 + i64::from(d) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    return era * 146_097 + doe - 719_468;
+}
+
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400;
+    return (if m <= 2 { y + 1 } else { y }, m, d);
+}
+
+fn parse_fixed_int(s: &str) -> Option<i64> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    return s.parse::<i64>().ok();
+}
+
+/// Parses a date of the form `YYYY-MM-DD` into days since the epoch.
+pub fn parse_date(s: &str) -> Option<i32> {
+    let mut parts = s.trim().splitn(3, '-');
+    let y = parse_fixed_int(parts.next()?)?;
+    let m = u32::try_from(parse_fixed_int(parts.next()?)?).ok()?;
+    let d = u32::try_from(parse_fixed_int(parts.next()?)?).ok()?;
+
+    // years this large are out of the DATE range anyway, and would
+    // overflow days_from_civil
+    if y > i64::from(i32::MAX) {
+        return None;
+    }
+
+    if !(1..=12).contains(&m) || d < 1 || d > days_in_month(y, m) {
+        return None;
+    }
+
+    return i32::try_from(days_from_civil(y, m, d)).ok();
+}
+
+/// Parses a timestamp of the form `YYYY-MM-DD[( |T)HH:MM[:SS[.ffffff]]]`
+/// into microseconds since the epoch. Fractional seconds beyond
+/// microsecond precision are truncated.
+pub fn parse_timestamp(s: &str) -> Option<i64> {
+    let s = s.trim();
+    let (date_str, time_str) = match s.find([' ', 'T']) {
+        Some(idx) => (&s[..idx], Some(s[idx+1..].trim())),
+        None => (s, None)
+    };
+
+    let days = i64::from(parse_date(date_str)?);
+    let mut micros = days.checked_mul(MICROS_PER_DAY)?;
+
+    if let Some(t) = time_str {
+        let (hms, frac) = match t.find('.') {
+            Some(idx) => (&t[..idx], Some(&t[idx+1..])),
+            None => (t, None)
+        };
+
+        let mut fields = hms.split(':');
+        let h = parse_fixed_int(fields.next()?)?;
+        let m = parse_fixed_int(fields.next()?)?;
+        let sec = match fields.next() {
+            Some(f) => parse_fixed_int(f)?,
+            None => 0
+        };
+
+        if fields.next().is_some() || h > 23 || m > 59 || sec > 59 {
+            return None;
+        }
+
+        micros = micros.checked_add((h * 3600 + m * 60 + sec) * MICROS_PER_SECOND)?;
+
+        if let Some(f) = frac {
+            parse_fixed_int(f)?;
+            let digits: String = f.chars().chain("000000".chars())
+                .take(6).collect();
+            micros = micros.checked_add(digits.parse::<i64>().unwrap())?;
+        }
+    }
+
+    return Some(micros);
+}
+
+pub fn format_date(days: i32) -> String {
+    return format_days(i64::from(days));
+}
+
+fn format_days(days: i64) -> String {
+    let (y, m, d) = civil_from_days(days);
+    return format!("{:04}-{:02}-{:02}", y, m, d);
+}
+
+pub fn format_timestamp(micros: i64) -> String {
+    let days = micros.div_euclid(MICROS_PER_DAY);
+    let in_day = micros.rem_euclid(MICROS_PER_DAY);
+    let secs = in_day / MICROS_PER_SECOND;
+    let frac = in_day % MICROS_PER_SECOND;
+
+    let mut to_r = format!("{} {:02}:{:02}:{:02}",
+                           format_days(days),
+                           secs / 3600, (secs / 60) % 60, secs % 60);
+
+    if frac != 0 {
+        to_r.push_str(format!(".{:06}", frac).as_str());
+    }
+
+    return to_r;
+}
+
+pub fn extract_from_timestamp(micros: i64, part: DatePart) -> i64 {
+    let days = micros.div_euclid(MICROS_PER_DAY);
+    let in_day = micros.rem_euclid(MICROS_PER_DAY) / MICROS_PER_SECOND;
+    return extract_from_days(days, in_day, part);
+}
+
+pub fn extract_from_date(days: i32, part: DatePart) -> i64 {
+    // not via a timestamp, since the furthest dates overflow one
+    return extract_from_days(i64::from(days), 0, part);
+}
+
+// extracts a part from a day since the epoch and a second within that day
+fn extract_from_days(days: i64, in_day: i64, part: DatePart) -> i64 {
+    let (y, m, d) = civil_from_days(days);
+
+    return match part {
+        DatePart::Year => y,
+        DatePart::Month => i64::from(m),
+        DatePart::Day => i64::from(d),
+        DatePart::Hour => in_day / 3600,
+        DatePart::Minute => (in_day / 60) % 60,
+        DatePart::Second => in_day % 60,
+        // 1970-01-01 was a Thursday, and Sunday is day 0
+        DatePart::DayOfWeek => (days + 4).rem_euclid(7),
+        DatePart::DayOfYear => days - days_from_civil(y, 1, 1) + 1,
+        DatePart::Epoch => days * 86_400 + in_day
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use datetime::*;
+
+    #[test]
+    fn civil_roundtrip() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+
+        for days in -800_000..800_000 {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m, d), days);
+        }
+    }
+
+    #[test]
+    fn parses_dates() {
+        assert_eq!(parse_date("1970-01-02"), Some(1));
+        assert_eq!(parse_date("1998-12-01").map(format_date),
+                   Some(String::from("1998-12-01")));
+        assert_eq!(parse_date("2000-02-29").map(format_date),
+                   Some(String::from("2000-02-29")));
+        assert_eq!(parse_date("1900-02-29"), None);
+        assert_eq!(parse_date("1998-13-01"), None);
+        assert_eq!(parse_date("1998-12"), None);
+        assert_eq!(parse_date("hello"), None);
+
+        // out of range fields are rejected rather than wrapped
+        assert_eq!(parse_date("2000-4294967297-01"), None);
+        assert_eq!(parse_date("2000-01-4294967297"), None);
+        assert_eq!(parse_date("99999999999-01-01"), None);
+        assert_eq!(parse_date("5000000-01-01").map(format_date),
+                   Some(String::from("5000000-01-01")));
+        assert_eq!(parse_date("6000000-01-01"), None);
+    }
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(parse_timestamp("1970-01-01 00:00:01"), Some(MICROS_PER_SECOND));
+        assert_eq!(parse_timestamp("1970-01-01T00:01"), Some(60 * MICROS_PER_SECOND));
+        assert_eq!(parse_timestamp("1970-01-02"), Some(MICROS_PER_DAY));
+        assert_eq!(parse_timestamp("1969-12-31 23:59:59.5"), Some(-MICROS_PER_SECOND / 2));
+        assert_eq!(parse_timestamp("1970-01-01 24:00:00"), None);
+        assert_eq!(parse_timestamp("5000000-01-01"), None);
+
+        let ts = parse_timestamp("2018-07-04 13:05:09.000250").unwrap();
+        assert_eq!(format_timestamp(ts), "2018-07-04 13:05:09.000250");
+        assert_eq!(format_timestamp(parse_timestamp("1950-06-30 01:02:03").unwrap()),
+                   "1950-06-30 01:02:03");
+    }
+
+    #[test]
+    fn extracts_parts() {
+        let ts = parse_timestamp("2018-07-04 13:05:09").unwrap();
+        assert_eq!(extract_from_timestamp(ts, DatePart::Year), 2018);
+        assert_eq!(extract_from_timestamp(ts, DatePart::Month), 7);
+        assert_eq!(extract_from_timestamp(ts, DatePart::Day), 4);
+        assert_eq!(extract_from_timestamp(ts, DatePart::Hour), 13);
+        assert_eq!(extract_from_timestamp(ts, DatePart::Minute), 5);
+        assert_eq!(extract_from_timestamp(ts, DatePart::Second), 9);
+        assert_eq!(extract_from_timestamp(ts, DatePart::DayOfWeek), 3);
+        assert_eq!(extract_from_timestamp(ts, DatePart::DayOfYear), 185);
+
+        let d = parse_date("1995-03-15").unwrap();
+        assert_eq!(extract_from_date(d, DatePart::Year), 1995);
+        assert_eq!(extract_from_date(d, DatePart::Epoch), 795_225_600);
+
+        let d = parse_date("5000000-07-04").unwrap();
+        assert_eq!(extract_from_date(d, DatePart::Year), 5_000_000);
+        assert_eq!(extract_from_date(d, DatePart::Day), 4);
+        assert_eq!(extract_from_timestamp(-1, DatePart::Epoch), -1);
+    }
+}
@@ -0,0 +1,249 @@
+// < begin copyright > 
+// Copyright Ryan Marcus 2018
+// 
+// This file is part of byoo.
+// 
+// byoo is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// byoo is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with byoo.  If not, see <http://www.gnu.org/licenses/>.
+// 
+// < end copyright > 
+// Fixed-point arithmetic for the DECIMAL type. A decimal value is stored
+// as an unscaled i128 and a scale, so that the value is v * 10^-scale.
+// Rounding is always half away from zero.
+use std::cmp::Ordering;
+
+pub const MAX_PRECISION: u8 = 38;
+
+fn pow10(exp: u8) -> i128 {
+    return 10i128.checked_pow(u32::from(exp)).unwrap_or_else(|| overflow());
+}
+
+/// Changes the scale of `v` from `from` to `to`, rounding if digits are
+/// dropped. Returns `None` on overflow.
+pub fn rescale(v: i128, from: u8, to: u8) -> Option<i128> {
+    if to >= from {
+        return v.checked_mul(pow10(to - from));
+    }
+
+    let divisor = pow10(from - to);
+    let quot = v / divisor;
+    let rem = v % divisor;
+
+    // rem * 2 >= divisor, without overflowing when divisor is 10^38
+    if rem.abs() >= divisor - rem.abs() {
+        return Some(quot + v.signum());
+    }
+
+    return Some(quot);
+}
+
+/// Parses a decimal string such as `-123.45`. If `scale` is `None`, the
+/// scale is the number of digits after the decimal point.
+pub fn parse(s: &str, scale: Option<u8>) -> Option<(i128, u8)> {
+    let s = s.trim();
+    let (neg, digits) = if let Some(rest) = s.strip_prefix('-') {
+        (true, rest)
+    } else if let Some(rest) = s.strip_prefix('+') {
+        (false, rest)
+    } else {
+        (false, s)
+    };
+
+    let (int_part, frac_part) = match digits.find('.') {
+        Some(idx) => (&digits[..idx], &digits[idx+1..]),
+        None => (digits, "")
+    };
+
+    if int_part.is_empty() && frac_part.is_empty() {
+        return None;
+    }
+
+    if !int_part.bytes().chain(frac_part.bytes()).all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    if frac_part.len() > usize::from(MAX_PRECISION) {
+        return None;
+    }
+
+    let natural_scale = frac_part.len() as u8;
+    let target_scale = scale.unwrap_or(natural_scale);
+    if target_scale > MAX_PRECISION {
+        return None;
+    }
+
+    let mut unscaled: i128 = 0;
+    for b in int_part.bytes().chain(frac_part.bytes()) {
+        unscaled = unscaled.checked_mul(10)?
+            .checked_add(i128::from(b - b'0'))?;
+    }
+
+    let v = rescale(unscaled, natural_scale, target_scale)?;
+    return Some((if neg { -v } else { v }, target_scale));
+}
+
+/// The number of decimal digits in the unscaled value.
+pub fn num_digits(v: i128) -> u8 {
+    let mut abs = v.unsigned_abs();
+    let mut count = 1;
+    while abs >= 10 {
+        abs /= 10;
+        count += 1;
+    }
+
+    return count;
+}
+
+pub fn format(v: i128, scale: u8) -> String {
+    if scale == 0 {
+        return v.to_string();
+    }
+
+    let mut digits = v.unsigned_abs().to_string();
+    while digits.len() <= usize::from(scale) {
+        digits.insert(0, '0');
+    }
+
+    digits.insert(digits.len() - usize::from(scale), '.');
+    if v < 0 {
+        digits.insert(0, '-');
+    }
+
+    return digits;
+}
+
+pub fn cmp(v1: i128, s1: u8, v2: i128, s2: u8) -> Ordering {
+    if s1 == s2 {
+        return v1.cmp(&v2);
+    }
+
+    // if scaling up overflows, the scaled value is larger in magnitude
+    // than anything representable, so its sign decides the comparison.
+    if s1 < s2 {
+        return match rescale(v1, s1, s2) {
+            Some(scaled) => scaled.cmp(&v2),
+            None => v1.cmp(&0)
+        };
+    }
+
+    return match rescale(v2, s2, s1) {
+        Some(scaled) => v1.cmp(&scaled),
+        None => 0.cmp(&v2)
+    };
+}
+
+/// Strips trailing zeros, so that equal values have identical
+/// representations (e.g., for hashing).
+pub fn normalize(mut v: i128, mut scale: u8) -> (i128, u8) {
+    while scale > 0 && v % 10 == 0 {
+        v /= 10;
+        scale -= 1;
+    }
+
+    return (v, scale);
+}
+
+fn overflow() -> ! {
+    panic!("Decimal overflow");
+}
+
+pub fn add(v1: i128, s1: u8, v2: i128, s2: u8) -> (i128, u8) {
+    let scale = s1.max(s2);
+    let a = rescale(v1, s1, scale).unwrap_or_else(|| overflow());
+    let b = rescale(v2, s2, scale).unwrap_or_else(|| overflow());
+    return (a.checked_add(b).unwrap_or_else(|| overflow()), scale);
+}
+
+pub fn sub(v1: i128, s1: u8, v2: i128, s2: u8) -> (i128, u8) {
+    return add(v1, s1, v2.checked_neg().unwrap_or_else(|| overflow()), s2);
+}
+
+pub fn mul(v1: i128, s1: u8, v2: i128, s2: u8) -> (i128, u8) {
+    let product = v1.checked_mul(v2).unwrap_or_else(|| overflow());
+    let scale = s1 + s2;
+
+    if scale > MAX_PRECISION {
+        return (rescale(product, scale, MAX_PRECISION).unwrap(), MAX_PRECISION);
+    }
+
+    return (product, scale);
+}
+
+/// Divides `v` by `divisor`, producing a result with scale `out_scale`.
+pub fn div(v: i128, scale: u8, divisor: i128, out_scale: u8) -> i128 {
+    let numerator = rescale(v, scale, out_scale).unwrap_or_else(|| overflow());
+    let quot = numerator / divisor;
+    let rem = numerator % divisor;
+
+    if rem.abs() >= divisor.abs() - rem.abs() {
+        return quot + numerator.signum() * divisor.signum();
+    }
+
+    return quot;
+}
+
+pub fn to_f64(v: i128, scale: u8) -> f64 {
+    return v as f64 / pow10(scale) as f64;
+}
+
+#[cfg(test)]
+mod tests {
+    use decimal;
+    use std::cmp::Ordering;
+
+    #[test]
+    fn parses_and_formats() {
+        assert_eq!(decimal::parse("123.45", None), Some((12345, 2)));
+        assert_eq!(decimal::parse("-0.5", None), Some((-5, 1)));
+        assert_eq!(decimal::parse("7", Some(2)), Some((700, 2)));
+        assert_eq!(decimal::parse("1.005", Some(2)), Some((101, 2)));
+        assert_eq!(decimal::parse("-1.005", Some(2)), Some((-101, 2)));
+        assert_eq!(decimal::parse(".25", None), Some((25, 2)));
+        assert_eq!(decimal::parse("1.2.3", None), None);
+        assert_eq!(decimal::parse("abc", None), None);
+        assert_eq!(decimal::parse("-", None), None);
+
+        assert_eq!(decimal::format(12345, 2), "123.45");
+        assert_eq!(decimal::format(-5, 3), "-0.005");
+        assert_eq!(decimal::format(42, 0), "42");
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(decimal::add(12345, 2, 5, 1), (12395, 2));
+        assert_eq!(decimal::sub(100, 2, 1, 0), (0, 2));
+        assert_eq!(decimal::mul(150, 2, 95, 2), (14250, 4));
+        assert_eq!(decimal::div(1000, 2, 3, 6), 3_333_333);
+        assert_eq!(decimal::div(-2000, 2, 3, 6), -6_666_667);
+        assert_eq!(decimal::rescale(9 * 10i128.pow(37), 38, 0), Some(1));
+        assert_eq!(decimal::rescale(-4 * 10i128.pow(37), 38, 0), Some(0));
+        assert_eq!(decimal::mul(10i128.pow(37), 38, 9, 38), (1, 38));
+        assert_eq!(decimal::num_digits(-12345), 5);
+        assert_eq!(decimal::num_digits(0), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Decimal overflow")]
+    fn div_scale_overflow() {
+        // 1 / 3e-38 does not fit at scale 6, which needs 44 digits
+        decimal::div(1, 0, 3, 6 + 38);
+    }
+
+    #[test]
+    fn compares() {
+        assert_eq!(decimal::cmp(150, 2, 15, 1), Ordering::Equal);
+        assert_eq!(decimal::cmp(151, 2, 15, 1), Ordering::Greater);
+        assert_eq!(decimal::cmp(-1, 0, 1, 38), Ordering::Less);
+        assert_eq!(decimal::normalize(1500, 3), (15, 1));
+    }
+}
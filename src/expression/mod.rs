@@ -0,0 +1,607 @@
+// < begin copyright > 
+// Copyright Ryan Marcus 2018
+// 
+// This file is part of byoo.
+// 
+// byoo is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// byoo is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with byoo.  If not, see <http://www.gnu.org/licenses/>.
+// 
+// < end copyright > 
+use serde_json;
+use std::boxed::Box;
+use std::cmp::Ordering;
+use data::{Data, DataType, DECIMAL_DIV_MIN_SCALE};
+use datetime::DatePart;
+use decimal;
+use predicate;
+use predicate::same_kind;
+
+pub enum Expression {
+    Col(usize),
+    Literal(Data),
+    Add(Box<Expression>, Box<Expression>),
+    Sub(Box<Expression>, Box<Expression>),
+    Mul(Box<Expression>, Box<Expression>),
+    Div(Box<Expression>, Box<Expression>),
+    Cmp(CmpOp, Box<Expression>, Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Not(Box<Expression>),
+    Cast(Box<Expression>, DataType),
+    Case(Vec<(Expression, Expression)>, Box<Expression>),
+    Extract(DatePart, Box<Expression>)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum CmpOp {
+    Lt, Lte, Gt, Gte, Eq, Neq
+}
+
+impl CmpOp {
+    fn from_string_code(code: &str) -> Option<CmpOp> {
+        return match code {
+            "lt" => Some(CmpOp::Lt),
+            "lte" => Some(CmpOp::Lte),
+            "gt" => Some(CmpOp::Gt),
+            "gte" => Some(CmpOp::Gte),
+            "eq" => Some(CmpOp::Eq),
+            "neq" => Some(CmpOp::Neq),
+            _ => None
+        };
+    }
+
+    fn holds(self, o: Ordering) -> bool {
+        return match self {
+            CmpOp::Lt => o == Ordering::Less,
+            CmpOp::Lte => o != Ordering::Greater,
+            CmpOp::Gt => o == Ordering::Greater,
+            CmpOp::Gte => o != Ordering::Less,
+            CmpOp::Eq => o == Ordering::Equal,
+            CmpOp::Neq => o != Ordering::Equal
+        };
+    }
+}
+
+// Booleans are represented as INTEGER 0 or 1, and an unknown truth value
+// (e.g., from comparing against a NULL) is NULL.
+fn to_bool(d: &Data) -> Option<bool> {
+    return match d {
+        Data::Integer(i) => Some(*i != 0),
+        Data::Null => None,
+        _ => panic!("Expected a boolean (INTEGER) value in expression, got {:?}", d)
+    };
+}
+
+fn from_bool(b: Option<bool>) -> Data {
+    return match b {
+        Some(b) => Data::Integer(i64::from(b)),
+        None => Data::Null
+    };
+}
+
+fn child(tree: &serde_json::Value, idx: usize) -> Result<Box<Expression>, String> {
+    let children = tree["children"].as_array()
+        .ok_or_else(|| format!("Expression {} requires children", tree["op"]))?;
+    let c = children.get(idx)
+        .ok_or_else(|| format!("Expression {} is missing child {}", tree["op"], idx))?;
+    return Ok(Box::new(Expression::from_json(c)?));
+}
+
+// reads a string attribute (e.g., the type of a cast) of an expression
+fn str_attr<'a>(tree: &'a serde_json::Value, attr: &str) -> Result<&'a str, String> {
+    return tree[attr].as_str()
+        .ok_or_else(|| format!("Expression {} requires a {} attribute", tree["op"], attr));
+}
+
+/// The result scale of dividing a DECIMAL with scale `s`. Must match the
+/// runtime behavior of `Data`'s div operator.
+fn div_scale(s: u8) -> u8 {
+    return s.max(DECIMAL_DIV_MIN_SCALE);
+}
+
+fn decimal_scale(dt: &DataType) -> u8 {
+    return match dt {
+        DataType::DECIMAL(_, s) => *s,
+        _ => 0
+    };
+}
+
+impl Expression {
+    pub fn from_json(tree: &serde_json::Value) -> Result<Expression, String> {
+        let op = tree["op"].as_str()
+            .ok_or_else(|| format!("Expression must have an op, got {}", tree))?;
+        
+        match op {
+            "col" => {
+                let idx = tree["col"].as_u64()
+                    .ok_or_else(|| String::from("Expression col requires a column index"))?;
+                return Ok(Expression::Col(idx as usize));
+            },
+
+            "literal" => {
+                let v = &tree["val"];
+                if v.is_null() {
+                    return Ok(Expression::Literal(Data::Null));
+                }
+                
+                return Ok(Expression::Literal(predicate::literal_from_json(v)?));
+            },
+
+            "add" => return Ok(Expression::Add(child(tree, 0)?, child(tree, 1)?)),
+            "sub" => return Ok(Expression::Sub(child(tree, 0)?, child(tree, 1)?)),
+            "mul" => return Ok(Expression::Mul(child(tree, 0)?, child(tree, 1)?)),
+            "div" => return Ok(Expression::Div(child(tree, 0)?, child(tree, 1)?)),
+            "and" => return Ok(Expression::And(child(tree, 0)?, child(tree, 1)?)),
+            "or" => return Ok(Expression::Or(child(tree, 0)?, child(tree, 1)?)),
+            "not" => return Ok(Expression::Not(child(tree, 0)?)),
+
+            "cast" => {
+                let dt = DataType::try_from_string_code(str_attr(tree, "type")?)?;
+                return Ok(Expression::Cast(child(tree, 0)?, dt));
+            },
+
+            "case" => {
+                let branches = tree["when"].as_array()
+                    .ok_or_else(|| String::from("Expression case requires a when array"))?
+                    .iter()
+                    .map(|b| Ok((Expression::from_json(&b["if"])?,
+                                 Expression::from_json(&b["then"])?)))
+                    .collect::<Result<Vec<_>, String>>()?;
+
+                let else_expr = match tree.get("else") {
+                    Some(e) => Expression::from_json(e)?,
+                    None => Expression::Literal(Data::Null)
+                };
+
+                return Ok(Expression::Case(branches, Box::new(else_expr)));
+            },
+
+            "extract" => {
+                let code = str_attr(tree, "part")?;
+                let part = DatePart::from_string_code(code)
+                    .ok_or_else(|| format!("Unknown date part {}", code))?;
+                return Ok(Expression::Extract(part, child(tree, 0)?));
+            },
+
+            _ => {}
+        };
+
+        if let Some(cmp) = CmpOp::from_string_code(op) {
+            return Ok(Expression::Cmp(cmp, child(tree, 0)?, child(tree, 1)?));
+        }
+
+        return Err(format!("Unknown expression op {}", op));
+    }
+
+    pub fn eval(&self, row: &[Data]) -> Data {
+        match self {
+            Expression::Col(idx) => return row[*idx].clone(),
+            Expression::Literal(d) => return d.clone(),
+            Expression::Add(l, r) => return l.eval(row) + r.eval(row),
+            Expression::Sub(l, r) => return l.eval(row) - r.eval(row),
+            Expression::Mul(l, r) => return l.eval(row) * r.eval(row),
+            Expression::Div(l, r) => return l.eval(row) / r.eval(row),
+            
+            Expression::Cmp(op, l, r) => {
+                let lv = l.eval(row);
+                let rv = r.eval(row);
+
+                if lv.is_null() || rv.is_null() {
+                    return Data::Null;
+                }
+
+                let ord = lv.partial_cmp(&rv)
+                    .unwrap_or_else(|| panic!("Cannot compare {:?} and {:?} in expression",
+                                              lv, rv));
+                return from_bool(Some(op.holds(ord)));
+            },
+
+            Expression::And(l, r) => {
+                let lv = to_bool(&l.eval(row));
+                if lv == Some(false) {
+                    return from_bool(lv);
+                }
+
+                return match (lv, to_bool(&r.eval(row))) {
+                    (_, Some(false)) => from_bool(Some(false)),
+                    (Some(true), Some(true)) => from_bool(Some(true)),
+                    _ => Data::Null
+                };
+            },
+
+            Expression::Or(l, r) => {
+                let lv = to_bool(&l.eval(row));
+                if lv == Some(true) {
+                    return from_bool(lv);
+                }
+
+                return match (lv, to_bool(&r.eval(row))) {
+                    (_, Some(true)) => from_bool(Some(true)),
+                    (Some(false), Some(false)) => from_bool(Some(false)),
+                    _ => Data::Null
+                };
+            },
+
+            Expression::Not(c) => {
+                return from_bool(to_bool(&c.eval(row)).map(|b| !b));
+            },
+
+            Expression::Cast(c, dt) => return c.eval(row).cast(dt),
+
+            Expression::Case(branches, else_expr) => {
+                for (cond, result) in branches.iter() {
+                    if to_bool(&cond.eval(row)) == Some(true) {
+                        return result.eval(row);
+                    }
+                }
+
+                return else_expr.eval(row);
+            },
+
+            Expression::Extract(part, c) => return c.eval(row).extract(*part)
+        };
+    }
+
+    /// Derives the type of this expression's result from the types of
+    /// the input columns, or describes why the expression is not well
+    /// typed.
+    pub fn out_type(&self, in_types: &[DataType]) -> Result<DataType, String> {
+        match self {
+            Expression::Col(idx) => {
+                return in_types.get(*idx).cloned().ok_or_else(
+                    || format!("Expression references column {}, but there are only {} columns",
+                               idx, in_types.len()));
+            },
+
+            // an untyped NULL literal is treated as an INTEGER
+            Expression::Literal(d) => return Ok(d.data_type().unwrap_or(DataType::INTEGER)),
+
+            Expression::Add(l, r) | Expression::Sub(l, r) => {
+                let lt = l.out_type(in_types)?;
+                let rt = r.out_type(in_types)?;
+                let is_sub = matches!(self, Expression::Sub(_, _));
+
+                return match (&lt, &rt) {
+                    (DataType::DATE, DataType::INTEGER) => Ok(DataType::DATE),
+                    (DataType::DATE, DataType::DATE) if is_sub => Ok(DataType::INTEGER),
+                    (DataType::TIMESTAMP, DataType::TIMESTAMP) if is_sub => Ok(DataType::INTEGER),
+                    _ => numeric_type(&lt, &rt, |ls, rs| ls.max(rs))
+                };
+            },
+
+            Expression::Mul(l, r) => {
+                let lt = l.out_type(in_types)?;
+                let rt = r.out_type(in_types)?;
+                return numeric_type(&lt, &rt,
+                                    |ls, rs| (ls + rs).min(decimal::MAX_PRECISION));
+            },
+
+            Expression::Div(l, r) => {
+                let lt = l.out_type(in_types)?;
+                let rt = r.out_type(in_types)?;
+                return numeric_type(&lt, &rt, |ls, _| div_scale(ls));
+            },
+
+            Expression::Cmp(op, l, r) => {
+                // NULL literals compare (to UNKNOWN) with anything, but
+                // otherwise both sides must have the same type
+                let lt = l.out_type(in_types)?;
+                let rt = r.out_type(in_types)?;
+                if l.is_null_literal() || r.is_null_literal() || same_kind(&lt, &rt) {
+                    return Ok(DataType::INTEGER);
+                }
+
+                return Err(format!("Cannot compare types {:?} and {:?} in {:?} expression",
+                                   lt, rt, op));
+            },
+
+            Expression::And(l, r) | Expression::Or(l, r) => {
+                let op = if matches!(self, Expression::And(_, _)) { "AND" } else { "OR" };
+                l.check_bool(in_types, op)?;
+                r.check_bool(in_types, op)?;
+                return Ok(DataType::INTEGER);
+            },
+
+            Expression::Not(c) => {
+                c.check_bool(in_types, "NOT")?;
+                return Ok(DataType::INTEGER);
+            },
+
+            Expression::Cast(c, dt) => {
+                let ct = c.out_type(in_types)?;
+                if !c.is_null_literal() && !ct.can_cast_to(dt) {
+                    return Err(format!("Cannot cast type {:?} to {:?}", ct, dt));
+                }
+                return Ok(dt.clone());
+            },
+
+            Expression::Case(branches, else_expr) => {
+                // the results are unified into one type, and the compute
+                // operator casts each result to it
+                for (cond, _) in branches.iter() {
+                    cond.check_bool(in_types, "CASE")?;
+                }
+
+                let mut result_type: Option<DataType> = None;
+                let results = branches.iter().map(|(_, result)| result)
+                    .chain(std::iter::once(&**else_expr));
+                for result in results {
+                    let rt = result.out_type(in_types)?;
+                    if result.is_null_literal() {
+                        continue;
+                    }
+                    
+                    result_type = Some(match result_type {
+                        None => rt,
+                        Some(t) => unify_types(&t, &rt)?
+                    });
+                }
+
+                return Ok(result_type.unwrap_or(DataType::INTEGER));
+            },
+
+            Expression::Extract(_, c) => {
+                match c.out_type(in_types)? {
+                    DataType::DATE | DataType::TIMESTAMP => {},
+                    t => {
+                        return Err(format!("Cannot extract a date part from type {:?}", t));
+                    }
+                };
+                return Ok(DataType::INTEGER);
+            }
+        };
+    }
+
+    // checks that this expression gives a boolean (INTEGER) for the given
+    // operator
+    fn check_bool(&self, in_types: &[DataType], op: &str) -> Result<(), String> {
+        return match self.out_type(in_types)? {
+            DataType::INTEGER => Ok(()),
+            t => Err(format!("{} requires boolean (INTEGER) operands, got {:?}", op, t))
+        };
+    }
+
+    fn is_null_literal(&self) -> bool {
+        return matches!(self, Expression::Literal(Data::Null));
+    }
+}
+
+// The result type of an arithmetic operator on numeric types. Any REAL
+// gives a REAL, and otherwise any DECIMAL gives a DECIMAL whose scale is
+// computed from the input scales by `scale_fn`.
+fn numeric_type<F>(lt: &DataType, rt: &DataType, scale_fn: F) -> Result<DataType, String>
+where F: Fn(u8, u8) -> u8 {
+    match (lt, rt) {
+        (DataType::INTEGER, DataType::INTEGER) => return Ok(DataType::INTEGER),
+        
+        (DataType::REAL, DataType::INTEGER)
+            | (DataType::REAL, DataType::REAL)
+            | (DataType::REAL, DataType::DECIMAL(_, _))
+            | (DataType::INTEGER, DataType::REAL)
+            | (DataType::DECIMAL(_, _), DataType::REAL) => return Ok(DataType::REAL),
+
+        (DataType::DECIMAL(_, _), DataType::INTEGER)
+            | (DataType::DECIMAL(_, _), DataType::DECIMAL(_, _))
+            | (DataType::INTEGER, DataType::DECIMAL(_, _)) => {
+            return Ok(DataType::DECIMAL(decimal::MAX_PRECISION,
+                                        scale_fn(decimal_scale(lt), decimal_scale(rt))));
+        },
+
+        _ => {
+            return Err(format!("Incompatible types {:?} and {:?} in arithmetic expression",
+                               lt, rt));
+        }
+    };
+}
+
+// The type that the results of a CASE with the types `t1` and `t2` are
+// cast to: numbers are unified like arithmetic operands, and otherwise
+// the types must be the same.
+fn unify_types(t1: &DataType, t2: &DataType) -> Result<DataType, String> {
+    if let Ok(t) = numeric_type(t1, t2, |ls, rs| ls.max(rs)) {
+        return Ok(t);
+    }
+
+    if same_kind(t1, t2) {
+        return Ok(t1.clone());
+    }
+
+    return Err(format!("CASE results have incompatible types {:?} and {:?}", t1, t2));
+}
+
+#[cfg(test)]
+mod tests {
+    use expression::Expression;
+    use data::{Data, DataType};
+
+    fn expr(json: serde_json::Value) -> Expression {
+        return Expression::from_json(&json).unwrap();
+    }
+
+    #[test]
+    fn arithmetic_test() {
+        // price * (1 - discount)
+        let e = expr(json!({"op": "mul", "children": [
+            {"op": "col", "col": 0},
+            {"op": "sub", "children": [
+                {"op": "literal", "val": 1},
+                {"op": "col", "col": 1}
+            ]}
+        ]}));
+
+        let types = vec![DataType::DECIMAL(12, 2), DataType::DECIMAL(12, 2)];
+        assert_matches!(e.out_type(&types), Ok(DataType::DECIMAL(38, 4)));
+
+        let row = vec![Data::Decimal(1000, 2), Data::Decimal(5, 2)];
+        assert_eq!(e.eval(&row), Data::Decimal(950, 2));
+        
+        let row = vec![Data::Null, Data::Decimal(5, 2)];
+        assert_eq!(e.eval(&row), Data::Null);
+
+        let e = expr(json!({"op": "div", "children": [
+            {"op": "col", "col": 0},
+            {"op": "literal", "val": 2}
+        ]}));
+        assert_matches!(e.out_type(&[DataType::INTEGER]), Ok(DataType::INTEGER));
+        assert_eq!(e.eval(&[Data::Integer(7)]), Data::Integer(3));
+
+        let e = expr(json!({"op": "div", "children": [
+            {"op": "literal", "val": 2},
+            {"op": "col", "col": 0}
+        ]}));
+        assert_eq!(e.eval(&[Data::Integer(0)]), Data::Null);
+    }
+
+    #[test]
+    fn compare_test() {
+        let e = expr(json!({"op": "and", "children": [
+            {"op": "gte", "children": [{"op": "col", "col": 0},
+                                       {"op": "literal", "val": 5}]},
+            {"op": "neq", "children": [{"op": "col", "col": 1},
+                                       {"op": "literal", "val": "x"}]}
+        ]}));
+
+        assert_matches!(e.out_type(&[DataType::INTEGER, DataType::TEXT]), Ok(DataType::INTEGER));
+        assert_eq!(e.eval(&[Data::Integer(5), Data::Text(String::from("y"))]),
+                   Data::Integer(1));
+        assert_eq!(e.eval(&[Data::Integer(4), Data::Text(String::from("y"))]),
+                   Data::Integer(0));
+        assert_eq!(e.eval(&[Data::Integer(5), Data::Null]), Data::Null);
+        assert_eq!(e.eval(&[Data::Integer(4), Data::Null]), Data::Integer(0));
+    }
+
+    #[test]
+    fn case_cast_test() {
+        let e = expr(json!({"op": "case",
+                            "when": [
+                                {"if": {"op": "lt", "children": [
+                                    {"op": "col", "col": 0},
+                                    {"op": "literal", "val": 0}]},
+                                 "then": {"op": "literal", "val": "negative"}},
+                                {"if": {"op": "eq", "children": [
+                                    {"op": "col", "col": 0},
+                                    {"op": "literal", "val": 0}]},
+                                 "then": {"op": "literal", "val": "zero"}}
+                            ],
+                            "else": {"op": "cast", "type": "TEXT",
+                                     "children": [{"op": "col", "col": 0}]}}));
+
+        assert_matches!(e.out_type(&[DataType::INTEGER]), Ok(DataType::TEXT));
+        assert_eq!(e.eval(&[Data::Integer(-3)]), Data::Text(String::from("negative")));
+        assert_eq!(e.eval(&[Data::Integer(0)]), Data::Text(String::from("zero")));
+        assert_eq!(e.eval(&[Data::Integer(7)]), Data::Text(String::from("7")));
+        assert_eq!(e.eval(&[Data::Null]), Data::Null);
+
+        let e = expr(json!({"op": "cast", "type": "DECIMAL(10,2)",
+                            "children": [{"op": "col", "col": 0}]}));
+        assert_eq!(e.eval(&[Data::Real(1.005)]), Data::Decimal(101, 2));
+        assert_eq!(e.eval(&[Data::Text(String::from("abc"))]), Data::Null);
+    }
+
+    #[test]
+    fn date_test() {
+        // extract(year from col 0) - 1990
+        let e = expr(json!({"op": "sub", "children": [
+            {"op": "extract", "part": "year",
+             "children": [{"op": "col", "col": 0}]},
+            {"op": "literal", "val": 1990}
+        ]}));
+
+        assert_matches!(e.out_type(&[DataType::DATE]), Ok(DataType::INTEGER));
+        let d = DataType::DATE.from_string(String::from("1998-12-01")).unwrap();
+        assert_eq!(e.eval(&[d.clone()]), Data::Integer(8));
+
+        let e = expr(json!({"op": "add", "children": [
+            {"op": "col", "col": 0},
+            {"op": "literal", "val": 30}
+        ]}));
+        assert_matches!(e.out_type(&[DataType::DATE]), Ok(DataType::DATE));
+        assert_eq!(e.eval(&[d]).to_string(), "1998-12-31");
+    }
+
+    #[test]
+    fn bad_types_test() {
+        let e = expr(json!({"op": "add", "children": [
+            {"op": "col", "col": 0},
+            {"op": "literal", "val": 1}
+        ]}));
+        assert!(e.out_type(&[DataType::TEXT]).is_err());
+
+        let e = expr(json!({"op": "not", "children": [{"op": "col", "col": 0}]}));
+        assert_eq!(e.out_type(&[DataType::TEXT]).err().unwrap(),
+                   "NOT requires boolean (INTEGER) operands, got TEXT");
+
+        let e = expr(json!({"op": "cast", "type": "INTEGER",
+                            "children": [{"op": "col", "col": 0}]}));
+        assert_eq!(e.out_type(&[DataType::DATE]).err().unwrap(),
+                   "Cannot cast type DATE to INTEGER");
+        assert!(e.out_type(&[DataType::BLOB]).is_err());
+        assert_matches!(e.out_type(&[DataType::DECIMAL(12, 2)]), Ok(DataType::INTEGER));
+        assert_matches!(e.out_type(&[DataType::TEXT]), Ok(DataType::INTEGER));
+
+        let e = expr(json!({"op": "case",
+                            "when": [{"if": {"op": "col", "col": 1},
+                                      "then": {"op": "col", "col": 0}}],
+                            "else": {"op": "literal", "val": 0}}));
+        assert_eq!(e.out_type(&[DataType::DATE, DataType::INTEGER]).err().unwrap(),
+                   "CASE results have incompatible types DATE and INTEGER");
+        assert!(e.out_type(&[DataType::INTEGER, DataType::TEXT]).is_err());
+        assert_matches!(e.out_type(&[DataType::DECIMAL(12, 2), DataType::INTEGER]),
+                        Ok(DataType::DECIMAL(38, 2)));
+        assert_matches!(e.out_type(&[DataType::REAL, DataType::INTEGER]),
+                        Ok(DataType::REAL));
+    }
+
+    #[test]
+    fn cmp_types_test() {
+        let e = expr(json!({"op": "gt", "children": [
+            {"op": "col", "col": 0},
+            {"op": "literal", "val": 0.5}
+        ]}));
+        assert_eq!(e.out_type(&[DataType::INTEGER]).err().unwrap(),
+                   "Cannot compare types INTEGER and REAL in Gt expression");
+        assert_matches!(e.out_type(&[DataType::REAL]), Ok(DataType::INTEGER));
+
+        // DECIMALs of different scales and NULLs compare with anything
+        let e = expr(json!({"op": "eq", "children": [
+            {"op": "col", "col": 0},
+            {"op": "col", "col": 1}
+        ]}));
+        assert!(e.out_type(&[DataType::DECIMAL(12, 2), DataType::DECIMAL(10, 4)]).is_ok());
+        assert!(e.out_type(&[DataType::DATE, DataType::TEXT]).is_err());
+
+        let e = expr(json!({"op": "neq", "children": [
+            {"op": "col", "col": 0},
+            {"op": "literal", "val": null}
+        ]}));
+        assert!(e.out_type(&[DataType::TEXT]).is_ok());
+    }
+
+    #[test]
+    fn bad_json_test() {
+        let err = |json: serde_json::Value| Expression::from_json(&json).err().unwrap();
+
+        assert_eq!(err(json!({"op": "col"})), "Expression col requires a column index");
+        assert_eq!(err(json!({"op": "not"})), "Expression \"not\" requires children");
+        assert_eq!(err(json!({"op": "add", "children": [{"op": "col", "col": 0}]})),
+                   "Expression \"add\" is missing child 1");
+        assert_eq!(err(json!({"op": "cast", "type": "DECIMAL(x)",
+                              "children": [{"op": "col", "col": 0}]})),
+                   "invalid DECIMAL type string DECIMAL(x)");
+        assert_eq!(err(json!({"op": "extract", "part": "week",
+                              "children": [{"op": "col", "col": 0}]})),
+                   "Unknown date part week");
+        assert_eq!(err(json!({"op": "case", "when": [{"if": {"op": "foo"}}]})),
+                   "Unknown expression op foo");
+    }
+}
@@ -17,6 +17,7 @@
 // along with byoo.  If not, see <http://www.gnu.org/licenses/>.
 // 
 // < end copyright > 
+use data::Data;
 use operator_buffer::OperatorReadBuffer;
 use spillable_store::WritableSpillableStore;
 use std::collections::vec_deque::VecDeque;
@@ -25,9 +26,39 @@ use fnv::{FnvHasher};
 use std::cmp;
 
 
-const MAX_FILES:usize = 32;
+pub const MAX_FILES:usize = 32;
 
 
+// the finalizer of MurmurHash3. The low bits of an FNV hash depend only on
+// the low bits of its input, so a seed has to be mixed into every bit of
+// the hash to split up rows that already share a partition.
+fn mix(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^= h >> 33;
+    return h;
+}
+
+// Finds which of `num_partitions` partitions a row belongs to, based on the
+// hash of its `relv_cols` seeded with `seed`.
+pub fn hash_partition(row: &[Data], relv_cols: &[usize], seed: u64,
+                      num_partitions: usize) -> usize {
+    let mut hasher = FnvHasher::default();
+    for &col_idx in relv_cols.iter() {
+        row[col_idx].hash(&mut hasher);
+    }
+
+    let hash = if seed == 0 {
+        hasher.finish()
+    } else {
+        mix(hasher.finish() ^ seed)
+    };
+
+    return (hash % num_partitions as u64) as usize;
+}
+
 pub struct ReadableHashPartitionStore {
     data: VecDeque<OperatorReadBuffer>,
     num_partitions: usize
@@ -58,8 +89,20 @@ impl ReadableHashPartitionStore {
 
     pub fn with_partitions(num_partitions: usize,
                        buf_size: usize,
-                       mut data: OperatorReadBuffer, relv_cols: &[usize])
+                       data: OperatorReadBuffer, relv_cols: &[usize])
                        -> ReadableHashPartitionStore {
+        return ReadableHashPartitionStore::with_seeded_partitions(
+            num_partitions, buf_size, data, relv_cols, 0);
+    }
+
+    // Partitions using a hash seeded with `seed`, so that the rows of a
+    // single partition can be split up again by re-partitioning them with
+    // a different seed.
+    pub fn with_seeded_partitions(num_partitions: usize,
+                                  buf_size: usize,
+                                  mut data: OperatorReadBuffer, relv_cols: &[usize],
+                                  seed: u64)
+                                  -> ReadableHashPartitionStore {
 
         let mut bufs = Vec::with_capacity(num_partitions);
 
@@ -68,15 +111,7 @@ impl ReadableHashPartitionStore {
         }
 
         iterate_buffer!(data, row, {
-            let mut hasher = FnvHasher::default();
-            for &col_idx in relv_cols.iter() {
-                row[col_idx].hash(&mut hasher);
-            }
-            
-            let mut hash_value = (hasher.finish() % num_partitions as u64)
-                as usize;
-
-            bufs[hash_value].push_row(row);
+            bufs[hash_partition(row, relv_cols, seed, num_partitions)].push_row(row);
         });
 
         let mut vdq = VecDeque::new();
@@ -142,4 +177,29 @@ mod test {
         assert!(rhps.next_buf().is_none());
         assert_eq!(row_count, 3*10000);
     }
+
+    #[test]
+    fn seeded_rhps_test() {
+        let mut wss = WritableSpillableStore::new(50000, vec![DataType::INTEGER]);
+        for i in 0..10000 {
+            wss.push_row(&vec![Data::Integer(i)]);
+        }
+
+        let mut rhps = ReadableHashPartitionStore::with_partitions(
+            4, 100, wss.into_read_buffer().1, &[0]);
+        let part = rhps.next_buf().unwrap();
+
+        // re-partitioning with the same hash would put every row back into
+        // a single partition
+        let mut rehashed = ReadableHashPartitionStore::with_seeded_partitions(
+            4, 100, part, &[0], 1);
+
+        let mut sizes = Vec::new();
+        while let Some(buf) = rehashed.next_buf() {
+            sizes.push(buf.into_vec().len());
+        }
+
+        assert_eq!(sizes.len(), 4);
+        assert!(sizes.iter().all(|&s| s > 0));
+    }
 }
@@ -30,6 +30,11 @@ extern crate rand;
 extern crate fnv;
 extern crate hashbrown;
 extern crate snap;
+extern crate regex;
+
+#[cfg(test)]
+#[macro_use]
+extern crate approx;
 
 
 #[macro_use] 
@@ -38,6 +43,8 @@ extern crate serde_json;
 extern crate either;
 
 mod data;
+mod datetime;
+mod decimal;
 
 #[macro_use]
 mod operator_buffer;
@@ -48,10 +55,11 @@ mod hash_partition_store;
 mod operator;
 
 mod predicate;
+mod expression;
 mod agg;
 mod compile;
 
-pub use compile::{compile, tree_to_gv};
+pub use compile::{compile, try_compile, tree_to_gv};
 pub use data::Data;
 pub use data::rows_to_string;
 
@@ -0,0 +1,205 @@
+// < begin copyright > 
+// Copyright Ryan Marcus 2018
+// 
+// This file is part of byoo.
+// 
+// byoo is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// byoo is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with byoo.  If not, see <http://www.gnu.org/licenses/>.
+// 
+// < end copyright > 
+use operator_buffer::{OperatorReadBuffer, OperatorWriteBuffer};
+use operator::ConstructableOperator;
+use expression::Expression;
+use data::{Data, DataType};
+use std::fs::File;
+use serde_json;
+
+/// A computed column: the expression, the column it replaces (if any),
+/// and the type of its values.
+struct ComputedCol {
+    expr: Expression,
+    replace: Option<usize>,
+    out_type: DataType
+}
+
+pub struct Compute {
+    input: OperatorReadBuffer,
+    output: OperatorWriteBuffer,
+    cols: Vec<ComputedCol>
+}
+
+/// Parses the `exprs` option of a compute operator. Each entry is an
+/// object with an `expr` and an optional `replace` column index; columns
+/// without `replace` are appended to the end of the row.
+fn cols_from_json(options: &serde_json::Value,
+                  in_types: &[DataType]) -> Result<Vec<ComputedCol>, String> {
+    let exprs = options["exprs"].as_array()
+        .ok_or_else(|| String::from("Compute operator requires an exprs array option"))?;
+
+    return exprs.iter()
+        .map(|e| {
+            let expr = Expression::from_json(&e["expr"])?;
+            let out_type = expr.out_type(in_types)?;
+            let replace = e.get("replace").map(|v| v.as_i64().unwrap() as usize);
+
+            if let Some(idx) = replace {
+                if idx >= in_types.len() {
+                    return Err(format!("Compute operator cannot replace column {}, there are only {} columns",
+                                       idx, in_types.len()));
+                }
+            }
+            
+            return Ok(ComputedCol { expr, replace, out_type });
+        })
+        .collect();
+}
+
+/// Checks the expressions of a compute operator against the types of its
+/// input columns.
+pub fn check_compute_types(options: &serde_json::Value,
+                           in_types: &[DataType]) -> Result<(), String> {
+    return cols_from_json(options, in_types).map(|_| ());
+}
+
+/// Computes the output types of a compute operator with the given options.
+pub fn compute_out_types(options: &serde_json::Value,
+                         in_types: &[DataType]) -> Vec<DataType> {
+    let mut types = in_types.to_vec();
+    
+    for col in cols_from_json(options, in_types).unwrap_or_else(|e| panic!("{}", e)) {
+        match col.replace {
+            Some(idx) => { types[idx] = col.out_type; },
+            None => { types.push(col.out_type); }
+        };
+    }
+
+    return types;
+}
+
+// converts a computed value to its column's declared type, since (for
+// example) CASE branches or decimal scales may differ from row to row
+fn conform(d: Data, dt: &DataType) -> Data {
+    let matches_type = match (&d, dt) {
+        (Data::Null, _) => true,
+        (Data::Decimal(_, s), DataType::DECIMAL(_, ts)) => s == ts,
+        (_, DataType::DECIMAL(_, _)) => false,
+        _ => d.data_type().map(|t| t.to_code() == dt.to_code()).unwrap_or(true)
+    };
+
+    if matches_type {
+        return d;
+    }
+
+    return d.cast(dt);
+}
+
+impl Compute {
+    fn new(input: OperatorReadBuffer, output: OperatorWriteBuffer,
+           cols: Vec<ComputedCol>) -> Compute {
+        return Compute {
+            input, output, cols
+        };
+    }
+
+    pub fn start(mut self) {
+        let mut computed = Vec::with_capacity(self.cols.len());
+        
+        iterate_buffer!(self.input, row, {
+            // evaluate everything against the input row first, so that
+            // replacing a column does not affect later expressions
+            for col in self.cols.iter() {
+                computed.push(conform(col.expr.eval(row), &col.out_type));
+            }
+
+            let mut out = row.to_vec();
+            for (col, val) in self.cols.iter().zip(computed.drain(..)) {
+                match col.replace {
+                    Some(idx) => { out[idx] = val; },
+                    None => { out.push(val); }
+                };
+            }
+
+            self.output.write(out);
+        });
+    }
+}
+
+impl ConstructableOperator for Compute {
+    fn from_buffers(output: Option<OperatorWriteBuffer>,
+                    mut input: Vec<OperatorReadBuffer>,
+                    file: Option<File>,
+                    options: serde_json::Value) -> Self {
+
+        assert!(file.is_none());
+        let ob = output.unwrap();
+
+        assert_eq!(input.len(), 1);
+        let ib = input.remove(0);
+
+        let cols = cols_from_json(&options, ib.types())
+            .unwrap_or_else(|e| panic!("{}", e));
+        return Compute::new(ib, ob, cols);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use operator::ConstructableOperator;
+    use operator::compute::{Compute, compute_out_types};
+    use operator_buffer::make_buffer_pair;
+    use data::{Data, DataType};
+
+    #[test]
+    fn appends_and_replaces() {
+        let options = json!({"exprs": [
+            {"expr": {"op": "mul", "children": [{"op": "col", "col": 0},
+                                                 {"op": "literal", "val": 2}]}},
+            {"expr": {"op": "cast", "type": "REAL",
+                      "children": [{"op": "col", "col": 0}]},
+             "replace": 1}
+        ]});
+
+        let in_types = vec![DataType::INTEGER, DataType::TEXT];
+        let out_types = compute_out_types(&options, &in_types);
+        assert_eq!(out_types.len(), 3);
+        assert_matches!(out_types[1], DataType::REAL);
+        assert_matches!(out_types[2], DataType::INTEGER);
+        
+        let (ir, mut iw) = make_buffer_pair(5, 10, in_types);
+        let (mut or, ow) = make_buffer_pair(5, 10, out_types);
+
+        for i in 0..25 {
+            iw.write(vec![Data::Integer(i), Data::Text(String::from("x"))]);
+        }
+        iw.write(vec![Data::Null, Data::Text(String::from("x"))]);
+        drop(iw);
+
+        let c = Compute::from_buffers(Some(ow), vec![ir], None, options);
+        c.start();
+
+        let mut rc = 0;
+        iterate_buffer!(or, idx, row, {
+            if idx == 25 {
+                assert_eq!(row.to_vec(), vec![Data::Null, Data::Null, Data::Null]);
+            } else {
+                let i = idx as i64;
+                assert_eq!(row.to_vec(), vec![Data::Integer(i),
+                                              Data::Real(i as f64),
+                                              Data::Integer(i * 2)]);
+            }
+            rc += 1;
+        });
+
+        assert_eq!(rc, 26);
+    }
+}
@@ -0,0 +1,311 @@
+// < begin copyright > 
+// Copyright Ryan Marcus 2018
+// 
+// This file is part of byoo.
+// 
+// byoo is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// byoo is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with byoo.  If not, see <http://www.gnu.org/licenses/>.
+// 
+// < end copyright > 
+use data::{Data, DataType};
+use operator::groupby;
+use operator::groupby::hash_aggregator::HashAggregator;
+use operator::ConstructableOperator;
+use operator_buffer::{OperatorReadBuffer, OperatorWriteBuffer};
+use serde_json;
+use std::fs::File;
+
+// the largest number of columns a cube may be taken over
+const MAX_CUBE_COLS: usize = 12;
+
+// A set of group keys aggregated together, along with its grouping id. Bit
+// `n - 1 - i` of the grouping id is set when the `i`th of the `n` key
+// columns is rolled up in this set, matching SQL's GROUPING_ID.
+struct GroupingSet {
+    grouping_id: i64,
+    rolled_up: Vec<bool>
+}
+
+pub struct GroupingSetsGroupBy {
+    child: OperatorReadBuffer,
+    out: OperatorWriteBuffer,
+    key_cols: Vec<usize>,
+    sets: Vec<GroupingSet>,
+    aggs: serde_json::Value,
+    memory_budget: usize
+}
+
+// Reads the grouping sets of a grouping sets group by, given either as an
+// explicit list of "sets" or as the "rollup" or "cube" of a list of columns.
+// Returns the key columns (every column used by any set, in order of first
+// use) along with the sets themselves. Duplicate sets are only aggregated
+// once.
+pub fn json_to_grouping_sets(options: &serde_json::Value)
+                             -> Result<(Vec<usize>, Vec<Vec<usize>>), String> {
+    fn to_cols(json: &serde_json::Value) -> Result<Vec<usize>, String> {
+        let cols = json.as_array()
+            .ok_or_else(|| format!("invalid grouping set {}", json))?;
+        
+        return cols.iter()
+            .map(|c| c.as_u64()
+                 .map(|c| c as usize)
+                 .ok_or_else(|| format!("invalid group key column {}", c)))
+            .collect();
+    }
+    
+    let (key_cols, sets) = if options["sets"].is_array() {
+        let sets = options["sets"].as_array().unwrap().iter()
+            .map(to_cols)
+            .collect::<Result<Vec<Vec<usize>>, String>>()?;
+
+        let mut key_cols = Vec::new();
+        for &col in sets.iter().flatten() {
+            if !key_cols.contains(&col) {
+                key_cols.push(col);
+            }
+        }
+
+        (key_cols, sets)
+    } else if options["rollup"].is_array() {
+        // every prefix of the columns, from longest to shortest
+        let key_cols = to_cols(&options["rollup"])?;
+        let sets = (0..=key_cols.len()).rev()
+            .map(|len| key_cols[..len].to_vec())
+            .collect();
+        
+        (key_cols, sets)
+    } else if options["cube"].is_array() {
+        // every subset of the columns, in grouping id order
+        let key_cols = to_cols(&options["cube"])?;
+        if key_cols.len() > MAX_CUBE_COLS {
+            return Err(format!("cube over {} columns, but at most {} are allowed",
+                               key_cols.len(), MAX_CUBE_COLS));
+        }
+
+        let n = key_cols.len();
+        let sets = (0..(1 << n))
+            .map(|gid: usize| key_cols.iter().enumerate()
+                 .filter(|(i, _)| gid & (1 << (n - 1 - i)) == 0)
+                 .map(|(_, &col)| col)
+                 .collect())
+            .collect();
+        
+        (key_cols, sets)
+    } else {
+        return Err(String::from("missing grouping sets, rollup, or cube"));
+    };
+
+    if sets.is_empty() {
+        return Err(String::from("grouping sets cannot be empty"));
+    }
+
+    if key_cols.len() > 63 {
+        return Err(format!("{} key columns is too many for a grouping id",
+                           key_cols.len()));
+    }
+
+    return Ok((key_cols, sets));
+}
+
+impl GroupingSetsGroupBy {
+    fn new(child: OperatorReadBuffer, out: OperatorWriteBuffer,
+           key_cols: Vec<usize>, sets: Vec<Vec<usize>>,
+           aggs: serde_json::Value, memory_budget: usize) -> GroupingSetsGroupBy {
+
+        let n = key_cols.len();
+        let mut grouping_sets: Vec<GroupingSet> = Vec::new();
+        for set in sets {
+            let rolled_up: Vec<bool> = key_cols.iter()
+                .map(|col| !set.contains(col))
+                .collect();
+            
+            let grouping_id = rolled_up.iter().enumerate()
+                .filter(|(_, &r)| r)
+                .map(|(i, _)| 1 << (n - 1 - i))
+                .sum();
+
+            if grouping_sets.iter().all(|s| s.grouping_id != grouping_id) {
+                grouping_sets.push(GroupingSet { grouping_id, rolled_up });
+            }
+        }
+        
+        return GroupingSetsGroupBy {
+            child, out, key_cols,
+            sets: grouping_sets,
+            aggs,
+            memory_budget
+        };
+    }
+
+    pub fn start(mut self) {
+        let in_types = self.child.types().to_vec();
+
+        // the grouping id followed by the key columns, with NULLs for rolled
+        // up keys
+        let mut key_types = vec![DataType::INTEGER];
+        key_types.extend(self.key_cols.iter().map(|&idx| in_types[idx].clone()));
+
+        let mut hash_agg = HashAggregator::new(
+            self.aggs.take(), key_types, in_types, self.memory_budget);
+
+        iterate_buffer!(self.child, row, {
+            for set in self.sets.iter() {
+                let mut key = Vec::with_capacity(self.key_cols.len() + 1);
+                key.push(Data::Integer(set.grouping_id));
+                for (&col, &rolled_up) in self.key_cols.iter().zip(set.rolled_up.iter()) {
+                    key.push(if rolled_up { Data::Null } else { row[col].clone() });
+                }
+                
+                hash_agg.consume(key, row);
+            }
+        });
+
+        let key_cols = &self.key_cols;
+        let out = &mut self.out;
+        hash_agg.finish(|key, mut row| {
+            for (&col, key_val) in key_cols.iter().zip(key[1..].iter()) {
+                if let Data::Null = key_val {
+                    row[col] = Data::Null;
+                }
+            }
+
+            row.push(key[0].clone());
+            out.write(row);
+        });
+    }
+}
+
+impl ConstructableOperator for GroupingSetsGroupBy {
+    fn from_buffers(output: Option<OperatorWriteBuffer>,
+                    mut input: Vec<OperatorReadBuffer>,
+                    file: Option<File>,
+                    options: serde_json::Value) -> Self {
+        
+        assert!(file.is_none());
+        let o = output.unwrap();
+
+        assert_eq!(input.len(), 1);
+        let child = input.remove(0);
+
+        let (key_cols, sets) = json_to_grouping_sets(&options)
+            .unwrap_or_else(|e| panic!("grouping sets group by {}", e));
+
+        assert!(options["aggregates"].is_array(),
+                "grouping sets group by missing aggregates");
+
+        let memory_budget = groupby::json_to_memory_budget(&options)
+            .unwrap_or_else(|e| panic!("grouping sets group by {}", e));
+
+        return GroupingSetsGroupBy::new(child, o, key_cols, sets,
+                                        options["aggregates"].clone(),
+                                        memory_budget);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use operator::groupby::GroupingSetsGroupBy;
+    use operator::groupby::grouping_sets_groupby::json_to_grouping_sets;
+    use operator::groupby::hash_aggregator::DEFAULT_MEMORY_BUDGET;
+    use operator_buffer::{make_buffer_pair};
+    use data::{Data, DataType};
+    use std::thread;
+
+    #[test]
+    fn parse_sets_test() {
+        let (keys, sets) = json_to_grouping_sets(&json!({"sets": [[1, 0], [2], []]})).unwrap();
+        assert_eq!(keys, vec![1, 0, 2]);
+        assert_eq!(sets, vec![vec![1, 0], vec![2], vec![]]);
+
+        let (keys, sets) = json_to_grouping_sets(&json!({"rollup": [0, 1]})).unwrap();
+        assert_eq!(keys, vec![0, 1]);
+        assert_eq!(sets, vec![vec![0, 1], vec![0], vec![]]);
+
+        let (keys, sets) = json_to_grouping_sets(&json!({"cube": [0, 1]})).unwrap();
+        assert_eq!(keys, vec![0, 1]);
+        assert_eq!(sets, vec![vec![0, 1], vec![0], vec![1], vec![]]);
+
+        assert!(json_to_grouping_sets(&json!({"sets": []})).is_err());
+        assert!(json_to_grouping_sets(&json!({"sets": [[0, "a"]]})).is_err());
+        assert!(json_to_grouping_sets(&json!({"cube": (0..13).collect::<Vec<usize>>()})).is_err());
+        assert!(json_to_grouping_sets(&json!({})).is_err());
+    }
+
+    fn rollup_results(memory_budget: usize) -> Vec<Vec<Data>> {
+        let (r, mut w) = make_buffer_pair(5, 10, vec![DataType::INTEGER, DataType::TEXT,
+                                                      DataType::INTEGER]);
+
+        let jh = thread::spawn(move || {
+            for i in 0..120 {
+                let flag = if i % 2 == 0 { "A" } else { "R" };
+                w.write(vec![Data::Integer(i % 3), Data::Text(String::from(flag)),
+                             Data::Integer(i)]);
+            }
+            w.flush();
+        });
+
+        let (r2, w2) = make_buffer_pair(5, 10, vec![DataType::INTEGER, DataType::TEXT,
+                                                    DataType::INTEGER, DataType::INTEGER,
+                                                    DataType::INTEGER]);
+
+        let aggs = json!([{"op": "count", "col": 2}]);
+        let gb = GroupingSetsGroupBy::new(r, w2, vec![0, 1],
+                                          vec![vec![0, 1], vec![0], vec![]], aggs,
+                                          memory_budget);
+        let gb_jh = thread::spawn(move || gb.start());
+
+        let mut results = r2.into_vec();
+        jh.join().unwrap();
+        gb_jh.join().unwrap();
+
+        results.sort_by(|a, b| {
+            a[4].partial_cmp(&b[4]).unwrap()
+                .then(a[0].partial_cmp(&b[0]).unwrap())
+                .then(a[1].partial_cmp(&b[1]).unwrap())
+        });
+        return results;
+    }
+
+    #[test]
+    fn rollup_test() {
+        let results = rollup_results(DEFAULT_MEMORY_BUDGET);
+        assert_eq!(results.len(), 6 + 3 + 1);
+
+        // the finest grouping comes first
+        for (idx, row) in results[0..6].iter().enumerate() {
+            assert_eq!(row[0], Data::Integer(idx as i64 / 2));
+            assert_eq!(row[1], Data::Text(String::from(if idx % 2 == 0 { "A" } else { "R" })));
+            assert_eq!(row[3], Data::Integer(20));
+            assert_eq!(row[4], Data::Integer(0));
+        }
+
+        // the second key is rolled up
+        for (idx, row) in results[6..9].iter().enumerate() {
+            assert_eq!(row[0], Data::Integer(idx as i64));
+            assert_eq!(row[1], Data::Null);
+            assert_eq!(row[3], Data::Integer(40));
+            assert_eq!(row[4], Data::Integer(1));
+        }
+
+        // the grand total
+        assert_eq!(results[9][0], Data::Null);
+        assert_eq!(results[9][1], Data::Null);
+        assert_eq!(results[9][3], Data::Integer(120));
+        assert_eq!(results[9][4], Data::Integer(3));
+    }
+
+    #[test]
+    fn spilled_rollup_test() {
+        assert_eq!(rollup_results(512), rollup_results(DEFAULT_MEMORY_BUDGET));
+    }
+}
@@ -0,0 +1,373 @@
+// < begin copyright > 
+// Copyright Ryan Marcus 2018
+// 
+// This file is part of byoo.
+// 
+// byoo is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// byoo is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with byoo.  If not, see <http://www.gnu.org/licenses/>.
+// 
+// < end copyright > 
+use agg::{Aggregate, AggregateSpec};
+use data::{Data, DataType};
+use hash_partition_store::{ReadableHashPartitionStore, MAX_FILES};
+use spillable_store::WritableSpillableStore;
+use operator::groupby;
+use serde_json;
+use std::collections::HashMap;
+use std::mem;
+
+// the default number of bytes of groups kept in memory before their
+// partial states are spilled
+pub const DEFAULT_MEMORY_BUDGET: usize = 64 * 1024 * 1024;
+
+// the estimated bookkeeping overhead of a group and of each of its
+// aggregates, in bytes
+const GROUP_OVERHEAD: usize = 64;
+const AGG_OVERHEAD: usize = 64;
+
+// how many times a partition that does not fit in memory is split up
+// before it is aggregated in memory regardless of the budget
+pub const MAX_REPARTITION_DEPTH: u64 = 8;
+
+type RowAndAggs = (Vec<Data>, Vec<Box<dyn Aggregate + Send>>);
+
+// Aggregates rows into groups identified by an arbitrary key. Groups are
+// kept in memory until their estimated size exceeds the memory budget, at
+// which point the partial states of every group are spilled. Once all the
+// rows have been consumed, the spilled states are hash partitioned by key
+// and each partition is merged on its own, recursively re-partitioning any
+// partition that still does not fit in the budget.
+pub struct HashAggregator {
+    agg_specs: Vec<AggregateSpec>,
+    key_types: Vec<DataType>,
+    in_types: Vec<DataType>,
+    state_types: Vec<Vec<DataType>>,
+    empty_states: Vec<Vec<Data>>,
+    groups: HashMap<Vec<Data>, RowAndAggs>,
+    groups_size: usize,
+    spilled: Option<WritableSpillableStore>,
+    spilled_size: usize,
+    memory_budget: usize,
+    depth: u64
+}
+
+pub fn estimate_size(key: &[Data], witness: &[Data], num_aggs: usize) -> usize {
+    let values = key.iter().chain(witness.iter());
+    return GROUP_OVERHEAD + num_aggs * AGG_OVERHEAD
+        + values.map(|d| mem::size_of::<Data>() + d.num_bytes()).sum::<usize>();
+}
+
+// the memory used by the values collected by a group's aggregates
+fn states_size(aggs: &[Box<dyn Aggregate + Send>]) -> usize {
+    return aggs.iter().map(|agg| agg.state_size()).sum();
+}
+
+impl HashAggregator {
+    pub fn new(aggs_config: serde_json::Value,
+               key_types: Vec<DataType>,
+               in_types: Vec<DataType>,
+               memory_budget: usize) -> HashAggregator {
+        let agg_specs = groupby::json_to_agg_specs(&aggs_config);
+        let mut aggs = groupby::new_aggs(&agg_specs);
+        let state_types = aggs.iter()
+            .map(|agg| agg.state_types(&in_types))
+            .collect();
+        let empty_states = aggs.iter_mut()
+            .map(|agg| agg.partial_state())
+            .collect();
+
+        return HashAggregator {
+            agg_specs, key_types, in_types, state_types, empty_states,
+            groups: HashMap::new(),
+            groups_size: 0,
+            spilled: None,
+            spilled_size: 0,
+            memory_budget,
+            depth: 0
+        };
+    }
+
+    // an aggregator for the spilled partial states of one partition
+    fn for_partition(&self) -> HashAggregator {
+        let memory_budget = if self.depth + 1 >= MAX_REPARTITION_DEPTH {
+            usize::MAX
+        } else {
+            self.memory_budget
+        };
+        
+        return HashAggregator {
+            agg_specs: self.agg_specs.clone(),
+            key_types: self.key_types.clone(),
+            in_types: self.in_types.clone(),
+            state_types: self.state_types.clone(),
+            empty_states: self.empty_states.clone(),
+            groups: HashMap::new(),
+            groups_size: 0,
+            spilled: None,
+            spilled_size: 0,
+            memory_budget,
+            depth: self.depth + 1
+        };
+    }
+
+    #[cfg(test)]
+    pub fn did_spill(&self) -> bool {
+        return self.spilled.is_some();
+    }
+
+    // Finds the aggregates of the group with the given key, creating the
+    // group with the given witness if it does not exist.
+    fn group_for(&mut self, key: Vec<Data>, witness: &[Data])
+                 -> &mut Vec<Box<dyn Aggregate + Send>> {
+        if !self.groups.contains_key(&key) {
+            let num_aggs = self.state_types.len();
+            let size = estimate_size(&key, witness, num_aggs);
+            if !self.groups.is_empty()
+                && self.groups_size.saturating_add(size) > self.memory_budget {
+                self.spill_groups();
+            }
+
+            self.groups_size += size;
+        }
+
+        let agg_specs = &self.agg_specs;
+        return &mut self.groups.entry(key)
+            .or_insert_with(|| {
+                return (witness.to_vec(), groupby::new_aggs(agg_specs));
+            }).1;
+    }
+
+    // Charges the growth of a group's aggregates to the groups in memory,
+    // spilling every group if that puts them over the budget.
+    fn charge_growth(&mut self, before: usize, after: usize) {
+        self.groups_size = (self.groups_size + after).saturating_sub(before);
+        if self.groups.len() > 1 && self.groups_size > self.memory_budget {
+            self.spill_groups();
+        }
+    }
+
+    // Adds a row to the group with the given key. The first row of each
+    // group is kept as its witness.
+    pub fn consume(&mut self, key: Vec<Data>, row: &[Data]) {
+        let aggs = self.group_for(key, row);
+        let before = states_size(aggs);
+        for agg in aggs.iter_mut() {
+            agg.consume(row);
+        }
+        let after = states_size(aggs);
+        self.charge_growth(before, after);
+    }
+
+    // Merges a spilled row of key, witness, and partial states.
+    fn merge(&mut self, spilled_row: &[Data]) {
+        let key_len = self.key_types.len();
+        let witness_end = key_len + self.in_types.len();
+        let state_types = self.state_types.clone();
+        let aggs = self.group_for(spilled_row[..key_len].to_vec(),
+                                  &spilled_row[key_len..witness_end]);
+
+        let before = states_size(aggs);
+        let mut offset = witness_end;
+        for (agg, types) in aggs.iter_mut().zip(state_types.iter()) {
+            agg.merge(&spilled_row[offset..offset + types.len()]);
+            offset += types.len();
+        }
+        let after = states_size(aggs);
+        self.charge_growth(before, after);
+    }
+
+    // Writes each group's key and witness row followed by the partial
+    // states of its aggregates. Any part of an aggregate's state that the
+    // aggregate itself spilled to disk is written first, one chunk per
+    // row, with the states of a new group for the other aggregates.
+    fn spill_groups(&mut self) {
+        if self.spilled.is_none() {
+            let mut types = self.key_types.clone();
+            types.extend(self.in_types.iter().cloned());
+            types.extend(self.state_types.iter().flatten().cloned());
+            self.spilled = Some(WritableSpillableStore::new(4096, types));
+        }
+
+        let store = self.spilled.as_mut().unwrap();
+        let empty_states = &self.empty_states;
+        let mut spill_row = Vec::new();
+        for (key, (witness, mut aggs)) in self.groups.drain() {
+            for (i, agg) in aggs.iter_mut().enumerate() {
+                while let Some(state) = agg.spilled_state() {
+                    spill_row.extend(key.iter().cloned());
+                    spill_row.extend(witness.iter().cloned());
+                    spill_row.extend(empty_states[..i].iter().flatten().cloned());
+                    spill_row.extend(state);
+                    spill_row.extend(empty_states[i + 1..].iter().flatten().cloned());
+                    store.push_row(&spill_row);
+                    spill_row.clear();
+                }
+            }
+
+            spill_row.extend(key);
+            spill_row.extend(witness);
+            for agg in aggs.iter_mut() {
+                spill_row.extend(agg.partial_state());
+            }
+            store.push_row(&spill_row);
+            spill_row.clear();
+        }
+        self.spilled_size = self.spilled_size.saturating_add(self.groups_size);
+        self.groups_size = 0;
+    }
+
+    // Produces the final value of every group, passing each group's key and
+    // its witness row followed by its aggregates to `emit`.
+    pub fn finish<F: FnMut(Vec<Data>, Vec<Data>)>(self, mut emit: F) {
+        self.finish_into(&mut emit);
+    }
+
+    fn finish_into<F: FnMut(Vec<Data>, Vec<Data>)>(mut self, emit: &mut F) {
+        if self.spilled.is_none() {
+            // everything fit in memory
+            for (key, (mut witness, aggs)) in self.groups.drain() {
+                witness.extend(aggs.into_iter().map(|mut agg| agg.produce()));
+                emit(key, witness);
+            }
+            return;
+        }
+
+        self.spill_groups();
+
+        // every partial state of a group ends up in the same partition, so
+        // each partition can be merged on its own. Each level of
+        // re-partitioning uses a different hash, so that a partition that
+        // is still too large gets split up.
+        let spilled = self.spilled.take().unwrap().into_read_buffer().1;
+        let num_partitions = (self.spilled_size / self.memory_budget + 1).clamp(2, MAX_FILES);
+        let key_cols: Vec<usize> = (0..self.key_types.len()).collect();
+        let mut rhps = ReadableHashPartitionStore::with_seeded_partitions(
+            num_partitions, 4096, spilled, &key_cols, self.depth);
+
+        while let Some(mut buf) = rhps.next_buf() {
+            let mut partition = self.for_partition();
+            iterate_buffer!(buf, row, {
+                partition.merge(row);
+            });
+            partition.finish_into(emit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use operator::groupby::hash_aggregator::HashAggregator;
+    use data::{Data, DataType};
+
+    fn count_groups(memory_budget: usize, num_groups: i64) -> (bool, Vec<(i64, i64)>) {
+        let aggs = json!([{"op": "count", "col": 0}]);
+        let mut hash_agg = HashAggregator::new(aggs, vec![DataType::INTEGER],
+                                               vec![DataType::INTEGER], memory_budget);
+
+        for i in 0..10000 {
+            let row = vec![Data::Integer(i % num_groups)];
+            hash_agg.consume(row.clone(), &row);
+        }
+        let did_spill = hash_agg.did_spill();
+
+        let mut results = Vec::new();
+        hash_agg.finish(|key, row| {
+            assert_eq!(key[0], row[0]);
+            results.push((row[0].as_i64(), row[1].as_i64()));
+        });
+        results.sort();
+        
+        return (did_spill, results);
+    }
+    
+    #[test]
+    fn low_cardinality_in_memory_test() {
+        let (did_spill, results) = count_groups(super::DEFAULT_MEMORY_BUDGET, 10);
+        assert!(!did_spill);
+        assert_eq!(results, (0..10).map(|i| (i, 1000)).collect::<Vec<(i64, i64)>>());
+    }
+
+    #[test]
+    fn repartition_test() {
+        // the budget only fits a few groups, so every partition has to be
+        // split up again
+        let (did_spill, results) = count_groups(1024, 500);
+        assert!(did_spill);
+        assert_eq!(results, (0..500).map(|i| (i, 20)).collect::<Vec<(i64, i64)>>());
+    }
+
+    #[test]
+    fn single_group_budget_test() {
+        // a group is always kept in memory, even if it exceeds the budget
+        let (_, results) = count_groups(1, 3);
+        assert_eq!(results, vec![(0, 3334), (1, 3333), (2, 3333)]);
+    }
+
+    #[test]
+    fn aggregate_growth_spill_test() {
+        // only a few groups, but each one collects enough distinct values
+        // to go over the budget
+        let aggs = json!([{"op": "count distinct", "col": 1}]);
+        let mut hash_agg = HashAggregator::new(aggs, vec![DataType::INTEGER],
+                                               vec![DataType::INTEGER, DataType::INTEGER],
+                                               64 * 1024);
+
+        for i in 0..20_000 {
+            hash_agg.consume(vec![Data::Integer(i % 4)],
+                             &[Data::Integer(i % 4), Data::Integer(i)]);
+        }
+        assert!(hash_agg.did_spill());
+
+        let mut results = Vec::new();
+        hash_agg.finish(|key, row| {
+            results.push((key[0].as_i64(), row[2].as_i64()));
+        });
+        results.sort();
+        assert_eq!(results, (0..4).map(|i| (i, 5000)).collect::<Vec<(i64, i64)>>());
+    }
+
+    #[test]
+    fn spilled_aggregate_state_test() {
+        // the first group is large enough for its aggregates to spill
+        // their values before the group itself is spilled
+        let aggs = json!([{"op": "count distinct", "col": 1},
+                          {"op": "median", "col": 1},
+                          {"op": "count", "col": 1}]);
+        let mut hash_agg = HashAggregator::new(aggs, vec![DataType::INTEGER],
+                                               vec![DataType::INTEGER, DataType::INTEGER],
+                                               4096);
+
+        for i in 0..100_000 {
+            hash_agg.consume(vec![Data::Integer(0)], &[Data::Integer(0), Data::Integer(i)]);
+        }
+
+        for i in 1..200 {
+            hash_agg.consume(vec![Data::Integer(i)], &[Data::Integer(i), Data::Integer(i)]);
+        }
+        assert!(hash_agg.did_spill());
+
+        let mut results = Vec::new();
+        hash_agg.finish(|key, row| {
+            results.push((key[0].as_i64(), row[2].clone(), row[3].clone(), row[4].clone()));
+        });
+        results.sort_by_key(|r| r.0);
+
+        assert_eq!(results.len(), 200);
+        assert_eq!(results[0], (0, Data::Integer(100_000), Data::Real(49_999.5),
+                                Data::Integer(100_000)));
+        for (i, r) in results.iter().enumerate().skip(1) {
+            assert_eq!(*r, (i as i64, Data::Integer(1), Data::Real(i as f64),
+                            Data::Integer(1)));
+        }
+    }
+}
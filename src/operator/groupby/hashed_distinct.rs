@@ -0,0 +1,179 @@
+// < begin copyright > 
+// Copyright Ryan Marcus 2018
+// 
+// This file is part of byoo.
+// 
+// byoo is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// byoo is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with byoo.  If not, see <http://www.gnu.org/licenses/>.
+// 
+// < end copyright > 
+use data::Data;
+use hash_partition_store::{ReadableHashPartitionStore, MAX_FILES};
+use spillable_store::WritableSpillableStore;
+use operator::groupby;
+use operator::groupby::hash_aggregator::{estimate_size, MAX_REPARTITION_DEPTH};
+use operator::ConstructableOperator;
+use operator_buffer::{OperatorReadBuffer, OperatorWriteBuffer};
+use serde_json;
+use std::collections::HashSet;
+use std::fs::File;
+
+pub struct HashedDistinct {
+    child: OperatorReadBuffer,
+    out: OperatorWriteBuffer,
+    cols: Vec<usize>,
+    memory_budget: usize
+}
+
+impl HashedDistinct {
+    fn new(child: OperatorReadBuffer, out: OperatorWriteBuffer,
+           cols: Vec<usize>, memory_budget: usize) -> HashedDistinct {
+        return HashedDistinct {
+            child, out, cols, memory_budget
+        };
+    }
+
+    pub fn start(mut self) {
+        HashedDistinct::distinct_rows(self.child, &mut self.out, &self.cols,
+                                      self.memory_budget, 0);
+    }
+
+    // Writes the first row of each distinct key as soon as it is seen,
+    // until the seen keys no longer fit in the memory budget. Rows with
+    // keys that have not been seen yet are then spilled, and since none of
+    // them can be duplicates of a written row, each hash partition of them
+    // can be made distinct on its own.
+    fn distinct_rows(mut input: OperatorReadBuffer, out: &mut OperatorWriteBuffer,
+                     cols: &[usize], memory_budget: usize, depth: u64) {
+        let mut seen: HashSet<Vec<Data>> = HashSet::new();
+        let mut seen_size: usize = 0;
+        let mut spilled: Option<WritableSpillableStore> = None;
+        let mut spilled_size: usize = 0;
+        let unbounded = depth >= MAX_REPARTITION_DEPTH;
+        let types = input.types().to_vec();
+
+        iterate_buffer!(input, row, {
+            let key = groupby::key_for_row(row, cols);
+            if !seen.contains(&key) {
+                let size = estimate_size(&key, &[], 0);
+                if unbounded || spilled.is_none() && seen_size + size <= memory_budget {
+                    seen_size += size;
+                    seen.insert(key);
+                    out.copy_and_write(row);
+                } else {
+                    spilled_size = spilled_size.saturating_add(size);
+                    spilled.get_or_insert_with(|| {
+                        return WritableSpillableStore::new(4096, types.clone());
+                    }).push_row(row);
+                }
+            }
+        });
+
+        let store = match spilled {
+            Some(store) => store,
+            None => return
+        };
+        drop(seen);
+
+        // each level of re-partitioning uses a different hash, so that a
+        // partition that is still too large gets split up
+        let num_partitions = (spilled_size / memory_budget + 1).clamp(2, MAX_FILES);
+        let mut rhps = ReadableHashPartitionStore::with_seeded_partitions(
+            num_partitions, 4096, store.into_read_buffer().1, cols, depth);
+
+        while let Some(buf) = rhps.next_buf() {
+            HashedDistinct::distinct_rows(buf, out, cols, memory_budget, depth + 1);
+        }
+    }
+}
+
+impl ConstructableOperator for HashedDistinct {
+    fn from_buffers(output: Option<OperatorWriteBuffer>,
+                    mut input: Vec<OperatorReadBuffer>,
+                    file: Option<File>,
+                    options: serde_json::Value) -> Self {
+        
+        assert!(file.is_none());
+        let o = output.unwrap();
+
+        assert_eq!(input.len(), 1);
+        let child = input.remove(0);
+
+        let cols = groupby::json_to_distinct_cols(&options, child.types().len())
+            .unwrap_or_else(|e| panic!("hashed distinct {}", e));
+
+        let memory_budget = groupby::json_to_memory_budget(&options)
+            .unwrap_or_else(|e| panic!("hashed distinct {}", e));
+        
+        return HashedDistinct::new(child, o, cols, memory_budget);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use operator::groupby::HashedDistinct;
+    use operator::groupby::hash_aggregator::DEFAULT_MEMORY_BUDGET;
+    use operator_buffer::{make_buffer_pair};
+    use data::{Data, DataType};
+    use std::thread;
+
+    fn distinct_results(cols: Vec<usize>, memory_budget: usize) -> Vec<Vec<Data>> {
+        let (r, mut w) = make_buffer_pair(5, 10, vec![DataType::INTEGER, DataType::INTEGER]);
+
+        let jh = thread::spawn(move || {
+            for i in 0..3000 {
+                w.write(vec![Data::Integer(i % 300), Data::Integer(i % 7)]);
+            }
+            w.flush();
+        });
+
+        let (r2, w2) = make_buffer_pair(5, 10, vec![DataType::INTEGER, DataType::INTEGER]);
+        let distinct = HashedDistinct::new(r, w2, cols, memory_budget);
+        let d_jh = thread::spawn(move || distinct.start());
+
+        let mut results = r2.into_vec();
+        jh.join().unwrap();
+        d_jh.join().unwrap();
+        results.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        return results;
+    }
+
+    #[test]
+    fn all_cols_test() {
+        let results = distinct_results(vec![0, 1], DEFAULT_MEMORY_BUDGET);
+
+        // 300 and 7 are coprime, so every pair within 2100 rows is distinct
+        assert_eq!(results.len(), 2100);
+        results.windows(2).for_each(|w| assert_ne!(w[0], w[1]));
+    }
+
+    #[test]
+    fn subset_test() {
+        let results = distinct_results(vec![0], DEFAULT_MEMORY_BUDGET);
+        assert_eq!(results.len(), 300);
+
+        // the first row of each key is kept
+        for (idx, row) in results.iter().enumerate() {
+            assert_eq!(row[0], Data::Integer(idx as i64));
+            assert_eq!(row[1], Data::Integer(idx as i64 % 7));
+        }
+    }
+
+    #[test]
+    fn spilled_test() {
+        assert_eq!(distinct_results(vec![0, 1], 1024),
+                   distinct_results(vec![0, 1], DEFAULT_MEMORY_BUDGET));
+        assert_eq!(distinct_results(vec![0], 1024),
+                   distinct_results(vec![0], DEFAULT_MEMORY_BUDGET));
+    }
+}
@@ -17,71 +17,120 @@
 // along with byoo.  If not, see <http://www.gnu.org/licenses/>.
 // 
 // < end copyright > 
-use agg::Aggregate;
-use data::Data;
-use hash_partition_store::ReadableHashPartitionStore;
+use agg;
+use data::DataType;
+use hash_partition_store::hash_partition;
 use operator::groupby;
+use operator::groupby::hash_aggregator::HashAggregator;
 use operator::ConstructableOperator;
-use operator_buffer::{OperatorReadBuffer, OperatorWriteBuffer};
+use operator_buffer::{make_buffer_pair, OperatorReadBuffer, OperatorWriteBuffer};
 use serde_json;
-use std::collections::HashMap;
 use std::fs::File;
+use std::thread;
+
+// the hash seed used to split rows between worker threads, chosen so that
+// it differs from the seeds used to partition spilled groups
+const WORKER_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
 
 pub struct HashedGroupBy {
     child: OperatorReadBuffer,
     out: OperatorWriteBuffer,
-    group_by_col_idx: usize,
+    group_by_cols: Vec<usize>,
     aggs: serde_json::Value,
+    memory_budget: usize,
+    parallelism: usize,
 }
 
-type RowAndAggs = (Vec<Data>, Vec<Box<Aggregate + Send>>);
 impl HashedGroupBy {
     fn new(
         child: OperatorReadBuffer,
         out: OperatorWriteBuffer,
-        group_by_col_idx: usize,
+        group_by_cols: Vec<usize>,
         aggs: serde_json::Value,
+        memory_budget: usize,
+        parallelism: usize,
     ) -> HashedGroupBy {
         return HashedGroupBy {
             child,
             out,
-            group_by_col_idx,
+            group_by_cols,
             aggs,
+            memory_budget,
+            parallelism,
         };
     }
 
     pub fn start(mut self) {
-        let mut rhps = ReadableHashPartitionStore::new(4096, self.child, &[self.group_by_col_idx]);
+        if self.parallelism > 1 {
+            self.start_parallel();
+            return;
+        }
 
-        let aggs_config = self.aggs.take();
+        let in_types = self.child.types().to_vec();
+        let key_types: Vec<DataType> = self.group_by_cols.iter()
+            .map(|&idx| in_types[idx].clone())
+            .collect();
 
-        loop {
-            let nxt_buf = rhps.next_buf();
-            if nxt_buf.is_none() {
-                return;
-            }
+        let mut hash_agg = HashAggregator::new(
+            self.aggs.take(), key_types, in_types, self.memory_budget);
+
+        iterate_buffer!(self.child, row, {
+            hash_agg.consume(groupby::key_for_row(row, &self.group_by_cols), row);
+        });
+
+        let out = &mut self.out;
+        hash_agg.finish(|_, row| out.write(row));
+    }
+
+    // Splits the input rows between worker threads by the hash of their
+    // keys, so that every group is aggregated by exactly one worker, and
+    // then writes out the groups of each worker in turn. The memory budget
+    // is shared evenly between the workers.
+    fn start_parallel(mut self) {
+        let in_types = self.child.types().to_vec();
+        let mut out_types = in_types.clone();
+        for agg_json in self.aggs.as_array().unwrap() {
+            out_types.push(agg::from_json(agg_json).out_type(&in_types));
+        }
+
+        let worker_budget = std::cmp::max(1, self.memory_budget / self.parallelism);
+        let mut inputs = Vec::with_capacity(self.parallelism);
+        let mut outputs = Vec::with_capacity(self.parallelism);
+        let mut handles = Vec::with_capacity(self.parallelism);
+
+        for _ in 0..self.parallelism {
+            let (in_r, in_w) = make_buffer_pair(5, 4096, in_types.clone());
+            let (out_r, out_w) = make_buffer_pair(5, 4096, out_types.clone());
+            let worker = HashedGroupBy::new(
+                in_r,
+                out_w,
+                self.group_by_cols.clone(),
+                self.aggs.clone(),
+                worker_budget,
+                1,
+            );
+
+            handles.push(thread::spawn(move || worker.start()));
+            inputs.push(in_w);
+            outputs.push(out_r);
+        }
 
-            // do the aggregation with a hashmap
-            let mut m: HashMap<Data, RowAndAggs> = HashMap::new();
+        iterate_buffer!(self.child, row, {
+            let worker = hash_partition(row, &self.group_by_cols, WORKER_SEED, self.parallelism);
+            inputs[worker].copy_and_write(row);
+        });
 
-            let mut buf = nxt_buf.unwrap();
-            iterate_buffer!(buf, row, {
-                let aggs_for_key =
-                    m.entry(row[self.group_by_col_idx].clone())
-                        .or_insert_with(|| {
-                            return (row.to_vec(), groupby::json_to_aggs(&aggs_config));
-                        });
+        // dropping the inputs flushes them and lets the workers finish
+        drop(inputs);
 
-                for agg in aggs_for_key.1.iter_mut() {
-                    agg.consume(row);
-                }
+        for mut output in outputs {
+            iterate_buffer!(output, row, {
+                self.out.copy_and_write(row);
             });
+        }
 
-            // dump all the results from the hashmap
-            for (_, (mut witness, mut aggs)) in m.into_iter() {
-                witness.extend(aggs.into_iter().map(|mut agg| agg.produce()));
-                self.out.write(witness);
-            }
+        for handle in handles {
+            handle.join().unwrap();
         }
     }
 }
@@ -99,19 +148,28 @@ impl ConstructableOperator for HashedGroupBy {
         assert_eq!(input.len(), 1);
         let child = input.remove(0);
 
-        assert!(
-            options["col"].is_i64(),
-            "sorted group by missing column index"
-        );
+        let group_by_cols = groupby::json_to_key_cols(&options)
+            .unwrap_or_else(|e| panic!("hashed group by {}", e));
 
         assert!(
             options["aggregates"].is_array(),
-            "sorted group by missing aggregates"
+            "hashed group by missing aggregates"
         );
 
-        let group_by_idx = options["col"].as_i64().unwrap() as usize;
+        let memory_budget = groupby::json_to_memory_budget(&options)
+            .unwrap_or_else(|e| panic!("hashed group by {}", e));
+
+        let parallelism = groupby::json_to_parallelism(&options)
+            .unwrap_or_else(|e| panic!("hashed group by {}", e));
 
-        return HashedGroupBy::new(child, o, group_by_idx, options["aggregates"].clone());
+        return HashedGroupBy::new(
+            child,
+            o,
+            group_by_cols,
+            options["aggregates"].clone(),
+            memory_budget,
+            parallelism,
+        );
     }
 }
 
@@ -119,7 +177,9 @@ impl ConstructableOperator for HashedGroupBy {
 mod tests {
     use data::{Data, DataType};
     use operator::groupby::HashedGroupBy;
+    use operator::groupby::hash_aggregator::DEFAULT_MEMORY_BUDGET;
     use operator_buffer::make_buffer_pair;
+    use std::thread;
 
     #[test]
     fn one_agg_test() {
@@ -138,7 +198,8 @@ mod tests {
         );
 
         let aggs = json!({ "aggregates": [{"op": "count", "col": 1}] });
-        let gb = HashedGroupBy::new(r, w2, 0, aggs["aggregates"].clone());
+        let gb = HashedGroupBy::new(r, w2, vec![0], aggs["aggregates"].clone(),
+                                    DEFAULT_MEMORY_BUDGET, 1);
         gb.start();
 
         let mut results = r2.into_vec();
@@ -177,7 +238,8 @@ mod tests {
 
         let aggs = json!({ "aggregates": [{"op": "count", "col": 1},
                                           {"op": "sum", "col": 1}] });
-        let gb = HashedGroupBy::new(r, w2, 0, aggs["aggregates"].clone());
+        let gb = HashedGroupBy::new(r, w2, vec![0], aggs["aggregates"].clone(),
+                                    DEFAULT_MEMORY_BUDGET, 1);
         gb.start();
 
         let mut results = r2.into_vec();
@@ -203,4 +265,140 @@ mod tests {
         );
     }
 
+    #[test]
+    fn spilled_partial_state_test() {
+        let (r, mut w) = make_buffer_pair(5, 10, vec![DataType::INTEGER, DataType::INTEGER]);
+
+        let jh = thread::spawn(move || {
+            for i in 0..1000 {
+                w.write(vec![Data::Integer(i % 100), Data::Integer(i)]);
+            }
+            w.flush();
+        });
+
+        let (r2, w2) = make_buffer_pair(
+            5,
+            10,
+            vec![
+                DataType::INTEGER,
+                DataType::INTEGER,
+                DataType::INTEGER,
+                DataType::INTEGER,
+                DataType::REAL,
+            ],
+        );
+
+        let aggs = json!({ "aggregates": [{"op": "count", "col": 1},
+                                          {"op": "sum", "col": 1},
+                                          {"op": "avg", "col": 1}] });
+        let gb = HashedGroupBy::new(r, w2, vec![0], aggs["aggregates"].clone(), 2048, 1);
+        let gb_jh = thread::spawn(move || gb.start());
+
+        let mut results = r2.into_vec();
+        jh.join().unwrap();
+        gb_jh.join().unwrap();
+        results.sort_by(|a, b| a[0].as_i64().cmp(&b[0].as_i64()));
+        assert_eq!(results.len(), 100);
+
+        for (key, row) in results.iter().enumerate() {
+            let key = key as i64;
+            let sum: i64 = (0..10).map(|i| key + 100 * i).sum();
+
+            // the witness is still the first row of the group
+            assert_eq!(row[0..2].to_vec(), vec![Data::Integer(key), Data::Integer(key)]);
+            assert_eq!(row[2], Data::Integer(10));
+            assert_eq!(row[3], Data::Integer(sum));
+            assert_eq!(row[4], Data::Real(sum as f64 / 10.0));
+        }
+    }
+
+    #[test]
+    fn multi_col_key_test() {
+        let (r, mut w) = make_buffer_pair(
+            5,
+            10,
+            vec![DataType::INTEGER, DataType::TEXT, DataType::INTEGER],
+        );
+
+        let jh = thread::spawn(move || {
+            for i in 0..300 {
+                let flag = if i % 2 == 0 { "A" } else { "R" };
+                w.write(vec![
+                    Data::Integer(i % 3),
+                    Data::Text(String::from(flag)),
+                    Data::Integer(i),
+                ]);
+            }
+            w.flush();
+        });
+
+        let (r2, w2) = make_buffer_pair(
+            5,
+            10,
+            vec![
+                DataType::INTEGER,
+                DataType::TEXT,
+                DataType::INTEGER,
+                DataType::INTEGER,
+            ],
+        );
+
+        let aggs = json!({ "aggregates": [{"op": "count", "col": 2}] });
+        let gb = HashedGroupBy::new(r, w2, vec![0, 1], aggs["aggregates"].clone(), 512, 1);
+        let gb_jh = thread::spawn(move || gb.start());
+
+        let mut results = r2.into_vec();
+        jh.join().unwrap();
+        gb_jh.join().unwrap();
+        results.sort_by(|a, b| {
+            a[0].as_i64().cmp(&b[0].as_i64())
+                .then_with(|| a[1].as_string().cmp(&b[1].as_string()))
+        });
+        assert_eq!(results.len(), 6);
+
+        for (idx, row) in results.iter().enumerate() {
+            assert_eq!(row[0], Data::Integer(idx as i64 / 2));
+            assert_eq!(row[1], Data::Text(String::from(if idx % 2 == 0 { "A" } else { "R" })));
+            assert_eq!(row[3], Data::Integer(50));
+        }
+    }
+
+    #[test]
+    fn parallel_test() {
+        let (r, mut w) = make_buffer_pair(5, 10, vec![DataType::INTEGER, DataType::INTEGER]);
+
+        let jh = thread::spawn(move || {
+            for i in 0..5000 {
+                w.write(vec![Data::Integer(i % 500), Data::Integer(i)]);
+            }
+            w.flush();
+        });
+
+        let (r2, w2) = make_buffer_pair(
+            5,
+            10,
+            vec![DataType::INTEGER, DataType::INTEGER, DataType::INTEGER, DataType::INTEGER],
+        );
+
+        let aggs = json!({ "aggregates": [{"op": "count", "col": 1},
+                                          {"op": "sum", "col": 1}] });
+
+        // a small budget, so that the workers spill as well
+        let gb = HashedGroupBy::new(r, w2, vec![0], aggs["aggregates"].clone(), 4096, 4);
+        let gb_jh = thread::spawn(move || gb.start());
+
+        let mut results = r2.into_vec();
+        jh.join().unwrap();
+        gb_jh.join().unwrap();
+        results.sort_by(|a, b| a[0].as_i64().cmp(&b[0].as_i64()));
+        assert_eq!(results.len(), 500);
+
+        for (key, row) in results.iter().enumerate() {
+            let key = key as i64;
+            let sum: i64 = (0..10).map(|i| key + 500 * i).sum();
+            assert_eq!(row[0], Data::Integer(key));
+            assert_eq!(row[2], Data::Integer(10));
+            assert_eq!(row[3], Data::Integer(sum));
+        }
+    }
 }
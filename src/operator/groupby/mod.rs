@@ -17,27 +17,148 @@
 // along with byoo.  If not, see <http://www.gnu.org/licenses/>.
 // 
 // < end copyright > 
-use agg;
-use agg::Aggregate;
+use agg::{Aggregate, AggregateSpec};
+use data::Data;
 
 mod sorted_groupby;
 pub use operator::groupby::sorted_groupby::SortedGroupBy;
 
+mod hash_aggregator;
+
 mod hashed_groupby;
 pub use operator::groupby::hashed_groupby::HashedGroupBy;
 
 mod all_rows_groupby;
 pub use operator::groupby::all_rows_groupby::AllRowsGroupBy;
 
+mod grouping_sets_groupby;
+pub use operator::groupby::grouping_sets_groupby::{GroupingSetsGroupBy, json_to_grouping_sets};
+
+mod hashed_distinct;
+pub use operator::groupby::hashed_distinct::HashedDistinct;
+
+mod sorted_distinct;
+pub use operator::groupby::sorted_distinct::SortedDistinct;
+
 fn json_to_aggs(json: &serde_json::Value) -> Vec<Box<Aggregate + Send>> {
-        let aggs: Vec<Box<Aggregate + Send>> = json
-            .as_array().unwrap().iter()
-            .map(|ref agg| {
-                let op = agg["op"].as_str().unwrap();
-                let col_idx = agg["col"].as_i64().unwrap() as usize;
-                
-                return agg::new(op, col_idx);
-            }).collect();
-
-    return aggs;
+    return new_aggs(&json_to_agg_specs(json));
+}
+
+// parses the aggregates of a group by once, so that the aggregates of
+// each new group can be created from them cheaply
+fn json_to_agg_specs(json: &serde_json::Value) -> Vec<AggregateSpec> {
+    return json.as_array().unwrap().iter()
+        .map(AggregateSpec::from_json)
+        .collect();
+}
+
+fn new_aggs(specs: &[AggregateSpec]) -> Vec<Box<dyn Aggregate + Send>> {
+    return specs.iter().map(|spec| spec.build()).collect();
+}
+
+// reads the group key columns of a group by, given either as a single
+// "col" or as a list of "cols"
+pub fn json_to_key_cols(options: &serde_json::Value) -> Result<Vec<usize>, String> {
+    if let Some(col) = options["col"].as_u64() {
+        return Ok(vec![col as usize]);
+    }
+
+    let cols = options["cols"].as_array()
+        .ok_or_else(|| String::from("missing group key column(s)"))?;
+
+    if cols.is_empty() {
+        return Err(String::from("group key columns cannot be empty"));
+    }
+
+    return cols.iter()
+        .map(|c| c.as_u64()
+             .map(|c| c as usize)
+             .ok_or_else(|| format!("invalid group key column {}", c)))
+        .collect();
+}
+
+// reads the columns rows are made distinct on, given as a single "col" or
+// as a list of "cols", defaulting to all of the columns
+pub fn json_to_distinct_cols(options: &serde_json::Value, num_cols: usize)
+                             -> Result<Vec<usize>, String> {
+    if options["col"].is_null() && options["cols"].is_null() {
+        return Ok((0..num_cols).collect());
+    }
+
+    return json_to_key_cols(options);
+}
+
+// reads the optional "memory_budget" of a hash-based group by, in bytes
+pub fn json_to_memory_budget(options: &serde_json::Value) -> Result<usize, String> {
+    if options["memory_budget"].is_null() {
+        return Ok(hash_aggregator::DEFAULT_MEMORY_BUDGET);
+    }
+
+    return match options["memory_budget"].as_u64() {
+        Some(budget) if budget > 0 => Ok(budget as usize),
+        _ => Err(format!("invalid memory budget {}, expected a positive number of bytes",
+                         options["memory_budget"]))
+    };
+}
+
+// reads the optional "parallelism" of a hashed group by, the number of
+// worker threads it aggregates with
+pub fn json_to_parallelism(options: &serde_json::Value) -> Result<usize, String> {
+    if options["parallelism"].is_null() {
+        return Ok(1);
+    }
+
+    return match options["parallelism"].as_u64() {
+        Some(n) if n > 0 => Ok(n as usize),
+        _ => Err(format!("invalid parallelism {}, expected a positive number of threads",
+                         options["parallelism"]))
+    };
+}
+
+fn key_for_row(row: &[Data], cols: &[usize]) -> Vec<Data> {
+    return cols.iter().map(|&idx| row[idx].clone()).collect();
+}
+
+fn same_key(r1: &[Data], r2: &[Data], cols: &[usize]) -> bool {
+    return cols.iter().all(|&idx| r1[idx] == r2[idx]);
+}
+
+#[cfg(test)]
+mod tests {
+    use operator::groupby::{json_to_key_cols, json_to_distinct_cols};
+    use operator::groupby::{json_to_memory_budget, json_to_parallelism};
+    use operator::groupby::hash_aggregator;
+
+    #[test]
+    fn key_cols_test() {
+        assert_eq!(json_to_key_cols(&json!({"col": 2})), Ok(vec![2]));
+        assert_eq!(json_to_key_cols(&json!({"cols": [3, 0]})), Ok(vec![3, 0]));
+        assert!(json_to_key_cols(&json!({"cols": []})).is_err());
+        assert!(json_to_key_cols(&json!({"cols": ["a"]})).is_err());
+        assert!(json_to_key_cols(&json!({})).is_err());
+    }
+
+    #[test]
+    fn distinct_cols_test() {
+        assert_eq!(json_to_distinct_cols(&json!({}), 3), Ok(vec![0, 1, 2]));
+        assert_eq!(json_to_distinct_cols(&json!({"cols": [2]}), 3), Ok(vec![2]));
+        assert!(json_to_distinct_cols(&json!({"cols": []}), 3).is_err());
+    }
+
+    #[test]
+    fn memory_budget_test() {
+        assert_eq!(json_to_memory_budget(&json!({})),
+                   Ok(hash_aggregator::DEFAULT_MEMORY_BUDGET));
+        assert_eq!(json_to_memory_budget(&json!({"memory_budget": 1024})), Ok(1024));
+        assert!(json_to_memory_budget(&json!({"memory_budget": 0})).is_err());
+        assert!(json_to_memory_budget(&json!({"memory_budget": "1MB"})).is_err());
+    }
+
+    #[test]
+    fn parallelism_test() {
+        assert_eq!(json_to_parallelism(&json!({})), Ok(1));
+        assert_eq!(json_to_parallelism(&json!({"parallelism": 8})), Ok(8));
+        assert!(json_to_parallelism(&json!({"parallelism": 0})).is_err());
+        assert!(json_to_parallelism(&json!({"parallelism": -2})).is_err());
+    }
 }
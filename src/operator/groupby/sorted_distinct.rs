@@ -0,0 +1,126 @@
+// < begin copyright > 
+// Copyright Ryan Marcus 2018
+// 
+// This file is part of byoo.
+// 
+// byoo is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// byoo is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with byoo.  If not, see <http://www.gnu.org/licenses/>.
+// 
+// < end copyright > 
+use operator_buffer::{OperatorReadBuffer, OperatorWriteBuffer};
+use operator::ConstructableOperator;
+use operator::groupby;
+use data::{Data};
+use serde_json;
+use std::fs::File;
+
+pub struct SortedDistinct {
+    child: OperatorReadBuffer,
+    out: OperatorWriteBuffer,
+    cols: Vec<usize>
+}
+
+
+impl SortedDistinct {
+    fn new(child: OperatorReadBuffer, out: OperatorWriteBuffer,
+           cols: Vec<usize>) -> SortedDistinct {
+        return SortedDistinct {
+            child, out, cols
+        };
+    }
+    
+    pub fn start(mut self) {
+        // the input is sorted on the distinct columns, so a row is a
+        // duplicate exactly when its key matches the previous row's
+        let mut last: Option<Vec<Data>> = None;
+        iterate_buffer!(self.child, row, {
+            let is_new = match last {
+                None => true,
+                Some(ref last_row) => !groupby::same_key(last_row, row, &self.cols)
+            };
+
+            if is_new {
+                self.out.copy_and_write(row);
+                last = Some(row.to_vec());
+            }
+        });
+    }
+}
+
+impl ConstructableOperator for SortedDistinct {
+    fn from_buffers(output: Option<OperatorWriteBuffer>,
+                    mut input: Vec<OperatorReadBuffer>,
+                    file: Option<File>,
+                    options: serde_json::Value) -> Self {
+        
+        assert!(file.is_none());
+        let o = output.unwrap();
+
+        assert_eq!(input.len(), 1);
+        let child = input.remove(0);
+
+        let cols = groupby::json_to_distinct_cols(&options, child.types().len())
+            .unwrap_or_else(|e| panic!("sorted distinct {}", e));
+        
+        return SortedDistinct::new(child, o, cols);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use operator::groupby::SortedDistinct;
+    use operator_buffer::{make_buffer_pair};
+    use data::{Data, DataType};
+
+    fn sorted_input() -> Vec<Vec<Data>> {
+        return vec![
+            vec![Data::Integer(1), Data::Text(String::from("a"))],
+            vec![Data::Integer(1), Data::Text(String::from("a"))],
+            vec![Data::Integer(1), Data::Text(String::from("b"))],
+            vec![Data::Integer(2), Data::Text(String::from("b"))],
+            vec![Data::Integer(2), Data::Text(String::from("b"))],
+            vec![Data::Null, Data::Text(String::from("c"))],
+            vec![Data::Null, Data::Text(String::from("c"))]
+        ];
+    }
+
+    fn distinct_results(cols: Vec<usize>) -> Vec<Vec<Data>> {
+        let (r, mut w) = make_buffer_pair(5, 10, vec![DataType::INTEGER, DataType::TEXT]);
+        for row in sorted_input() {
+            w.write(row);
+        }
+        w.flush();
+        drop(w);
+
+        let (r2, w2) = make_buffer_pair(5, 10, vec![DataType::INTEGER, DataType::TEXT]);
+        let distinct = SortedDistinct::new(r, w2, cols);
+        distinct.start();
+
+        return r2.into_vec();
+    }
+
+    #[test]
+    fn all_cols_test() {
+        let input = sorted_input();
+        assert_eq!(distinct_results(vec![0, 1]),
+                   vec![input[0].clone(), input[2].clone(),
+                        input[3].clone(), input[5].clone()]);
+    }
+
+    #[test]
+    fn subset_test() {
+        let input = sorted_input();
+        assert_eq!(distinct_results(vec![0]),
+                   vec![input[0].clone(), input[3].clone(), input[5].clone()]);
+    }
+}
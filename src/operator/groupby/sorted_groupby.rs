@@ -28,18 +28,18 @@ use agg::Aggregate;
 pub struct SortedGroupBy {
     child: OperatorReadBuffer,
     out: OperatorWriteBuffer,
-    group_by_col_idx: usize,
+    group_by_cols: Vec<usize>,
     aggs: Vec<Box<Aggregate + Send>>
 }
 
 
 impl SortedGroupBy {
     fn new(child: OperatorReadBuffer, out: OperatorWriteBuffer,
-               group_by_col_idx: usize, aggs: Vec<Box<Aggregate + Send>>)
+               group_by_cols: Vec<usize>, aggs: Vec<Box<Aggregate + Send>>)
                -> SortedGroupBy {
         return SortedGroupBy {
             child, out,  
-            group_by_col_idx, aggs
+            group_by_cols, aggs
         };
     }
     
@@ -47,11 +47,10 @@ impl SortedGroupBy {
         let mut last: Option<Vec<Data>> = None;
         iterate_buffer!(self.child, row, {
             //println!("{:?}", row);
-            let curr = &row[self.group_by_col_idx];
             last = match last.take() {
                 None => Some(row.to_vec()),
                 Some(mut last_row) => {
-                    if &last_row[self.group_by_col_idx] != curr {
+                    if !groupby::same_key(&last_row, row, &self.group_by_cols) {
                         // we have a new value! we need to emit a result.
                         for agg in self.aggs.iter_mut() {
                             last_row.push(agg.produce());
@@ -93,19 +92,16 @@ impl ConstructableOperator for SortedGroupBy {
         assert_eq!(input.len(), 1);
         let child = input.remove(0);
 
-        assert!(options["col"].is_i64(),
-                "sorted group by missing column index");
+        let group_by_cols = groupby::json_to_key_cols(&options)
+            .unwrap_or_else(|e| panic!("sorted group by {}", e));
 
         assert!(options["aggregates"].is_array(),
                 "sorted group by missing aggregates");
 
-        let group_by_idx = options["col"].as_i64().unwrap()
-            as usize;
-
         let aggs = groupby::json_to_aggs(&options["aggregates"]);
         
         return SortedGroupBy::new(child, o,
-                                  group_by_idx, aggs);
+                                  group_by_cols, aggs);
     }
 }
 
@@ -129,7 +125,7 @@ mod tests {
         let (r2, w2) = make_buffer_pair(5, 10, vec![DataType::INTEGER, DataType::INTEGER,
                                                         DataType::INTEGER]);
         
-        let gb = SortedGroupBy::new(r, w2, 0, vec![agg::new("count", 1)]);
+        let gb = SortedGroupBy::new(r, w2, vec![0], vec![agg::new("count", 1)]);
         gb.start();
 
         let results = r2.into_vec();
@@ -151,7 +147,7 @@ mod tests {
         let (r2, w2) = make_buffer_pair(5, 10, vec![DataType::INTEGER, DataType::INTEGER,
                                                         DataType::INTEGER, DataType::INTEGER]);
         
-        let gb = SortedGroupBy::new(r, w2, 0, vec![agg::new("count", 1),
+        let gb = SortedGroupBy::new(r, w2, vec![0], vec![agg::new("count", 1),
                                                    agg::new("sum", 1)]);
         gb.start();
 
@@ -162,6 +158,30 @@ mod tests {
         assert_eq!(results[1], vec![Data::Integer(2), Data::Integer(-15),
                                     Data::Integer(1), Data::Integer(-15)]);
     }
-    
-}
 
+    #[test]
+    fn multi_col_key_test() {
+        let (r, mut w) = make_buffer_pair(5, 10, vec![DataType::INTEGER, DataType::TEXT,
+                                                      DataType::INTEGER]);
+
+        w.write(vec![Data::Integer(1), Data::Text(String::from("a")), Data::Integer(1)]);
+        w.write(vec![Data::Integer(1), Data::Text(String::from("a")), Data::Integer(2)]);
+        w.write(vec![Data::Integer(1), Data::Text(String::from("b")), Data::Integer(3)]);
+        w.write(vec![Data::Integer(2), Data::Text(String::from("b")), Data::Integer(4)]);
+        w.flush();
+        drop(w);
+
+        let (r2, w2) = make_buffer_pair(5, 10, vec![DataType::INTEGER, DataType::TEXT,
+                                                    DataType::INTEGER, DataType::INTEGER]);
+        
+        let gb = SortedGroupBy::new(r, w2, vec![0, 1], vec![agg::new("sum", 2)]);
+        gb.start();
+
+        let results = r2.into_vec();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0][3], Data::Integer(3));
+        assert_eq!(results[1][3], Data::Integer(3));
+        assert_eq!(results[2][3], Data::Integer(4));
+        assert_eq!(results[1][1], Data::Text(String::from("b")));
+    }
+}
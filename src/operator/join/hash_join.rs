@@ -158,6 +158,11 @@ impl HashJoin {
 
         // first, load the left side into a hash table.
         iterate_buffer!(left, row, {
+            // NULL keys never match anything
+            if self.left_cols.iter().any(|&idx| row[idx].is_null()) {
+                continue;
+            }
+            
             let key = HashJoinKey::new_by_val(&self.left_cols, row);
             ht.entry(key)
                 .or_insert_with(Vec::new)
@@ -166,6 +171,10 @@ impl HashJoin {
 
         let mut out_row = Vec::new();
         iterate_buffer!(right, row, {
+            if self.right_cols.iter().any(|&idx| row[idx].is_null()) {
+                continue;
+            }
+            
             let key2 = HashJoinKey::new_by_ref(&self.right_cols, row);
             if let Some(matches) = ht.get(&key2) {
                 // all these rows match.
@@ -257,5 +266,26 @@ mod tests {
 
         assert_eq!(rc, 3);
     }
-}
 
+    #[test]
+    fn null_keys_do_not_match() {
+        let (r1, mut w1) = make_buffer_pair(5, 10, vec![DataType::INTEGER]);
+        let (r2, mut w2) = make_buffer_pair(5, 10, vec![DataType::INTEGER]);
+        let (r, w) = make_buffer_pair(5, 10, vec![DataType::INTEGER,
+                                                  DataType::INTEGER]);
+
+        w1.write(vec![Data::Integer(5)]);
+        w1.write(vec![Data::Null]);
+        drop(w1);
+        
+        w2.write(vec![Data::Null]);
+        w2.write(vec![Data::Integer(5)]);
+        drop(w2);
+
+        let j = HashJoin::new(r1, r2, w, vec![0], vec![0]);
+        j.start();
+
+        let results = r.into_vec();
+        assert_eq!(results, vec![vec![Data::Integer(5), Data::Integer(5)]]);
+    }
+}
@@ -110,7 +110,7 @@ impl ConstructableOperator for LoopJoin {
         let lb = input.remove(0);
         let rb = input.remove(0);
 
-        let pred = Predicate::from_json(&options["predicate"]);
+        let pred = Predicate::from_options(&options);
 
         return LoopJoin::new_with_interp(lb, rb, o, pred);
     }
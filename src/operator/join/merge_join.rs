@@ -48,6 +48,10 @@ fn matches_on_cols(r1: &[Data], r2: &[Data], cols: &[usize]) -> bool {
     return true;
 }
 
+fn has_null_on_cols(r: &[Data], cols: &[usize]) -> bool {
+    return cols.iter().any(|&col| r[col].is_null());
+}
+
 fn cmp_on_col_sets(r1: &[Data], r2: &[Data], cols1: &[usize], cols2: &[usize]) -> Ordering {
     debug_assert!(cols1.len() == cols2.len());
     debug_assert!(r1.len() >= cols1.len());
@@ -126,6 +130,19 @@ impl MergeJoin {
             // check to see if the left and right match
             match cmp_on_col_sets(&left_set[0], &right_set[0],
                                       &self.left_cols, &self.right_cols) {
+                Ordering::Equal if has_null_on_cols(&left_set[0], &self.left_cols) => {
+                    // NULL keys never match anything, so skip both sets
+                    left_set = match MergeJoin::read_matching(&mut pleft, &self.left_cols) {
+                        Some(v) => v,
+                        None => { return; }
+                    };
+                    
+                    right_set = match MergeJoin::read_matching(&mut pright, &self.right_cols) {
+                        Some(v) => v,
+                        None => { return; }
+                    };
+                },
+                
                 Ordering::Equal => {
                     // emit the product
                     for l in left_set.iter() {
@@ -21,6 +21,7 @@ pub mod scan;
 
 mod column_union;
 mod sort;
+mod compute;
 
 pub mod output;
 pub mod join;
@@ -28,6 +29,7 @@ pub mod groupby;
 
 pub use operator::column_union::ColumnUnion;
 pub use operator::sort::Sort;
+pub use operator::compute::{Compute, compute_out_types, check_compute_types};
 
 use operator_buffer::{OperatorReadBuffer, OperatorWriteBuffer};
 use std::fs::File;
@@ -28,6 +28,10 @@ use data::{WriteByooDataExt, ReadByooDataExt};
 use tempfile::tempfile;
 use std::io;
 
+// the format code at the start of a columnar file. Format 1 files were
+// written before values were prefixed with a NULL marker, and cannot be
+// read with the current layout.
+pub const COLUMNAR_FORMAT: u8 = 2;
 
 pub struct ColumnarOutput<T> {
     input: OperatorReadBuffer,
@@ -69,7 +73,7 @@ impl <T: Write + Seek> ColumnarOutput<T> {
             all_readers.push(reader);
         }
         
-        self.output.write_u8(1).unwrap(); // columnar format
+        self.output.write_u8(COLUMNAR_FORMAT).unwrap();
         
         // num columns
         self.output.write_u16::<LittleEndian>(all_stats.len() as u16).unwrap();
@@ -167,7 +171,7 @@ mod tests {
 
         cursor.seek(SeekFrom::Start(0)).unwrap();
         
-        assert_eq!(cursor.read_u8().unwrap(), 1); // tag
+        assert_eq!(cursor.read_u8().unwrap(), 2); // tag
         assert_eq!(cursor.read_u16::<LittleEndian>().unwrap(), 1); // cols
         assert_eq!(cursor.read_u64::<LittleEndian>().unwrap(), 3); // rows
         assert_eq!(cursor.read_u16::<LittleEndian>().unwrap(),
@@ -196,7 +200,7 @@ mod tests {
 
         cursor.seek(SeekFrom::Start(0)).unwrap();
         
-        assert_eq!(cursor.read_u8().unwrap(), 1); // tag
+        assert_eq!(cursor.read_u8().unwrap(), 2); // tag
         assert_eq!(cursor.read_u16::<LittleEndian>().unwrap(), 2); // cols
         assert_eq!(cursor.read_u64::<LittleEndian>().unwrap(), 4); // rows
         assert_eq!(cursor.read_u16::<LittleEndian>().unwrap(),
@@ -20,5 +20,5 @@
 mod columnar_output;
 mod csv_output;
 
-pub use operator::output::columnar_output::ColumnarOutput;
+pub use operator::output::columnar_output::{ColumnarOutput, COLUMNAR_FORMAT};
 pub use operator::output::csv_output::CsvOutput;
@@ -19,6 +19,7 @@
 // < end copyright > 
 use operator_buffer::{OperatorWriteBuffer, OperatorReadBuffer};
 use operator::ConstructableOperator;
+use operator::output::COLUMNAR_FORMAT;
 use std::fs::File;
 use std::io::{Seek, SeekFrom, BufRead, BufReader};
 use byteorder::{ReadBytesExt, LittleEndian};
@@ -44,7 +45,11 @@ impl <T: BufRead + Seek> ColumnarScan<T> {
         // read the format code
         let format_code = self.reader.read_u8().unwrap();
 
-        assert_eq!(format_code, 1); // column order
+        if format_code != COLUMNAR_FORMAT {
+            panic!("Columnar file has format {}, but only format {} can be read. \
+                    Files written before NULL support (format 1) must be rewritten.",
+                   format_code, COLUMNAR_FORMAT);
+        }
 
         // read the number of columns
         let num_columns = self.reader.read_u16::<LittleEndian>().unwrap() as usize;
@@ -93,3 +98,40 @@ impl ConstructableOperator for ColumnarScan<BufReader<File>> {
         return ColumnarScan::new(BufReader::new(f), col_idx, out);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use operator::scan::ColumnarScan;
+    use operator::output::ColumnarOutput;
+    use operator_buffer::make_buffer_pair;
+    use data::{Data, DataType};
+    use std::io::{Cursor, Seek, SeekFrom};
+
+    #[test]
+    fn reads_nulls() {
+        let (r, mut w) = make_buffer_pair(5, 10, vec![DataType::INTEGER]);
+        w.write(vec![Data::Integer(5)]);
+        w.write(vec![Data::Null]);
+        drop(w);
+
+        let mut cursor = Cursor::new(Vec::new());
+        ColumnarOutput::new(1024, r, &mut cursor).start();
+        cursor.seek(SeekFrom::Start(0)).unwrap();
+
+        let (mut r, w) = make_buffer_pair(5, 10, vec![DataType::INTEGER]);
+        ColumnarScan::new(cursor, 0, w).start();
+
+        let mut results = Vec::new();
+        iterate_buffer!(r, row, {
+            results.push(row[0].clone());
+        });
+        assert_eq!(results, vec![Data::Integer(5), Data::Null]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Columnar file has format 1")]
+    fn rejects_old_format() {
+        let (_r, w) = make_buffer_pair(5, 10, vec![DataType::INTEGER]);
+        ColumnarScan::new(Cursor::new(vec![1, 1, 0]), 0, w).start();
+    }
+}
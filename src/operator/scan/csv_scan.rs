@@ -41,11 +41,14 @@ impl <T: Read> CsvScan<T> {
         let mut rdr = Reader::from_reader(self.reader);
         for result in rdr.records() {
             let record = result.unwrap();
+            let line = record.position().map(|p| p.line()).unwrap_or(0);
             let row: Vec<String> = record.iter()
                 .map(String::from)
                 .collect();
 
-            self.output.write_strings(row);
+            if let Err(e) = self.output.write_strings(row) {
+                panic!("Invalid CSV field on line {}: {}", line, e);
+            }
         }
 
         self.output.flush();
@@ -159,4 +162,37 @@ mod tests {
 
         assert_eq!(num_rows, 3);
     }
+
+    #[test]
+    fn reads_nulls() {
+        let csv_data = "header1,header2,header3
+1,,3.5
+,hello,
+".as_bytes();
+
+        let (r, w) = make_buffer_pair(5, 10, vec![
+            DataType::INTEGER, DataType::TEXT, DataType::REAL]);
+        
+        let scan = CsvScan::new(csv_data, w);
+        scan.start();
+
+        let rows = r.into_vec();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0], vec![Data::Integer(1), Data::Null, Data::Real(3.5)]);
+        assert_eq!(rows[1], vec![Data::Null, Data::Text(String::from("hello")),
+                                 Data::Null]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid CSV field on line 3: cannot parse \"oops\" in column 2 as REAL")]
+    fn rejects_unparseable() {
+        let csv_data = "header1,header2,header3
+1,,3.5
+,hello,oops
+".as_bytes();
+
+        let (_r, w) = make_buffer_pair(5, 10, vec![
+            DataType::INTEGER, DataType::TEXT, DataType::REAL]);
+        CsvScan::new(csv_data, w).start();
+    }
 }
@@ -36,8 +36,8 @@ pub struct Sort {
 }
 
 impl Sort {
-    fn new(input: OperatorReadBuffer, output: OperatorWriteBuffer,
-           by_cols: Vec<usize>, buf_size: usize) -> Sort {
+    pub fn new(input: OperatorReadBuffer, output: OperatorWriteBuffer,
+               by_cols: Vec<usize>, buf_size: usize) -> Sort {
         return Sort {
             input: Some(input),
             output, by_cols, buf_size,
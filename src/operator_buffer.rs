@@ -356,25 +356,33 @@ impl OperatorWriteBuffer {
         drop(rows);
     }
 
-    pub fn write_strings(&mut self, mut row: Vec<String>) {
+    /// Parses and writes a row of strings. Empty fields become NULL, and
+    /// a field that cannot be parsed as its column's type is an error.
+    pub fn write_strings(&mut self, mut row: Vec<String>) -> Result<(), String> {
         if let Some(ref cols) = self.projection {
             row = cols.iter().map(|&col_idx| row[col_idx].clone()).collect()
         }
         
-        let data: Vec<Data> = row.into_iter().enumerate().map(|(idx, field)| {
+        let data = row.into_iter().enumerate().map(|(idx, field)| {
+            if field.is_empty() {
+                return Ok(Data::Null);
+            }
+
             let dt = &self.types[idx];
-            return dt.from_string(field)
-                .unwrap_or_else(|| dt.default_value());
-        }).collect();
+            return dt.from_string(field.clone())
+                .ok_or_else(|| format!("cannot parse {:?} in column {} as {:?}",
+                                       field, idx, dt));
+        }).collect::<Result<Vec<Data>, String>>()?;
 
         if !self.filters.iter().all(|p| p.eval(&data)) {
             // don't write the row.
-            return;
+            return Ok(());
         }
 
         self.prepare_for_write();
         self.buffers.front_mut().unwrap()
             .write_values(data);
+        return Ok(());
     }
 
     pub fn flush(&mut self) {
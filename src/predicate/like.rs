@@ -0,0 +1,222 @@
+// < begin copyright > 
+// Copyright Ryan Marcus 2018
+// 
+// This file is part of byoo.
+// 
+// byoo is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// byoo is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with byoo.  If not, see <http://www.gnu.org/licenses/>.
+// 
+// < end copyright > 
+// SQL LIKE patterns. `%` matches any sequence of characters, `_` matches
+// any single character, and a backslash escapes the next character.
+// Patterns are compiled once, and the common shapes ('abc', 'abc%',
+// '%abc' and '%abc%') are matched with plain string operations.
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Token {
+    Char(char),
+    One,
+    Many
+}
+
+#[derive(Debug, PartialEq)]
+enum Kind {
+    Exact(String),
+    Prefix(String),
+    Suffix(String),
+    Contains(String),
+    General(Vec<Token>)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LikeMatcher {
+    kind: Kind,
+    case_insensitive: bool
+}
+
+fn tokenize(pattern: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars();
+
+    while let Some(c) = chars.next() {
+        let token = match c {
+            '%' => Token::Many,
+            '_' => Token::One,
+            '\\' => Token::Char(chars.next().unwrap_or('\\')),
+            _ => Token::Char(c)
+        };
+
+        // consecutive %s are the same as a single one
+        if token == Token::Many && tokens.last() == Some(&Token::Many) {
+            continue;
+        }
+        
+        tokens.push(token);
+    }
+
+    return tokens;
+}
+
+// returns the literal string if all the tokens are plain characters
+fn literal(tokens: &[Token]) -> Option<String> {
+    return tokens.iter()
+        .map(|t| match t {
+            Token::Char(c) => Some(*c),
+            _ => None
+        })
+        .collect();
+}
+
+impl LikeMatcher {
+    pub fn new(pattern: &str, case_insensitive: bool) -> LikeMatcher {
+        let pattern = if case_insensitive {
+            pattern.to_lowercase()
+        } else {
+            String::from(pattern)
+        };
+        
+        let tokens = tokenize(&pattern);
+        let n = tokens.len();
+        let starts_many = tokens.first() == Some(&Token::Many);
+        let ends_many = n > 0 && tokens[n - 1] == Token::Many;
+
+        let kind = if let Some(s) = literal(&tokens) {
+            Kind::Exact(s)
+        } else if n == 1 && starts_many {
+            Kind::Prefix(String::new())
+        } else if let (true, true, Some(s)) = (starts_many, ends_many,
+                                                literal(&tokens[1..n.max(2) - 1])) {
+            Kind::Contains(s)
+        } else if let (true, Some(s)) = (ends_many, literal(&tokens[..n - 1])) {
+            Kind::Prefix(s)
+        } else if let (true, Some(s)) = (starts_many, literal(&tokens[1..])) {
+            Kind::Suffix(s)
+        } else {
+            Kind::General(tokens)
+        };
+
+        return LikeMatcher { kind, case_insensitive };
+    }
+
+    pub fn matches(&self, s: &str) -> bool {
+        if self.case_insensitive {
+            return self.matches_exact_case(&s.to_lowercase());
+        }
+
+        return self.matches_exact_case(s);
+    }
+
+    fn matches_exact_case(&self, s: &str) -> bool {
+        return match &self.kind {
+            Kind::Exact(p) => s == p,
+            Kind::Prefix(p) => s.starts_with(p.as_str()),
+            Kind::Suffix(p) => s.ends_with(p.as_str()),
+            Kind::Contains(p) => s.contains(p.as_str()),
+            Kind::General(tokens) => general_match(tokens, s)
+        };
+    }
+}
+
+// Wildcard matching with backtracking to the most recent %, which is
+// linear for most patterns and O(nm) in the worst case.
+fn general_match(pattern: &[Token], s: &str) -> bool {
+    let text: Vec<char> = s.chars().collect();
+    let mut p = 0;
+    let mut t = 0;
+    let mut last_many: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        let token = pattern.get(p);
+        match token {
+            Some(Token::Char(c)) if *c == text[t] => {
+                p += 1;
+                t += 1;
+                continue;
+            },
+            Some(Token::One) => {
+                p += 1;
+                t += 1;
+                continue;
+            },
+            Some(Token::Many) => {
+                last_many = Some((p, t));
+                p += 1;
+                continue;
+            },
+            _ => {}
+        };
+
+        // mismatch, so let the last % absorb one more character
+        match last_many {
+            Some((mp, mt)) => {
+                p = mp + 1;
+                t = mt + 1;
+                last_many = Some((mp, mt + 1));
+            },
+            None => { return false; }
+        };
+    }
+
+    return pattern[p..].iter().all(|t| *t == Token::Many);
+}
+
+#[cfg(test)]
+mod tests {
+    use predicate::like::{LikeMatcher, Kind};
+
+    #[test]
+    fn pattern_kinds() {
+        assert_matches!(LikeMatcher::new("abc", false).kind, Kind::Exact(_));
+        assert_matches!(LikeMatcher::new("abc%", false).kind, Kind::Prefix(_));
+        assert_matches!(LikeMatcher::new("%abc", false).kind, Kind::Suffix(_));
+        assert_matches!(LikeMatcher::new("%abc%%", false).kind, Kind::Contains(_));
+        assert_matches!(LikeMatcher::new("%", false).kind, Kind::Prefix(_));
+        assert_matches!(LikeMatcher::new("%%", false).kind, Kind::Prefix(_));
+        assert_matches!(LikeMatcher::new("a%c", false).kind, Kind::General(_));
+        assert_matches!(LikeMatcher::new("50\\%", false).kind, Kind::Exact(_));
+    }
+
+    #[test]
+    fn matching() {
+        let m = LikeMatcher::new("%Star%", false);
+        assert!(m.matches("Star Wars"));
+        assert!(m.matches("A Star is Born"));
+        assert!(!m.matches("star trek"));
+
+        let m = LikeMatcher::new("%Star%", true);
+        assert!(m.matches("star trek"));
+
+        let m = LikeMatcher::new("%", false);
+        assert!(m.matches(""));
+        assert!(m.matches("anything"));
+
+        let m = LikeMatcher::new("a_c%e", false);
+        assert!(m.matches("abcde"));
+        assert!(m.matches("abce"));
+        assert!(m.matches("abcxexe"));
+        assert!(!m.matches("abcd"));
+        assert!(!m.matches("ac"));
+
+        let m = LikeMatcher::new("%a%b%", false);
+        assert!(m.matches("xxaxxbxx"));
+        assert!(!m.matches("xxbxxaxx"));
+
+        let m = LikeMatcher::new("__", false);
+        assert!(m.matches("ü!"));
+        assert!(!m.matches("abc"));
+
+        let m = LikeMatcher::new("100\\%", false);
+        assert!(m.matches("100%"));
+        assert!(!m.matches("1000"));
+    }
+}
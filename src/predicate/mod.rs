@@ -19,57 +19,183 @@
 // < end copyright > 
 #![allow(clippy::float_cmp)]
 
+mod like;
+mod parser;
+mod normalize;
+mod type_check;
+
+pub use self::type_check::same_kind;
+
 use serde_json;
 use std::boxed::Box;
-use data::{Data};
-use either::*;
+use data::{Data, DataType};
+use decimal;
+use std::cmp::Ordering;
+use std::collections::{HashSet, HashMap};
+use predicate::like::LikeMatcher;
+use regex::{Regex, RegexBuilder};
 
 
+#[derive(Debug)]
 pub enum Predicate {
-    And(Box<Predicate>, Box<Predicate>),
-    Or(Box<Predicate>, Box<Predicate>),
+    And(Vec<Predicate>),
+    Or(Vec<Predicate>),
     Not(Box<Predicate>),
-    Lt(usize, Either<i64, f64>),
-    Gt(usize, Either<i64, f64>),
-    Eq(usize, Either<i64, f64>),
+    Const(bool),
+    Lt(usize, Data),
+    Lte(usize, Data),
+    Gt(usize, Data),
+    Gte(usize, Data),
+    Eq(usize, Data),
+    Neq(usize, Data),
     LtCol(usize, usize),
+    LteCol(usize, usize),
     GtCol(usize, usize),
+    GteCol(usize, usize),
     EqCol(usize, usize),
-    Contains(usize, String)
+    NeqCol(usize, usize),
+    Between(usize, Data, Data),
+    In(usize, InList),
+    Contains(usize, String),
+    Like(usize, LikeMatcher),
+    Regex(usize, Regex),
+    IsNull(usize)
 }
 
-fn either_from_json(v: &serde_json::Value) -> Either<i64, f64> {
-    if v.is_f64() {
-        return Right(v.as_f64().unwrap());
-    } else if v.is_i64() {
-        return Left(v.as_i64().unwrap());
+// Structural equality, used to find duplicate predicates. Regexes are
+// equal if they have the same pattern.
+impl PartialEq for Predicate {
+    fn eq(&self, other: &Predicate) -> bool {
+        return match (self, other) {
+            (Predicate::And(a), Predicate::And(b)) => a == b,
+            (Predicate::Or(a), Predicate::Or(b)) => a == b,
+            (Predicate::Not(a), Predicate::Not(b)) => a == b,
+            (Predicate::Const(a), Predicate::Const(b)) => a == b,
+            (Predicate::Lt(c1, v1), Predicate::Lt(c2, v2))
+                | (Predicate::Lte(c1, v1), Predicate::Lte(c2, v2))
+                | (Predicate::Gt(c1, v1), Predicate::Gt(c2, v2))
+                | (Predicate::Gte(c1, v1), Predicate::Gte(c2, v2))
+                | (Predicate::Eq(c1, v1), Predicate::Eq(c2, v2))
+                | (Predicate::Neq(c1, v1), Predicate::Neq(c2, v2)) => c1 == c2 && v1 == v2,
+            (Predicate::LtCol(a1, b1), Predicate::LtCol(a2, b2))
+                | (Predicate::LteCol(a1, b1), Predicate::LteCol(a2, b2))
+                | (Predicate::GtCol(a1, b1), Predicate::GtCol(a2, b2))
+                | (Predicate::GteCol(a1, b1), Predicate::GteCol(a2, b2))
+                | (Predicate::EqCol(a1, b1), Predicate::EqCol(a2, b2))
+                | (Predicate::NeqCol(a1, b1), Predicate::NeqCol(a2, b2)) => a1 == a2 && b1 == b2,
+            (Predicate::Between(c1, l1, h1), Predicate::Between(c2, l2, h2)) => {
+                c1 == c2 && l1 == l2 && h1 == h2
+            },
+            (Predicate::In(c1, v1), Predicate::In(c2, v2)) => c1 == c2 && v1 == v2,
+            (Predicate::Contains(c1, s1), Predicate::Contains(c2, s2)) => c1 == c2 && s1 == s2,
+            (Predicate::Like(c1, m1), Predicate::Like(c2, m2)) => c1 == c2 && m1 == m2,
+            (Predicate::Regex(c1, r1), Predicate::Regex(c2, r2)) => {
+                c1 == c2 && r1.as_str() == r2.as_str()
+            },
+            (Predicate::IsNull(c1), Predicate::IsNull(c2)) => c1 == c2,
+            _ => false
+        };
     }
+}
 
-    panic!("Unknown type in numeric predicate operator");
+/// The literals of an IN predicate. Short lists are scanned, and longer
+/// ones are hashed.
+#[derive(Debug, PartialEq)]
+pub enum InList {
+    Small(Vec<Data>),
+    Hashed(HashSet<Data>)
+}
+
+// lists with more than this many literals are put into a hash set
+const IN_LIST_HASH_THRESHOLD: usize = 8;
+
+impl InList {
+    fn new(vals: Vec<Data>) -> InList {
+        if vals.len() > IN_LIST_HASH_THRESHOLD {
+            return InList::Hashed(vals.into_iter().collect());
+        }
+
+        return InList::Small(vals);
+    }
+
+    fn contains(&self, data: &Data) -> Option<bool> {
+        if data.is_null() {
+            return None;
+        }
+
+        return match self {
+            InList::Small(vals) => Some(
+                vals.iter().any(|v| cmp_to_literal(data, v) == Some(Ordering::Equal))),
+            InList::Hashed(vals) => Some(vals.contains(data))
+        };
+    }
+}
+
+/// Parses a literal value in a predicate. Numbers become INTEGER or REAL
+/// values, strings become TEXT, and dates and timestamps are given as
+/// objects, e.g.
+/// `{"date": "1998-12-01"}` or `{"timestamp": "1998-12-01 08:00:00"}`,
+/// and so are exact decimals, e.g. `{"decimal": "0.05"}`.
+pub fn literal_from_json(v: &serde_json::Value) -> Result<Data, String> {
+    if v.is_i64() {
+        return Ok(Data::Integer(v.as_i64().unwrap()));
+    } else if v.is_f64() {
+        return Ok(Data::Real(v.as_f64().unwrap()));
+    } else if let Some(s) = v.as_str() {
+        return Ok(Data::Text(String::from(s)));
+    }
+
+    if let Some(s) = v.get("date").and_then(|d| d.as_str()) {
+        return DataType::DATE.from_string(String::from(s))
+            .ok_or_else(|| format!("Invalid date literal {} in predicate", s));
+    }
+
+    if let Some(s) = v.get("timestamp").and_then(|d| d.as_str()) {
+        return DataType::TIMESTAMP.from_string(String::from(s))
+            .ok_or_else(|| format!("Invalid timestamp literal {} in predicate", s));
+    }
+
+    if let Some(s) = v.get("decimal").and_then(|d| d.as_str()) {
+        let (val, scale) = decimal::parse(s, None)
+            .ok_or_else(|| format!("Invalid decimal literal {} in predicate", s))?;
+        return Ok(Data::Decimal(val, scale));
+    }
+
+    return Err(format!("Unknown type in predicate literal {}", v));
+}
+
+fn cmp_to_literal(data: &Data, literal: &Data) -> Option<Ordering> {
+    if data.is_null() {
+        return None;
+    }
+
+    match data.partial_cmp(literal) {
+        Some(o) => { return Some(o); },
+        None => {
+            panic!("Comparing column value {:?} to incompatible literal {:?} in predicate",
+                   data, literal);
+        }
+    };
 }
 
 macro_rules! apply_op {
     ($val: expr, $data: expr, $op: tt) => {
-        match $val {
-            Left(int_val) => {
-                if let Data::Integer(i) = $data {
-                    i $op int_val
-                } else {
-                    panic!("Comparing non-integer column to integer value in predicate");
-                }
-            },
-            
-            Right(float_val) => {
-                if let Data::Real(f) = $data {
-                    f $op float_val
-                } else {
-                    panic!("Comparing non-float column to float value in predicate");
-                }
-            }
-        }   
+        cmp_to_literal($data, $val).map(|o| o $op Ordering::Equal)
     }
 }
 
+macro_rules! apply_col_op {
+    ($data1: expr, $data2: expr, $op: tt) => {{
+        let v1 = $data1;
+        let v2 = $data2;
+        if v1.is_null() || v2.is_null() {
+            None
+        } else {
+            Some(v1 $op v2)
+        }
+    }}
+}
+
 macro_rules! overflow_access {
     ($data1: expr, $data2: expr, $idx: expr) => {
         if ($data1).len() > $idx {
@@ -80,89 +206,242 @@ macro_rules! overflow_access {
     }
 }
 
+// Reads a column index attribute (e.g., col or col2) of a predicate node.
+fn col_from_json(tree: &serde_json::Value, op: &str, attr: &str) -> Result<usize, String> {
+    return tree.get(attr)
+        .and_then(|v| v.as_u64())
+        .map(|c| c as usize)
+        .ok_or_else(|| format!("{} operator requires a column index {} attribute",
+                               op.to_uppercase(), attr));
+}
+
+fn children_from_json(tree: &serde_json::Value, op: &str)
+                      -> Result<Vec<Predicate>, String> {
+    return tree["children"].as_array()
+        .ok_or_else(|| format!("{} operator requires a children array",
+                               op.to_uppercase()))?
+        .iter()
+        .map(Predicate::try_from_json)
+        .collect();
+}
+
+fn string_val_from_json<'a>(tree: &'a serde_json::Value, op: &str) -> Result<&'a str, String> {
+    return tree["val"].as_str()
+        .ok_or_else(|| format!("{} operator requires a string val", op.to_uppercase()));
+}
+
+// Parses a comparison of a column to either a literal (val) or another
+// column (col2).
+fn cmp_from_json<F, G>(tree: &serde_json::Value, op: &str, lit_op: F, col_op: G)
+                       -> Result<Predicate, String>
+where F: Fn(usize, Data) -> Predicate, G: Fn(usize, usize) -> Predicate {
+    let col_idx = col_from_json(tree, op, "col")?;
+
+    if let Some(v) = tree.get("val") {
+        return Ok(lit_op(col_idx, literal_from_json(v)?));
+    }
+
+    if tree.get("col2").is_some() {
+        return Ok(col_op(col_idx, col_from_json(tree, op, "col2")?));
+    }
+
+    return Err(format!("{} operator must have either a val or col2 attribute",
+                       op.to_uppercase()));
+}
+
 impl Predicate {
-    pub fn from_json(tree: &serde_json::Value) -> Predicate {
-        match tree["op"].as_str().unwrap() {
-            "and" => {
-                let mut children = tree["children"].as_array().unwrap();
-                assert_eq!(children.len(), 2);
+    /// Builds the predicate in an operator's options. The `predicate`
+    /// option is either a JSON tree or a string in the textual syntax
+    /// (e.g., `t.0 = 5 and t.1 contains "x"`), in which case the optional
+    /// `tables` option maps each table name to the offset of its columns.
+    pub fn from_options(options: &serde_json::Value) -> Predicate {
+        return Predicate::try_from_options(options)
+            .unwrap_or_else(|e| panic!("Invalid predicate: {}", e));
+    }
 
-                let c1_p = Predicate::from_json(&children[0]);
-                let c2_p = Predicate::from_json(&children[1]);
+    pub fn try_from_options(options: &serde_json::Value) -> Result<Predicate, String> {
+        let pred = &options["predicate"];
 
-                return Predicate::And(Box::new(c1_p),
-                                      Box::new(c2_p));
-            },
+        if pred.is_null() {
+            return Err(String::from("missing predicate option"));
+        }
 
-            "or" => {
-                let mut children = tree["children"].as_array().unwrap();
-                assert_eq!(children.len(), 2);
+        let tables: Option<HashMap<String, usize>> = options.get("tables")
+            .map(|t| -> Result<_, String> {
+                t.as_object()
+                    .ok_or_else(|| String::from("tables option must be an object"))?
+                    .iter()
+                    .map(|(k, v)| v.as_u64()
+                         .map(|offset| (k.clone(), offset as usize))
+                         .ok_or_else(|| format!("offset of table {} must be a column index", k)))
+                    .collect()
+            })
+            .transpose()?;
+
+        return Predicate::try_from_value(pred, tables.as_ref());
+    }
 
-                let c1_p = Predicate::from_json(&children[0]);
-                let c2_p = Predicate::from_json(&children[1]);
+    /// Reads a (normalized) predicate given either as a JSON tree or as
+    /// text.
+    pub fn try_from_value(pred: &serde_json::Value,
+                          tables: Option<&HashMap<String, usize>>)
+                          -> Result<Predicate, String> {
+        let text = match pred.as_str() {
+            Some(s) => s,
+            None => { return Ok(Predicate::try_from_json(pred)?.normalize()); }
+        };
 
-                return Predicate::Or(Box::new(c1_p),
-                                     Box::new(c2_p));
-            }
+        return parser::parse(text, tables)
+            .map(|p| p.normalize());
+    }
+    
+    #[cfg(test)]
+    pub fn from_json(tree: &serde_json::Value) -> Predicate {
+        return Predicate::try_from_json(tree)
+            .unwrap_or_else(|e| panic!("Invalid predicate: {}", e));
+    }
 
-            "not" => {
-                let mut children = tree["children"].as_array().unwrap();
-                assert_eq!(children.len(), 1);
+    pub fn try_from_json(tree: &serde_json::Value) -> Result<Predicate, String> {
+        let op = tree["op"].as_str()
+            .ok_or_else(|| format!("predicate node {} has no op", tree))?;
 
-                let c1_p = Predicate::from_json(&children[0]);
+        match op {
+            "and" => {
+                return Ok(Predicate::And(children_from_json(tree, op)?));
+            },
 
-                return Predicate::Not(Box::new(c1_p));
+            "or" => {
+                return Ok(Predicate::Or(children_from_json(tree, op)?));
             },
 
-            "lt" => {
-                let col_idx = tree["col"].as_i64().unwrap() as usize;
+            "true" => return Ok(Predicate::Const(true)),
+            "false" => return Ok(Predicate::Const(false)),
 
-                if let Some(v) = tree.get("val") {
-                    return Predicate::Lt(col_idx, either_from_json(v));
+            "not" => {
+                let mut children = children_from_json(tree, op)?;
+                if children.len() != 1 {
+                    return Err(format!("NOT operator requires exactly one child, got {}",
+                                       children.len()));
                 }
 
-                if let Some(v) = tree.get("col2").map(|v| v.as_i64()) {
-                    return Predicate::LtCol(col_idx, v.unwrap() as usize);
+                return Ok(Predicate::Not(Box::new(children.remove(0))));
+            },
+
+            "lt" => return cmp_from_json(tree, op, Predicate::Lt, Predicate::LtCol),
+            "lte" => return cmp_from_json(tree, op, Predicate::Lte, Predicate::LteCol),
+            "gt" => return cmp_from_json(tree, op, Predicate::Gt, Predicate::GtCol),
+            "gte" => return cmp_from_json(tree, op, Predicate::Gte, Predicate::GteCol),
+            "eq" => return cmp_from_json(tree, op, Predicate::Eq, Predicate::EqCol),
+            "neq" => return cmp_from_json(tree, op, Predicate::Neq, Predicate::NeqCol),
+
+            "between" => {
+                // col BETWEEN low AND high, where the bounds are either
+                // literals (low / high) or columns (low_col / high_col)
+                let col_idx = col_from_json(tree, op, "col")?;
+
+                if let (Some(low), Some(high)) = (tree.get("low"), tree.get("high")) {
+                    return Ok(Predicate::Between(col_idx,
+                                                 literal_from_json(low)?,
+                                                 literal_from_json(high)?));
                 }
 
-                panic!("LT operator must have either a val or col2 attribute");
+                let low = match (tree.get("low"), tree.get("low_col")) {
+                    (Some(v), _) => Predicate::Gte(col_idx, literal_from_json(v)?),
+                    (None, Some(_)) => Predicate::GteCol(col_idx, col_from_json(tree, op, "low_col")?),
+                    (None, None) => {
+                        return Err(String::from("BETWEEN operator must have either a low or low_col attribute"));
+                    }
+                };
+
+                let high = match (tree.get("high"), tree.get("high_col")) {
+                    (Some(v), _) => Predicate::Lte(col_idx, literal_from_json(v)?),
+                    (None, Some(_)) => Predicate::LteCol(col_idx, col_from_json(tree, op, "high_col")?),
+                    (None, None) => {
+                        return Err(String::from("BETWEEN operator must have either a high or high_col attribute"));
+                    }
+                };
+
+                return Ok(Predicate::And(vec![low, high]));
             },
 
-            "gt" => {
-                let col_idx = tree["col"].as_i64().unwrap() as usize;
-
-                if let Some(v) = tree.get("val") {
-                    return Predicate::Gt(col_idx, either_from_json(v));
-                }
+            "in" => {
+                let col_idx = col_from_json(tree, op, "col")?;
+                let vals = tree["vals"].as_array()
+                    .ok_or_else(|| String::from("IN operator must have a vals array"))?
+                    .iter()
+                    .map(literal_from_json)
+                    .collect::<Result<Vec<Data>, String>>()?;
 
-                if let Some(v) = tree.get("col2").map(|v| v.as_i64()) {
-                    return Predicate::GtCol(col_idx, v.unwrap() as usize);
-                }
+                return Ok(Predicate::In(col_idx, InList::new(vals)));
+            },
 
-                panic!("GT operator must have either a val or col2 attribute");
+            "contains" => {
+                let col_idx = col_from_json(tree, op, "col")?;
+                let str_val = string_val_from_json(tree, op)?;
+                return Ok(Predicate::Contains(col_idx, String::from(str_val)));
             },
 
-            "eq" => {
-                let col_idx = tree["col"].as_i64().unwrap() as usize;
+            "like" | "not like" | "ilike" | "not ilike" => {
+                let col_idx = col_from_json(tree, op, "col")?;
+                let pattern = string_val_from_json(tree, op)?;
 
-                if let Some(v) = tree.get("val") {
-                    return Predicate::Eq(col_idx, either_from_json(v));
+                let matcher = LikeMatcher::new(pattern, op.ends_with("ilike"));
+                let like = Predicate::Like(col_idx, matcher);
+                
+                if op.starts_with("not") {
+                    return Ok(Predicate::Not(Box::new(like)));
                 }
 
-                if let Some(v) = tree.get("col2").map(|v| v.as_i64()) {
-                    return Predicate::EqCol(col_idx, v.unwrap() as usize);
+                return Ok(like);
+            },
+
+            "regex" | "not regex" | "iregex" | "not iregex" => {
+                // the pattern may match anywhere in the value, unless the
+                // anchored option is set, in which case the whole value
+                // must match
+                let col_idx = col_from_json(tree, op, "col")?;
+                let pattern = string_val_from_json(tree, op)?;
+
+                let anchored = match tree.get("anchored") {
+                    None => false,
+                    Some(v) => v.as_bool()
+                        .ok_or_else(|| format!("{} operator's anchored attribute must be a boolean",
+                                               op.to_uppercase()))?
+                };
+                
+                let pattern = if anchored {
+                    format!("^(?:{})$", pattern)
+                } else {
+                    String::from(pattern)
+                };
+
+                let re = RegexBuilder::new(&pattern)
+                    .case_insensitive(op.ends_with("iregex"))
+                    .build()
+                    .map_err(|e| format!("Invalid regex in predicate: {}", e))?;
+
+                let regex = Predicate::Regex(col_idx, re);
+                
+                if op.starts_with("not") {
+                    return Ok(Predicate::Not(Box::new(regex)));
                 }
 
-                panic!("EQ operator must have either a val or col2 attribute");
+                return Ok(regex);
             },
 
-            "contains" => {
-                let col_idx = tree["col"].as_i64().unwrap() as usize;
-                let str_val = tree["val"].as_str().unwrap();
-                return Predicate::Contains(col_idx, String::from(str_val));
+            "is null" => {
+                let col_idx = col_from_json(tree, op, "col")?;
+                return Ok(Predicate::IsNull(col_idx));
+            },
+
+            "is not null" => {
+                let col_idx = col_from_json(tree, op, "col")?;
+                return Ok(Predicate::Not(Box::new(Predicate::IsNull(col_idx))));
             },
 
-            _ => panic!("unknown op in predicate")
+            _ => {
+                return Err(format!("unknown op {} in predicate", op));
+            }
         };
     }
 
@@ -171,55 +450,148 @@ impl Predicate {
     }
 
     pub fn eval_with_accessor<'a, F>(&self, accessor_func: &F) -> bool
+    where F: Fn(usize) -> &'a Data {
+        // rows for which the predicate is unknown (NULL) are filtered
+        // out, just like rows for which it is false.
+        return self.eval_tristate(accessor_func) == Some(true);
+    }
+
+    /// Evaluates the predicate using SQL three-valued logic, where `None`
+    /// represents UNKNOWN (e.g., any comparison involving a NULL).
+    pub fn eval_tristate<'a, F>(&self, accessor_func: &F) -> Option<bool>
     where F: Fn(usize) -> &'a Data {
         match &self {
-            Predicate::And(p1, p2) => {
-                return p1.eval_with_accessor(accessor_func) && p2.eval_with_accessor(accessor_func);
+            Predicate::And(children) => {
+                // FALSE if any child is FALSE, otherwise UNKNOWN if any
+                // child is UNKNOWN
+                let mut result = Some(true);
+                for c in children.iter() {
+                    match c.eval_tristate(accessor_func) {
+                        Some(false) => { return Some(false); },
+                        None => { result = None; },
+                        Some(true) => {}
+                    };
+                }
+
+                return result;
             }
 
-            Predicate::Or(p1, p2) => {
-                return p1.eval_with_accessor(accessor_func) || p2.eval_with_accessor(accessor_func);
+            Predicate::Or(children) => {
+                let mut result = Some(false);
+                for c in children.iter() {
+                    match c.eval_tristate(accessor_func) {
+                        Some(true) => { return Some(true); },
+                        None => { result = None; },
+                        Some(false) => {}
+                    };
+                }
+
+                return result;
+            },
+
+            Predicate::Const(b) => {
+                return Some(*b);
             },
 
             Predicate::Not(p1) => {
-                return !p1.eval_with_accessor(accessor_func);
+                return p1.eval_tristate(accessor_func).map(|b| !b);
             },
 
             Predicate::Lt(col_idx, val) => {
                 return apply_op!(val, accessor_func(*col_idx), <);
             },
 
+            Predicate::Lte(col_idx, val) => {
+                return apply_op!(val, accessor_func(*col_idx), <=);
+            },
+
             Predicate::LtCol(col_idx, col2_idx) => {
-                return accessor_func(*col_idx) < accessor_func(*col2_idx);
+                return apply_col_op!(accessor_func(*col_idx),
+                                     accessor_func(*col2_idx), <);
+            },
+
+            Predicate::LteCol(col_idx, col2_idx) => {
+                return apply_col_op!(accessor_func(*col_idx),
+                                     accessor_func(*col2_idx), <=);
             },
             
             Predicate::Gt(col_idx, val) => {
                 return apply_op!(val, accessor_func(*col_idx), >);
             },
+
+            Predicate::Gte(col_idx, val) => {
+                return apply_op!(val, accessor_func(*col_idx), >=);
+            },
             
             Predicate::GtCol(col_idx, col2_idx) => {
-                return accessor_func(*col_idx) > accessor_func(*col2_idx);
+                return apply_col_op!(accessor_func(*col_idx),
+                                     accessor_func(*col2_idx), >);
+            },
+
+            Predicate::GteCol(col_idx, col2_idx) => {
+                return apply_col_op!(accessor_func(*col_idx),
+                                     accessor_func(*col2_idx), >=);
             },
 
             Predicate::Eq(col_idx, val) => {
                 return apply_op!(val, accessor_func(*col_idx), ==);
             },
 
+            Predicate::Neq(col_idx, val) => {
+                return apply_op!(val, accessor_func(*col_idx), !=);
+            },
+
             Predicate::EqCol(col_idx, col2_idx) => {
-                let v1 = accessor_func(*col_idx);
-                let v2 = accessor_func(*col2_idx);
-                return v1 == v2;
+                return apply_col_op!(accessor_func(*col_idx),
+                                     accessor_func(*col2_idx), ==);
+            },
+
+            Predicate::NeqCol(col_idx, col2_idx) => {
+                return apply_col_op!(accessor_func(*col_idx),
+                                     accessor_func(*col2_idx), !=);
+            },
+
+            Predicate::Between(col_idx, low, high) => {
+                let d = accessor_func(*col_idx);
+                return cmp_to_literal(d, low)
+                    .map(|o| o != Ordering::Less
+                         && cmp_to_literal(d, high).unwrap() != Ordering::Greater);
+            },
+
+            Predicate::In(col_idx, vals) => {
+                return vals.contains(accessor_func(*col_idx));
             },
 
             Predicate::Contains(col_idx, string_val) => {
                 let d = accessor_func(*col_idx);
-                if let Data::Text(ref s) = d {
-                    return s.contains(string_val);
-                }
+                match d {
+                    Data::Text(ref s) => { return Some(s.contains(string_val)); },
+                    Data::Null => { return None; },
+                    _ => panic!("contains requires a string column")
+                };
+            },
+
+            Predicate::Like(col_idx, matcher) => {
+                let d = accessor_func(*col_idx);
+                match d {
+                    Data::Text(ref s) => { return Some(matcher.matches(s)); },
+                    Data::Null => { return None; },
+                    _ => panic!("LIKE requires a string column")
+                };
+            },
+
+            Predicate::Regex(col_idx, re) => {
+                let d = accessor_func(*col_idx);
+                match d {
+                    Data::Text(ref s) => { return Some(re.is_match(s)); },
+                    Data::Null => { return None; },
+                    _ => panic!("regex requires a string column")
+                };
+            },
 
-                panic!("contains requires a string column");
+            Predicate::IsNull(col_idx) => {
+                return Some(accessor_func(*col_idx).is_null());
             }
-            
         }
     }
     
@@ -232,9 +604,9 @@ impl Predicate {
 #[cfg(test)]
 mod tests {
 
-    use predicate::Predicate;
+    use predicate::{Predicate, InList};
     use serde_json;
-    use data::Data;
+    use data::{Data, DataType};
     
     #[test]
     fn simple_test() {
@@ -298,4 +670,206 @@ mod tests {
         assert!(p.eval(&r3));
         assert!(!p.eval(&r4));
     }
+
+    #[test]
+    fn null_test() {
+        let v: serde_json::Value = serde_json::from_str(r#"
+{ "op": "or",
+  "children": [
+    { "op": "lt", "col": 0, "val": 4 },
+    { "op": "is null", "col": 1 }
+  ]
+} 
+"#).unwrap();
+
+        let p = Predicate::from_json(&v);
+
+        let r1 = vec![Data::Null, Data::Real(8.0)];
+        let r2 = vec![Data::Null, Data::Null];
+        let r3 = vec![Data::Integer(3), Data::Real(2.0)];
+
+        assert!(!p.eval(&r1));
+        assert!(p.eval(&r2));
+        assert!(p.eval(&r3));
+
+        // NOT(UNKNOWN) is still UNKNOWN, so the row is filtered
+        let not_p = Predicate::Not(Box::new(Predicate::from_json(
+            &json!({ "op": "eq", "col": 0, "col2": 1 }))));
+        assert!(!not_p.eval(&[Data::Integer(1), Data::Null]));
+        assert!(not_p.eval(&[Data::Integer(1), Data::Integer(2)]));
+
+        // UNKNOWN AND FALSE is FALSE
+        let and_p = Predicate::And(vec![
+            Predicate::from_json(&json!({ "op": "gt", "col": 0, "val": 1 })),
+            Predicate::from_json(&json!({ "op": "gt", "col": 1, "val": 1 }))]);
+        let tri = |row: &[Data]| {
+            let acc = |idx: usize| &row[idx];
+            and_p.eval_tristate(&acc)
+        };
+        assert_eq!(tri(&[Data::Null, Data::Integer(0)]), Some(false));
+        assert_eq!(tri(&[Data::Null, Data::Integer(2)]), None);
+    }
+
+    #[test]
+    fn date_test() {
+        let v: serde_json::Value = serde_json::from_str(r#"
+{ "op": "and",
+  "children": [
+    { "op": "gt", "col": 0, "val": {"date": "1994-01-01"} },
+    { "op": "lt", "col": 1, "val": {"timestamp": "1995-01-01 12:00:00"} }
+  ]
+} 
+"#).unwrap();
+
+        let p = Predicate::from_json(&v);
+
+        let d = |s: &str| DataType::DATE.from_string(String::from(s)).unwrap();
+        let t = |s: &str| DataType::TIMESTAMP.from_string(String::from(s)).unwrap();
+
+        assert!(p.eval(&[d("1994-06-01"), t("1995-01-01 11:59:59")]));
+        assert!(!p.eval(&[d("1994-01-01"), t("1995-01-01 11:59:59")]));
+        assert!(!p.eval(&[d("1994-06-01"), t("1995-01-01 12:00:00")]));
+        assert!(!p.eval(&[Data::Null, t("1990-01-01")]));
+    }
+
+    #[test]
+    fn decimal_test() {
+        let v = json!({ "op": "and",
+                        "children": [
+                            { "op": "gt", "col": 0, "val": {"decimal": "0.04"} },
+                            { "op": "lt", "col": 0, "val": {"decimal": "0.070"} }
+                        ]});
+
+        let p = Predicate::from_json(&v);
+
+        assert!(p.eval(&[Data::Decimal(5, 2)]));
+        assert!(!p.eval(&[Data::Decimal(7, 2)]));
+        assert!(!p.eval(&[Data::Decimal(4, 2)]));
+    }
+
+    #[test]
+    fn comparison_ops_test() {
+        let p = Predicate::from_json(&json!({
+            "op": "and", "children": [
+                { "op": "gte", "col": 0, "val": 3 },
+                { "op": "and", "children": [
+                    { "op": "lte", "col": 0, "col2": 1 },
+                    { "op": "neq", "col": 1, "val": 7 }
+                ]}
+            ]}));
+
+        assert!(p.eval(&[Data::Integer(3), Data::Integer(3)]));
+        assert!(p.eval(&[Data::Integer(4), Data::Integer(6)]));
+        assert!(!p.eval(&[Data::Integer(2), Data::Integer(6)]));
+        assert!(!p.eval(&[Data::Integer(4), Data::Integer(3)]));
+        assert!(!p.eval(&[Data::Integer(4), Data::Integer(7)]));
+        assert!(!p.eval(&[Data::Integer(4), Data::Null]));
+
+        let p = Predicate::from_json(&json!({ "op": "neq", "col": 0, "col2": 1 }));
+        assert!(p.eval(&[Data::Integer(4), Data::Integer(3)]));
+        assert!(!p.eval(&[Data::Integer(4), Data::Integer(4)]));
+        assert!(!p.eval(&[Data::Null, Data::Integer(4)]));
+    }
+
+    #[test]
+    fn between_in_test() {
+        let p = Predicate::from_json(&json!({ "op": "between", "col": 0,
+                                              "low": 2, "high": 4 }));
+        assert!(!p.eval(&[Data::Integer(1)]));
+        assert!(p.eval(&[Data::Integer(2)]));
+        assert!(p.eval(&[Data::Integer(4)]));
+        assert!(!p.eval(&[Data::Integer(5)]));
+        assert!(!p.eval(&[Data::Null]));
+
+        let p = Predicate::from_json(&json!({ "op": "between", "col": 0,
+                                              "low": 2, "high_col": 1 }));
+        assert!(p.eval(&[Data::Integer(3), Data::Integer(3)]));
+        assert!(!p.eval(&[Data::Integer(3), Data::Integer(2)]));
+
+        let p = Predicate::from_json(&json!({ "op": "in", "col": 0,
+                                              "vals": [1, 3, 5] }));
+        assert!(matches!(p, Predicate::In(_, InList::Small(_))));
+        assert!(p.eval(&[Data::Integer(3)]));
+        assert!(!p.eval(&[Data::Integer(4)]));
+        assert!(!p.eval(&[Data::Null]));
+
+        let vals: Vec<i64> = (0..100).map(|i| i * 2).collect();
+        let p = Predicate::from_json(&json!({ "op": "in", "col": 0,
+                                              "vals": vals }));
+        assert!(matches!(p, Predicate::In(_, InList::Hashed(_))));
+        assert!(p.eval(&[Data::Integer(42)]));
+        assert!(!p.eval(&[Data::Integer(43)]));
+        assert!(!p.eval(&[Data::Null]));
+
+        // NOT IN with a NULL value is unknown, not true
+        let not_p = Predicate::Not(Box::new(p));
+        assert!(!not_p.eval(&[Data::Null]));
+        assert!(not_p.eval(&[Data::Integer(43)]));
+    }
+
+    #[test]
+    fn string_test() {
+        let p = Predicate::from_json(&json!({
+            "op": "and", "children": [
+                { "op": "eq", "col": 0, "val": "f" },
+                { "op": "like", "col": 1, "val": "%Star%" }
+            ]}));
+
+        let row = |a: &str, b: &str| vec![Data::Text(String::from(a)),
+                                          Data::Text(String::from(b))];
+        assert!(p.eval(&row("f", "Star Wars")));
+        assert!(!p.eval(&row("m", "Star Wars")));
+        assert!(!p.eval(&row("f", "star wars")));
+        assert!(!p.eval(&[Data::Text(String::from("f")), Data::Null]));
+
+        let p = Predicate::from_json(&json!({ "op": "lt", "col": 0, "val": "movie" }));
+        assert!(p.eval(&row("episode", "")));
+        assert!(!p.eval(&row("tv series", "")));
+
+        let p = Predicate::from_json(&json!({ "op": "not ilike", "col": 1, "val": "%star%" }));
+        assert!(!p.eval(&row("f", "Star Wars")));
+        assert!(p.eval(&row("f", "Trek")));
+        assert!(!p.eval(&[Data::Text(String::from("f")), Data::Null]));
+    }
+
+    #[test]
+    fn regex_test() {
+        let text = |s: &str| vec![Data::Text(String::from(s))];
+        
+        let p = Predicate::from_json(&json!({ "op": "regex", "col": 0,
+                                              "val": "(Star|Space) (Wars|Trek)" }));
+        assert!(p.eval(&text("Star Wars: A New Hope")));
+        assert!(p.eval(&text("The Space Trek")));
+        assert!(!p.eval(&text("star wars")));
+        assert!(!p.eval(&[Data::Null]));
+
+        let p = Predicate::from_json(&json!({ "op": "iregex", "col": 0,
+                                              "val": "(star|space) (wars|trek)",
+                                              "anchored": true }));
+        assert!(p.eval(&text("Star Wars")));
+        assert!(!p.eval(&text("Star Wars: A New Hope")));
+
+        let p = Predicate::from_json(&json!({ "op": "not regex", "col": 0,
+                                              "val": "^[0-9]+$" }));
+        assert!(p.eval(&text("12a")));
+        assert!(!p.eval(&text("123")));
+        assert!(!p.eval(&[Data::Null]));
+    }
+
+    #[test]
+    fn invalid_json_test() {
+        let err = |v: serde_json::Value| Predicate::try_from_json(&v).err().unwrap();
+
+        assert_eq!(err(json!({ "op": "bogus", "col": 0 })),
+                   "unknown op bogus in predicate");
+        assert_eq!(err(json!({ "op": "and", "children": [
+            { "op": "lt", "col": 0 }] })),
+                   "LT operator must have either a val or col2 attribute");
+        assert_eq!(err(json!({ "op": "is null" })),
+                   "IS NULL operator requires a column index col attribute");
+        assert!(err(json!({ "op": "regex", "col": 0, "val": "(" }))
+                .starts_with("Invalid regex in predicate"));
+        assert!(Predicate::try_from_value(&json!({ "op": "not", "children": [] }), None)
+                .is_err());
+    }
 }
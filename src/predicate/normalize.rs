@@ -0,0 +1,201 @@
+// < begin copyright > 
+// Copyright Ryan Marcus 2018
+// 
+// This file is part of byoo.
+// 
+// byoo is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// byoo is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with byoo.  If not, see <http://www.gnu.org/licenses/>.
+// 
+// < end copyright > 
+// Simplifies predicate trees before they are evaluated: nested ANDs and
+// ORs are flattened, constants are folded, duplicate children are
+// removed, and the children of each AND / OR are ordered so that cheap
+// tests run (and short circuit) before expensive ones. All of these
+// rewrites preserve SQL's three-valued semantics.
+use predicate::{Predicate, InList};
+use data::Data;
+
+impl Predicate {
+    pub fn normalize(self) -> Predicate {
+        match self {
+            Predicate::And(children) => return normalize_junction(children, true),
+            Predicate::Or(children) => return normalize_junction(children, false),
+            Predicate::Not(child) => {
+                return match child.normalize() {
+                    Predicate::Const(b) => Predicate::Const(!b),
+                    Predicate::Not(inner) => *inner,
+                    p => Predicate::Not(Box::new(p))
+                };
+            },
+            p => return p
+        };
+    }
+
+    /// A rough estimate of the cost of evaluating this predicate on a
+    /// single row, used to order the children of ANDs and ORs.
+    pub fn cost(&self) -> usize {
+        return match self {
+            Predicate::Const(_) => 0,
+            Predicate::IsNull(_) => 1,
+            Predicate::Lt(_, lit) | Predicate::Lte(_, lit)
+                | Predicate::Gt(_, lit) | Predicate::Gte(_, lit)
+                | Predicate::Eq(_, lit) | Predicate::Neq(_, lit) => literal_cost(lit),
+            Predicate::LtCol(_, _) | Predicate::LteCol(_, _)
+                | Predicate::GtCol(_, _) | Predicate::GteCol(_, _)
+                | Predicate::EqCol(_, _) | Predicate::NeqCol(_, _) => 3,
+            Predicate::Between(_, low, _) => 2 * literal_cost(low),
+            Predicate::In(_, InList::Small(vals)) => {
+                vals.iter().map(literal_cost).sum::<usize>().min(8)
+            },
+            Predicate::In(_, InList::Hashed(_)) => 8,
+            Predicate::Contains(_, _) | Predicate::Like(_, _) => 16,
+            Predicate::Regex(_, _) => 64,
+            Predicate::Not(child) => child.cost(),
+            Predicate::And(children) | Predicate::Or(children) => {
+                children.iter().map(|c| c.cost()).sum()
+            }
+        };
+    }
+}
+
+fn literal_cost(lit: &Data) -> usize {
+    return match lit {
+        Data::Text(_) | Data::Blob(_) => 4,
+        _ => 2
+    };
+}
+
+fn push_unique(children: &mut Vec<Predicate>, p: Predicate) {
+    if !children.contains(&p) {
+        children.push(p);
+    }
+}
+
+// For an AND, TRUE children can be dropped and any FALSE child makes the
+// whole predicate FALSE. For an OR, the reverse.
+fn normalize_junction(children: Vec<Predicate>, is_and: bool) -> Predicate {
+    let mut flat = Vec::with_capacity(children.len());
+
+    for child in children {
+        match child.normalize() {
+            Predicate::Const(b) if b == is_and => {},
+            Predicate::Const(b) => { return Predicate::Const(b); },
+            Predicate::And(grandchildren) if is_and => {
+                for gc in grandchildren {
+                    push_unique(&mut flat, gc);
+                }
+            },
+            Predicate::Or(grandchildren) if !is_and => {
+                for gc in grandchildren {
+                    push_unique(&mut flat, gc);
+                }
+            },
+            p => push_unique(&mut flat, p)
+        };
+    }
+
+    if flat.len() <= 1 {
+        return flat.pop().unwrap_or(Predicate::Const(is_and));
+    }
+
+    flat.sort_by_cached_key(|p| p.cost());
+
+    if is_and {
+        return Predicate::And(flat);
+    }
+
+    return Predicate::Or(flat);
+}
+
+#[cfg(test)]
+mod tests {
+    use predicate::Predicate;
+    use data::Data;
+
+    fn pred(json: serde_json::Value) -> Predicate {
+        return Predicate::from_json(&json).normalize();
+    }
+
+    #[test]
+    fn flattens_and_orders() {
+        let p = pred(json!({ "op": "and", "children": [
+            { "op": "like", "col": 1, "val": "%x%" },
+            { "op": "and", "children": [
+                { "op": "eq", "col": 2, "val": "movie" },
+                { "op": "and", "children": [
+                    { "op": "lt", "col": 0, "val": 5 },
+                    { "op": "lt", "col": 0, "val": 5 }
+                ]}
+            ]}
+        ]}));
+
+        match p {
+            Predicate::And(ref children) => {
+                assert_eq!(children.len(), 3);
+                assert_eq!(children[0], Predicate::Lt(0, Data::Integer(5)));
+                assert_eq!(children[1], Predicate::Eq(2, Data::Text(String::from("movie"))));
+                assert!(matches!(children[2], Predicate::Like(1, _)));
+            },
+            _ => panic!("expected an AND, got {:?}", p)
+        };
+    }
+
+    #[test]
+    fn folds_constants() {
+        let p = pred(json!({ "op": "or", "children": [
+            { "op": "lt", "col": 0, "val": 5 },
+            { "op": "not", "children": [{ "op": "false" }] }
+        ]}));
+        assert_eq!(p, Predicate::Const(true));
+
+        let p = pred(json!({ "op": "and", "children": [
+            { "op": "true" },
+            { "op": "or", "children": [
+                { "op": "false" },
+                { "op": "not", "children": [{ "op": "not", "children": [
+                    { "op": "gt", "col": 0, "val": 5 }]}]}
+            ]}
+        ]}));
+        assert_eq!(p, Predicate::Gt(0, Data::Integer(5)));
+
+        assert_eq!(pred(json!({ "op": "and", "children": [] })), Predicate::Const(true));
+        assert_eq!(pred(json!({ "op": "or", "children": [] })), Predicate::Const(false));
+    }
+
+    #[test]
+    fn keeps_semantics() {
+        let json = json!({ "op": "or", "children": [
+            { "op": "and", "children": [
+                { "op": "contains", "col": 1, "val": "a" },
+                { "op": "gt", "col": 0, "val": 1 },
+                { "op": "true" }
+            ]},
+            { "op": "is null", "col": 0 },
+            { "op": "eq", "col": 0, "val": 0 }
+        ]});
+
+        let raw = Predicate::from_json(&json);
+        let norm = Predicate::from_json(&json).normalize();
+
+        let vals = vec![Data::Null, Data::Integer(0), Data::Integer(1), Data::Integer(2)];
+        let strs = vec![Data::Null, Data::Text(String::from("a")),
+                        Data::Text(String::from("b"))];
+        for v in vals.iter() {
+            for s in strs.iter() {
+                let row = [v.clone(), s.clone()];
+                let acc = |idx: usize| &row[idx];
+                assert_eq!(raw.eval_tristate(&acc), norm.eval_tristate(&acc));
+            }
+        }
+    }
+}
@@ -0,0 +1,447 @@
+// < begin copyright > 
+// Copyright Ryan Marcus 2018
+// 
+// This file is part of byoo.
+// 
+// byoo is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// byoo is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with byoo.  If not, see <http://www.gnu.org/licenses/>.
+// 
+// < end copyright > 
+// A parser for the textual predicate syntax of python/grammer/predicate.g4,
+// e.g. `t.0 = 5 and not (t.2 contains "x")`. As in the ANTLR grammar,
+// `and` and `or` have the same precedence and associate to the left, and
+// `not` binds tighter than both.
+use std::collections::HashMap;
+use data::Data;
+use predicate::Predicate;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Col(String, usize),
+    Literal(Data),
+    CmpOp(String),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+    End
+}
+
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    col: usize
+}
+
+// a token along with the line and column where it started
+type Spanned = (Token, usize, usize);
+
+fn error(line: usize, col: usize, msg: &str) -> String {
+    return format!("line {}, column {}: {}", line, col, msg);
+}
+
+impl Lexer {
+    fn new(text: &str) -> Lexer {
+        return Lexer {
+            chars: text.chars().collect(),
+            pos: 0, line: 1, col: 1
+        };
+    }
+
+    fn peek(&self) -> Option<char> {
+        return self.chars.get(self.pos).cloned();
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        return self.chars.get(self.pos + offset).cloned();
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek();
+        if let Some(c) = c {
+            self.pos += 1;
+            if c == '\n' {
+                self.line += 1;
+                self.col = 1;
+            } else {
+                self.col += 1;
+            }
+        }
+        return c;
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> String {
+        let mut s = String::new();
+        while let Some(c) = self.peek() {
+            if !f(c) {
+                break;
+            }
+            s.push(c);
+            self.bump();
+        }
+        return s;
+    }
+
+    fn tokens(mut self) -> Result<Vec<Spanned>, String> {
+        let mut tokens = Vec::new();
+
+        loop {
+            self.take_while(char::is_whitespace);
+            let (line, col) = (self.line, self.col);
+            
+            let c = match self.peek() {
+                Some(c) => c,
+                None => {
+                    tokens.push((Token::End, line, col));
+                    return Ok(tokens);
+                }
+            };
+
+            let token = match c {
+                '(' => { self.bump(); Token::LParen },
+                ')' => { self.bump(); Token::RParen },
+                '"' => self.string_literal(line, col)?,
+                '=' => { self.bump(); Token::CmpOp(String::from("=")) },
+                '<' | '>' | '!' => {
+                    self.bump();
+                    if self.peek() == Some('=') {
+                        self.bump();
+                        Token::CmpOp(format!("{}=", c))
+                    } else if c == '!' {
+                        return Err(error(line, col, "expected = after !"));
+                    } else {
+                        Token::CmpOp(c.to_string())
+                    }
+                },
+                '-' if self.peek_at(1).map(|c| c.is_ascii_digit()).unwrap_or(false) => {
+                    self.bump();
+                    self.word(line, col, true)?
+                },
+                _ if c.is_ascii_alphanumeric() => self.word(line, col, false)?,
+                _ => {
+                    return Err(error(line, col, &format!("unexpected character '{}'", c)));
+                }
+            };
+
+            tokens.push((token, line, col));
+        }
+    }
+
+    fn string_literal(&mut self, line: usize, col: usize) -> Result<Token, String> {
+        self.bump();
+        let mut s = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => { return Ok(Token::Literal(Data::Text(s))); },
+                Some('\\') => {
+                    match self.bump() {
+                        Some(c) if c == '"' || c == '\\' => s.push(c),
+                        _ => {
+                            return Err(error(self.line, self.col - 1,
+                                             "invalid escape in string literal"));
+                        }
+                    };
+                },
+                Some('\n') | Some('\r') | None => {
+                    return Err(error(line, col, "unterminated string literal"));
+                },
+                Some(c) => s.push(c)
+            };
+        }
+    }
+
+    // a number, a column reference (table.idx) or a keyword
+    fn word(&mut self, line: usize, col: usize, negative: bool) -> Result<Token, String> {
+        let start = self.pos;
+        let word = self.take_while(|c| c.is_ascii_alphanumeric());
+        let has_fraction = self.peek() == Some('.')
+            && self.peek_at(1).map(|c| c.is_ascii_digit()).unwrap_or(false);
+
+        if word.chars().all(|c| c.is_ascii_digit()) {
+            if has_fraction {
+                self.bump();
+                self.take_while(|c| c.is_ascii_digit());
+            }
+
+            let mut text: String = self.chars[start..self.pos].iter().collect();
+            if negative {
+                text.insert(0, '-');
+            }
+            
+            if has_fraction {
+                return Ok(Token::Literal(Data::Real(text.parse::<f64>().unwrap())));
+            }
+
+            return text.parse::<i64>()
+                .map(|i| Token::Literal(Data::Integer(i)))
+                .map_err(|_| error(line, col, "integer literal out of range"));
+        }
+
+        if negative {
+            return Err(error(line, col, "expected a number after -"));
+        }
+
+        if has_fraction {
+            self.bump();
+            let idx = self.take_while(|c| c.is_ascii_digit());
+            let idx = idx.parse::<usize>()
+                .map_err(|_| error(line, col, "column index out of range"))?;
+            return Ok(Token::Col(word, idx));
+        }
+
+        return match word.as_str() {
+            "and" => Ok(Token::And),
+            "or" => Ok(Token::Or),
+            "not" => Ok(Token::Not),
+            "contains" => Ok(Token::CmpOp(word)),
+            _ => Err(error(line, col, &format!(
+                "unexpected word '{}' (columns are written as table.index)", word)))
+        };
+    }
+}
+
+struct Parser<'a> {
+    tokens: Vec<Spanned>,
+    pos: usize,
+    tables: Option<&'a HashMap<String, usize>>
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> &Spanned {
+        return &self.tokens[self.pos];
+    }
+
+    fn next(&mut self) -> Spanned {
+        let t = self.tokens[self.pos].clone();
+        if t.0 != Token::End {
+            self.pos += 1;
+        }
+        return t;
+    }
+
+    fn unexpected(&self, expected: &str) -> String {
+        let (ref t, line, col) = *self.peek();
+        let found = match t {
+            Token::End => String::from("end of input"),
+            Token::Col(table, idx) => format!("column {}.{}", table, idx),
+            Token::Literal(d) => format!("literal {:?}", d),
+            Token::CmpOp(op) => format!("operator {}", op),
+            Token::And => String::from("and"),
+            Token::Or => String::from("or"),
+            Token::Not => String::from("not"),
+            Token::LParen => String::from("("),
+            Token::RParen => String::from(")")
+        };
+        
+        return error(line, col, &format!("expected {}, found {}", expected, found));
+    }
+
+    // predicate := unary (('and' | 'or') unary)*
+    fn predicate(&mut self) -> Result<Predicate, String> {
+        let mut lhs = self.unary()?;
+
+        loop {
+            let is_and = match self.peek().0 {
+                Token::And => true,
+                Token::Or => false,
+                _ => { return Ok(lhs); }
+            };
+            self.next();
+
+            let rhs = self.unary()?;
+            lhs = if is_and {
+                Predicate::And(vec![lhs, rhs])
+            } else {
+                Predicate::Or(vec![lhs, rhs])
+            };
+        }
+    }
+
+    // unary := 'not' unary | '(' predicate ')' | term
+    fn unary(&mut self) -> Result<Predicate, String> {
+        match self.peek().0 {
+            Token::Not => {
+                self.next();
+                return Ok(Predicate::Not(Box::new(self.unary()?)));
+            },
+            Token::LParen => {
+                self.next();
+                let p = self.predicate()?;
+                if self.peek().0 != Token::RParen {
+                    return Err(self.unexpected(")"));
+                }
+                self.next();
+                return Ok(p);
+            },
+            _ => return self.term()
+        };
+    }
+
+    fn column(&mut self) -> Result<Option<usize>, String> {
+        let (t, line, col) = self.peek().clone();
+        let (table, idx) = match t {
+            Token::Col(table, idx) => (table, idx),
+            _ => { return Ok(None); }
+        };
+        self.next();
+
+        return match self.tables {
+            None => Ok(Some(idx)),
+            Some(tables) => match tables.get(&table) {
+                Some(offset) => Ok(Some(offset + idx)),
+                None => Err(error(line, col, &format!("unknown table '{}'", table)))
+            }
+        };
+    }
+
+    // term := COL C_OP COL | COL C_OP LITERAL
+    fn term(&mut self) -> Result<Predicate, String> {
+        let lhs = match self.column()? {
+            Some(c) => c,
+            None => { return Err(self.unexpected("a column, 'not' or '('")); }
+        };
+
+        let (op, line, col) = match self.peek().clone() {
+            (Token::CmpOp(op), line, col) => (op, line, col),
+            _ => { return Err(self.unexpected("a comparison operator")); }
+        };
+        self.next();
+
+        if let Some(rhs) = self.column()? {
+            return match op.as_str() {
+                "=" => Ok(Predicate::EqCol(lhs, rhs)),
+                "!=" => Ok(Predicate::NeqCol(lhs, rhs)),
+                "<" => Ok(Predicate::LtCol(lhs, rhs)),
+                "<=" => Ok(Predicate::LteCol(lhs, rhs)),
+                ">" => Ok(Predicate::GtCol(lhs, rhs)),
+                ">=" => Ok(Predicate::GteCol(lhs, rhs)),
+                _ => Err(error(line, col, &format!(
+                    "operator {} cannot compare two columns", op)))
+            };
+        }
+
+        let literal = match self.peek().0.clone() {
+            Token::Literal(d) => d,
+            _ => { return Err(self.unexpected("a column or literal")); }
+        };
+        self.next();
+
+        return match op.as_str() {
+            "=" => Ok(Predicate::Eq(lhs, literal)),
+            "!=" => Ok(Predicate::Neq(lhs, literal)),
+            "<" => Ok(Predicate::Lt(lhs, literal)),
+            "<=" => Ok(Predicate::Lte(lhs, literal)),
+            ">" => Ok(Predicate::Gt(lhs, literal)),
+            ">=" => Ok(Predicate::Gte(lhs, literal)),
+            "contains" => match literal {
+                Data::Text(s) => Ok(Predicate::Contains(lhs, s)),
+                _ => Err(error(line, col, "contains requires a string literal"))
+            },
+            _ => unreachable!()
+        };
+    }
+}
+
+/// Parses a textual predicate. Columns are written as `table.index`; if
+/// `tables` is given, each table name maps to the offset of its first
+/// column (e.g., the width of the left input of a join), and otherwise
+/// the index is used as is.
+pub fn parse(text: &str, tables: Option<&HashMap<String, usize>>)
+             -> Result<Predicate, String> {
+    let tokens = Lexer::new(text).tokens()?;
+    let mut parser = Parser { tokens, pos: 0, tables };
+    
+    let p = parser.predicate()?;
+    if parser.peek().0 != Token::End {
+        return Err(parser.unexpected("'and', 'or' or end of input"));
+    }
+
+    return Ok(p);
+}
+
+#[cfg(test)]
+mod tests {
+    use predicate::parser::parse;
+    use predicate::Predicate;
+    use data::Data;
+    use std::collections::HashMap;
+
+    fn eval(p: &Predicate, row: &[i64]) -> bool {
+        let data: Vec<Data> = row.iter().map(|i| Data::Integer(*i)).collect();
+        return p.eval(&data);
+    }
+
+    #[test]
+    fn parse_simple() {
+        let p = parse("t.0 = 5 and not (t.1 >= t.2)", None).unwrap();
+        assert!(eval(&p, &[5, 1, 2]));
+        assert!(!eval(&p, &[5, 2, 2]));
+        assert!(!eval(&p, &[4, 1, 2]));
+
+        let p = parse("t.0 != -3", None).unwrap();
+        assert!(eval(&p, &[3]));
+        assert!(!eval(&p, &[-3]));
+
+        let p = parse("t.0 contains \"a \\\"b\\\"\"", None).unwrap();
+        assert!(p.eval(&[Data::Text(String::from("is a \"b\""))]));
+        assert!(!p.eval(&[Data::Text(String::from("a b"))]));
+    }
+
+    #[test]
+    fn parse_precedence() {
+        // and/or are left associative with equal precedence:
+        // (a or b) and c
+        let p = parse("t.0 = 1 or t.1 = 1 and t.2 = 1", None).unwrap();
+        assert!(!eval(&p, &[1, 0, 0]));
+        assert!(eval(&p, &[1, 0, 1]));
+
+        // not binds tighter: (not a) or b
+        let p = parse("not t.0 = 1 or t.1 = 1", None).unwrap();
+        assert!(eval(&p, &[1, 1]));
+        assert!(!eval(&p, &[1, 0]));
+    }
+
+    #[test]
+    fn parse_tables() {
+        let mut tables = HashMap::new();
+        tables.insert(String::from("a"), 0);
+        tables.insert(String::from("b"), 2);
+
+        let p = parse("a.1 = b.0", Some(&tables)).unwrap();
+        assert!(p.eval_with_2(&[Data::Integer(0), Data::Integer(7)],
+                              &[Data::Integer(7)]));
+
+        let e = parse("a.1 = c.0", Some(&tables)).err().unwrap();
+        assert_eq!(e, "line 1, column 7: unknown table 'c'");
+    }
+
+    #[test]
+    fn parse_errors() {
+        let e = parse("t.0 = 5 and\n  (t.1 < )", None).err().unwrap();
+        assert_eq!(e, "line 2, column 10: expected a column or literal, found )");
+
+        let e = parse("t.0 = 5 t.1 = 3", None).err().unwrap();
+        assert_eq!(e, "line 1, column 9: expected 'and', 'or' or end of input, found column t.1");
+
+        let e = parse("t.0 = \"abc", None).err().unwrap();
+        assert_eq!(e, "line 1, column 7: unterminated string literal");
+
+        let e = parse("t.0 ~ 5", None).err().unwrap();
+        assert_eq!(e, "line 1, column 5: unexpected character '~'");
+
+        let e = parse("t.0 contains t.1", None).err().unwrap();
+        assert_eq!(e, "line 1, column 5: operator contains cannot compare two columns");
+    }
+}
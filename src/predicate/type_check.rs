@@ -0,0 +1,173 @@
+// < begin copyright > 
+// Copyright Ryan Marcus 2018
+// 
+// This file is part of byoo.
+// 
+// byoo is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// byoo is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with byoo.  If not, see <http://www.gnu.org/licenses/>.
+// 
+// < end copyright > 
+// Checks predicates against the types of their input columns, so that
+// mistakes are reported when a plan is compiled rather than by a panic
+// part way through a query.
+use predicate::{Predicate, InList};
+use data::{Data, DataType};
+
+/// Whether values of the two types can be compared. DECIMALs of any
+/// precision and scale can be compared with each other.
+pub fn same_kind(t1: &DataType, t2: &DataType) -> bool {
+    return match (t1, t2) {
+        (DataType::DECIMAL(_, _), DataType::DECIMAL(_, _)) => true,
+        _ => t1.to_code() == t2.to_code()
+    };
+}
+
+fn check_literal(col: usize, col_type: &DataType, literal: &Data) -> Result<(), String> {
+    let lit_type = literal.data_type().unwrap();
+    if same_kind(col_type, &lit_type) {
+        return Ok(());
+    }
+
+    return Err(format!("column {} has type {:?}, but is compared to the {:?} literal {}",
+                       col, col_type, lit_type, literal));
+}
+
+impl Predicate {
+    /// Checks that every column this predicate references exists in
+    /// `types`, and that it is only compared to values of the same type.
+    pub fn type_check(&self, types: &[DataType]) -> Result<(), String> {
+        let col_type = |idx: usize| {
+            return types.get(idx).ok_or_else(
+                || format!("column {} does not exist (the input has {} columns)",
+                           idx, types.len()));
+        };
+
+        let require_text = |idx: usize, op: &str| {
+            let t = col_type(idx)?;
+            if let DataType::TEXT = t {
+                return Ok(());
+            }
+            
+            return Err(format!("column {} has type {:?}, but {} requires TEXT",
+                               idx, t, op));
+        };
+        
+        match self {
+            Predicate::And(children) | Predicate::Or(children) => {
+                for c in children.iter() {
+                    c.type_check(types)?;
+                }
+                return Ok(());
+            },
+
+            Predicate::Not(c) => return c.type_check(types),
+            Predicate::Const(_) => return Ok(()),
+
+            Predicate::Lt(col, lit) | Predicate::Lte(col, lit)
+                | Predicate::Gt(col, lit) | Predicate::Gte(col, lit)
+                | Predicate::Eq(col, lit) | Predicate::Neq(col, lit) => {
+                return check_literal(*col, col_type(*col)?, lit);
+            },
+
+            Predicate::LtCol(col1, col2) | Predicate::LteCol(col1, col2)
+                | Predicate::GtCol(col1, col2) | Predicate::GteCol(col1, col2)
+                | Predicate::EqCol(col1, col2) | Predicate::NeqCol(col1, col2) => {
+                let t1 = col_type(*col1)?;
+                let t2 = col_type(*col2)?;
+                if same_kind(t1, t2) {
+                    return Ok(());
+                }
+
+                return Err(format!("column {} has type {:?}, but is compared to column {} of type {:?}",
+                                   col1, t1, col2, t2));
+            },
+
+            Predicate::Between(col, low, high) => {
+                let t = col_type(*col)?;
+                check_literal(*col, t, low)?;
+                return check_literal(*col, t, high);
+            },
+
+            Predicate::In(col, vals) => {
+                let t = col_type(*col)?;
+                match vals {
+                    InList::Small(v) => {
+                        for lit in v.iter() {
+                            check_literal(*col, t, lit)?;
+                        }
+                    },
+                    InList::Hashed(v) => {
+                        for lit in v.iter() {
+                            check_literal(*col, t, lit)?;
+                        }
+                    }
+                };
+                return Ok(());
+            },
+
+            Predicate::Contains(col, _) => return require_text(*col, "contains"),
+            Predicate::Like(col, _) => return require_text(*col, "LIKE"),
+            Predicate::Regex(col, _) => return require_text(*col, "regex"),
+            Predicate::IsNull(col) => return col_type(*col).map(|_| ())
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use predicate::Predicate;
+    use data::DataType;
+
+    fn check(json: serde_json::Value, types: &[DataType]) -> Result<(), String> {
+        return Predicate::from_json(&json).type_check(types);
+    }
+
+    #[test]
+    fn accepts_valid() {
+        let types = vec![DataType::INTEGER, DataType::TEXT, DataType::DECIMAL(12, 2),
+                         DataType::DECIMAL(10, 4)];
+        
+        assert!(check(json!({ "op": "and", "children": [
+            { "op": "lt", "col": 0, "val": 5 },
+            { "op": "like", "col": 1, "val": "%x" },
+            { "op": "eq", "col": 2, "val": { "decimal": "0.5" } },
+            { "op": "gt", "col": 2, "col2": 3 },
+            { "op": "in", "col": 1, "vals": ["a", "b"] },
+            { "op": "is null", "col": 3 }
+        ]}), &types).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        let types = vec![DataType::INTEGER, DataType::TEXT, DataType::REAL];
+
+        assert_eq!(check(json!({ "op": "lt", "col": 0, "val": 5.5 }), &types),
+                   Err(String::from(
+                       "column 0 has type INTEGER, but is compared to the REAL literal 5.5")));
+        
+        assert_eq!(check(json!({ "op": "contains", "col": 2, "val": "x" }), &types),
+                   Err(String::from("column 2 has type REAL, but contains requires TEXT")));
+
+        assert_eq!(check(json!({ "op": "eq", "col": 0, "col2": 1 }), &types),
+                   Err(String::from(
+                       "column 0 has type INTEGER, but is compared to column 1 of type TEXT")));
+
+        assert_eq!(check(json!({ "op": "or", "children": [
+            { "op": "lt", "col": 0, "val": 5 },
+            { "op": "is null", "col": 3 }
+        ]}), &types),
+                   Err(String::from("column 3 does not exist (the input has 3 columns)")));
+
+        assert!(check(json!({ "op": "in", "col": 0, "vals": [1, 2, "x"] }), &types).is_err());
+    }
+}
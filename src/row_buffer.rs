@@ -106,11 +106,20 @@ impl RowBuffer {
     #[cfg(any(test, debug_assertions))]
     fn write_value(&mut self, d: Data) {
         debug_assert!(!self.is_full());
+        if d.is_null() {
+            // NULL is a valid value for any column type
+            self.data.push(d);
+            return;
+        }
+
         match self.types[self.data.len() % self.types.len()] {
             DataType::INTEGER => { debug_assert_matches!(d, Data::Integer(_)); },
             DataType::REAL => { debug_assert_matches!(d, Data::Real(_)); },
             DataType::TEXT => { debug_assert_matches!(d, Data::Text(_)); },
-            DataType::BLOB => { debug_assert_matches!(d, Data::Blob(_)); }
+            DataType::BLOB => { debug_assert_matches!(d, Data::Blob(_)); },
+            DataType::DATE => { debug_assert_matches!(d, Data::Date(_)); },
+            DataType::TIMESTAMP => { debug_assert_matches!(d, Data::Timestamp(_)); },
+            DataType::DECIMAL(_, _) => { debug_assert_matches!(d, Data::Decimal(_, _)); }
         };
         
         self.data.push(d);
@@ -17,6 +17,8 @@
 // along with byoo.  If not, see <http://www.gnu.org/licenses/>.
 // 
 // < end copyright > 
+#![allow(clippy::needless_return)]
+
 extern crate byoo;
 
 #[macro_use]
@@ -26,6 +28,7 @@ extern crate approx;
 mod tests {
     use byoo;
     use byoo::rows_to_string;
+    use byoo::Data;
 
     #[test]
     fn sorted_group_by_plan() {
@@ -246,4 +249,372 @@ mod tests {
         assert_abs_diff_eq!(data_vec[4][1].as_f64(), 4931.66, epsilon=0.01);
         assert_abs_diff_eq!(data_vec[4][2].as_f64(), 0.50, epsilon=0.01);
     }
+
+    #[test]
+    fn hashed_group_by_plan_variance() {
+        
+        let json = String::from(r#"
+{"op": "project",
+ "options": { "cols": [0, 3, 4, 5, 6] }, 
+ "input": [
+     {
+         "op": "hashed group by",
+         "options": {
+             "col": 0,
+             "aggregates": [
+                 {"op": "var_pop", "col": 2},
+                 {"op": "stddev", "col": 2},
+                 {"op": "corr", "cols": [1, 1]},
+                 {"op": "covar_pop", "cols": [1, 2]}
+             ]
+         },
+
+         "input": [            
+             { "op": "csv read",
+               "options": {
+                   "file": "res/inputs/agg_test.csv",
+                   "types": ["INTEGER", "INTEGER", "REAL"]
+               }
+             }]
+     }
+ ]
+}
+"#);
+
+        let root = byoo::compile(json);
+        let (read_buf, _) = root.start_save();
+
+        let data_vec = read_buf.into_vec();
+        assert_eq!(data_vec.len(), 5);
+        assert_eq!(data_vec[0].len(), 5);
+
+        for row in data_vec {
+            // the third column is uniform on [0, 1]
+            assert_abs_diff_eq!(row[1].as_f64(), 1.0 / 12.0, epsilon=0.01);
+            assert_abs_diff_eq!(row[2].as_f64(), (1.0f64 / 12.0).sqrt(), epsilon=0.01);
+            assert_abs_diff_eq!(row[3].as_f64(), 1.0, epsilon=1e-9);
+            assert!(row[4].as_f64().is_finite());
+        }
+    }
+
+    #[test]
+    fn hashed_group_by_plan_approx_distinct() {
+        
+        let json = String::from(r#"
+{"op": "project",
+ "options": { "cols": [0, 3, 4, 5] }, 
+ "input": [
+     {
+         "op": "hashed group by",
+         "options": {
+             "col": 0,
+             "aggregates": [
+                 {"op": "count distinct", "col": 1},
+                 {"op": "approx_count_distinct", "col": 1, "precision": 12},
+                 {"op": "approx_count_distinct", "col": 2, "precision": 12}
+             ]
+         },
+
+         "input": [            
+             { "op": "csv read",
+               "options": {
+                   "file": "res/inputs/agg_test.csv",
+                   "types": ["INTEGER", "INTEGER", "REAL"]
+               }
+             }]
+     }
+ ]
+}
+"#);
+
+        let root = byoo::compile(json);
+        let (read_buf, _) = root.start_save();
+
+        let mut data_vec = read_buf.into_vec();
+        data_vec.sort_by(|a, b| a[0].as_i64().cmp(&b[0].as_i64()));
+        assert_eq!(data_vec.len(), 5);
+
+        let totals = [10144, 9976, 10136, 9921, 9823];
+        for (row, total) in data_vec.iter().zip(totals.iter()) {
+            let exact = row[1].as_i64() as f64;
+            let approx = row[2].as_i64() as f64;
+            assert_relative_eq!(exact, approx, max_relative=0.05);
+
+            // every REAL in the file is distinct
+            let approx_real = row[3].as_i64() as f64;
+            assert_relative_eq!(*total as f64, approx_real, max_relative=0.05);
+        }
+    }
+
+    #[test]
+    fn hashed_group_by_plan_filtered() {
+        
+        let json = String::from(r#"
+{"op": "project",
+ "options": { "cols": [0, 3, 4, 5] }, 
+ "input": [
+     {
+         "op": "hashed group by",
+         "options": {
+             "col": 0,
+             "aggregates": [
+                 {"op": "count", "col": 1},
+                 {"op": "count", "col": 1,
+                  "filter": {"op": "lt", "col": 1, "val": 0}},
+                 {"op": "count", "col": 1, "filter": "t.1 >= 0"}
+             ]
+         },
+
+         "input": [            
+             { "op": "csv read",
+               "options": {
+                   "file": "res/inputs/agg_test.csv",
+                   "types": ["INTEGER", "INTEGER", "REAL"]
+               }
+             }]
+     }
+ ]
+}
+"#);
+
+        let root = byoo::compile(json);
+        let (read_buf, _) = root.start_save();
+
+        let mut data_vec = read_buf.into_vec();
+        data_vec.sort_by(|a, b| a[0].as_i64().cmp(&b[0].as_i64()));
+        assert_eq!(data_vec.len(), 5);
+
+        let totals = [10144, 9976, 10136, 9921, 9823];
+        for (row, total) in data_vec.iter().zip(totals.iter()) {
+            assert_eq!(row[1].as_i64(), *total);
+            assert!(row[2].as_i64() > 0);
+            assert!(row[3].as_i64() > 0);
+            assert_eq!(row[2].as_i64() + row[3].as_i64(), *total);
+        }
+    }
+
+    #[test]
+    fn filtered_aggregate_type_error() {
+        let json = String::from(r#"
+{"op": "hashed group by",
+ "options": {
+     "col": 0,
+     "aggregates": [
+         {"op": "count", "col": 1, "filter": "t.2 contains \"x\""}
+     ]
+ },
+ "input": [
+     { "op": "csv read",
+       "options": {
+           "file": "res/inputs/agg_test.csv",
+           "types": ["INTEGER", "INTEGER", "REAL"]
+       }
+     }]
+}
+"#);
+
+        let err = byoo::try_compile(json).err().unwrap();
+        assert_eq!(err, "hashed group by operator 0: column 2 has type REAL, but contains requires TEXT");
+    }
+
+    fn multi_col_group_by_plan(group_by: &str) -> Vec<Vec<Data>> {
+        let child = String::from(r#"
+{"op": "compute",
+ "options": {
+     "exprs": [
+         {"expr": {"op": "case",
+                   "when": [{"if": {"op": "gt", "children": [
+                                {"op": "col", "col": 1},
+                                {"op": "literal", "val": 0}]},
+                             "then": {"op": "literal", "val": 1}}],
+                   "else": {"op": "literal", "val": -1}},
+          "replace": 1}
+     ]
+ },
+ "input": [
+     { "op": "csv read",
+       "options": {
+           "file": "res/inputs/agg_test.csv",
+           "types": ["INTEGER", "INTEGER", "REAL"]
+       }
+     }]
+}"#);
+
+        let child = if group_by == "sorted group by" {
+            format!(r#"{{"op": "sort", "options": {{"cols": [0, 1]}}, "input": [{}]}}"#, child)
+        } else {
+            child
+        };
+
+        let json = format!(r#"
+{{"op": "project",
+ "options": {{ "cols": [0, 1, 3, 4] }},
+ "input": [
+     {{
+         "op": "{}",
+         "options": {{
+             "cols": [0, 1],
+             "aggregates": [
+                 {{"op": "count", "col": 1}},
+                 {{"op": "max", "col": 2}}
+             ]
+         }},
+         "input": [{}]
+     }}
+ ]
+}}
+"#, group_by, child);
+
+        let root = byoo::compile(json);
+        let (read_buf, _) = root.start_save();
+
+        let mut data_vec = read_buf.into_vec();
+        data_vec.sort_by(|a, b| a[0].as_i64().cmp(&b[0].as_i64())
+                         .then(a[1].as_i64().cmp(&b[1].as_i64())));
+        return data_vec;
+    }
+
+    #[test]
+    fn multi_col_group_by_plan_test() {
+        let hashed = multi_col_group_by_plan("hashed group by");
+        let sorted = multi_col_group_by_plan("sorted group by");
+        assert_eq!(hashed.len(), 10);
+        assert_eq!(hashed, sorted);
+
+        let totals = [10144, 9976, 10136, 9921, 9823];
+        for (idx, total) in totals.iter().enumerate() {
+            let neg = &hashed[2 * idx];
+            let pos = &hashed[2 * idx + 1];
+            assert_eq!(neg[0], Data::Integer(idx as i64));
+            assert_eq!(neg[1], Data::Integer(-1));
+            assert_eq!(pos[1], Data::Integer(1));
+            assert_eq!(neg[2].as_i64() + pos[2].as_i64(), *total);
+        }
+    }
+
+    #[test]
+    fn cube_group_by_plan() {
+        let json = String::from(r#"
+{"op": "project",
+ "options": { "cols": [0, 1, 3, 4] },
+ "input": [
+     {
+         "op": "grouping sets group by",
+         "options": {
+             "cube": [0, 1],
+             "aggregates": [
+                 {"op": "count", "col": 2}
+             ]
+         },
+         "input": [
+             {"op": "compute",
+              "options": {
+                  "exprs": [
+                      {"expr": {"op": "case",
+                                "when": [{"if": {"op": "gt", "children": [
+                                             {"op": "col", "col": 1},
+                                             {"op": "literal", "val": 0}]},
+                                          "then": {"op": "literal", "val": 1}}],
+                                "else": {"op": "literal", "val": -1}},
+                       "replace": 1}
+                  ]
+              },
+              "input": [
+                  { "op": "csv read",
+                    "options": {
+                        "file": "res/inputs/agg_test.csv",
+                        "types": ["INTEGER", "INTEGER", "REAL"]
+                    }
+                  }]
+             }]
+     }
+ ]
+}
+"#);
+
+        let root = byoo::compile(json);
+        let (read_buf, _) = root.start_save();
+
+        let mut data_vec = read_buf.into_vec();
+        data_vec.sort_by(|a, b| a[3].partial_cmp(&b[3]).unwrap()
+                         .then(a[0].partial_cmp(&b[0]).unwrap())
+                         .then(a[1].partial_cmp(&b[1]).unwrap()));
+        assert_eq!(data_vec.len(), 10 + 5 + 2 + 1);
+
+        let totals = [10144, 9976, 10136, 9921, 9823];
+        let by_key = &data_vec[10..15];
+        for (idx, (row, total)) in by_key.iter().zip(totals.iter()).enumerate() {
+            assert_eq!(row[0], Data::Integer(idx as i64));
+            assert_eq!(row[1], Data::Null);
+            assert_eq!(row[2], Data::Integer(*total));
+            assert_eq!(row[3], Data::Integer(1));
+        }
+
+        let by_sign = &data_vec[15..17];
+        assert_eq!(by_sign[0][0], Data::Null);
+        assert_eq!(by_sign[0][3], Data::Integer(2));
+        assert_eq!(by_sign[0][2].as_i64() + by_sign[1][2].as_i64(),
+                   totals.iter().sum::<i64>());
+
+        let grand_total = &data_vec[17];
+        assert_eq!(grand_total[..2].to_vec(), vec![Data::Null, Data::Null]);
+        assert_eq!(grand_total[2], Data::Integer(totals.iter().sum()));
+        assert_eq!(grand_total[3], Data::Integer(3));
+    }
+
+    fn high_cardinality_group_by_plan(memory_budget: u64, parallelism: u64) -> Vec<Vec<Data>> {
+        let json = format!(r#"
+{{"op": "project",
+ "options": {{ "cols": [1, 3, 4] }},
+ "input": [
+     {{
+         "op": "hashed group by",
+         "options": {{
+             "col": 1,
+             "memory_budget": {},
+             "parallelism": {},
+             "aggregates": [
+                 {{"op": "count", "col": 1}},
+                 {{"op": "sum", "col": 0}}
+             ]
+         }},
+         "input": [
+             {{ "op": "csv read",
+               "options": {{
+                   "file": "res/inputs/agg_test.csv",
+                   "types": ["INTEGER", "INTEGER", "REAL"]
+               }}
+             }}]
+     }}
+ ]
+}}
+"#, memory_budget, parallelism);
+
+        let root = byoo::compile(json);
+        let (read_buf, _) = root.start_save();
+
+        let mut data_vec = read_buf.into_vec();
+        data_vec.sort_by(|a, b| a[0].as_i64().cmp(&b[0].as_i64()));
+        return data_vec;
+    }
+
+    #[test]
+    fn memory_budget_group_by_plan() {
+        let in_memory = high_cardinality_group_by_plan(64 * 1024 * 1024, 1);
+        let spilled = high_cardinality_group_by_plan(256 * 1024, 1);
+
+        assert_eq!(in_memory.len(), 18388);
+        assert_eq!(in_memory.iter().map(|r| r[1].as_i64()).sum::<i64>(), 50000);
+        assert_eq!(in_memory, spilled);
+    }
+
+    #[test]
+    fn parallel_group_by_plan() {
+        let serial = high_cardinality_group_by_plan(64 * 1024 * 1024, 1);
+        let parallel = high_cardinality_group_by_plan(64 * 1024 * 1024, 4);
+        let parallel_spilled = high_cardinality_group_by_plan(256 * 1024, 3);
+
+        assert_eq!(serial, parallel);
+        assert_eq!(serial, parallel_spilled);
+    }
 }
@@ -103,4 +103,250 @@ mod tests {
         assert_eq!(data_vec[0], r_f);
         assert_eq!(data_vec[data_vec.len()-1], r_l);
     }
+
+    #[test]
+    fn compute_plan() {
+        
+        let json = String::from(r#"
+{
+    "op": "project",
+    "options": {
+        "cols": [0, 1, 5, 3]
+    },
+    "input": [{
+        "op": "compute",
+        "options": {
+            "exprs": [
+                {"expr": {"op": "add", "children": [{"op": "col", "col": 0},
+                                                     {"op": "col", "col": 1}]}},
+                {"expr": {"op": "case",
+                          "when": [{"if": {"op": "gt", "children": [
+                                       {"op": "col", "col": 3},
+                                       {"op": "literal", "val": 0}]},
+                                    "then": {"op": "literal", "val": 1}}],
+                          "else": {"op": "literal", "val": -1}},
+                 "replace": 3}
+            ]
+        },
+        
+        "input": [
+            { "op": "csv read",
+              "options": {
+                  "file": "res/inputs/test1.csv",
+                  "types": ["INTEGER", "INTEGER", "TEXT", "INTEGER", "REAL"]
+              }
+            }
+        ]
+    }]
+}
+
+"#);
+
+        let root = byoo::compile(json);
+        let (read_buf, _) = root.start_save();
+
+        let data_vec = read_buf.into_vec();
+
+        assert_eq!(data_vec.len(), 1000);
+
+        // check the first and last rows
+        let r_f = vec![Data::Integer(-7119), Data::Integer(8430),
+                       Data::Integer(1311), Data::Integer(1)];
+        let r_l = vec![Data::Integer(-4240), Data::Integer(2604),
+                       Data::Integer(-1636), Data::Integer(-1)];
+
+        assert_eq!(data_vec[0], r_f);
+        assert_eq!(data_vec[data_vec.len()-1], r_l);
+    }
+
+    #[test]
+    fn text_predicate_plan() {
+        
+        let json = String::from(r#"
+{
+    "op": "filter",
+    "options": {
+        "predicate": "t.0 < 100 and not (t.3 >= 0 or t.1 = t.3)"
+    },
+        
+    "input": [
+        { "op": "csv read",
+          "options": {
+              "file": "res/inputs/test1.csv",
+              "types": ["INTEGER", "INTEGER", "TEXT", "INTEGER", "REAL"]
+          }
+        }
+    ]
+}
+
+"#);
+
+        let root = byoo::compile(json);
+        let (read_buf, _) = root.start_save();
+
+        let data_vec = read_buf.into_vec();
+
+        assert!(!data_vec.is_empty());
+        for row in data_vec {
+            assert!(row[0] < Data::Integer(100));
+            assert!(row[3] < Data::Integer(0));
+        }
+    }
+
+    #[test]
+    fn type_error_plan() {
+        
+        let json = String::from(r#"
+{
+    "op": "project",
+    "options": {
+        "cols": [0, 1, 3]
+    },
+    "input": [{
+        "op": "filter",
+        "options": {
+            "predicate": {"op": "or", "children": [
+                {"op": "lt", "col": 0, "val": 100},
+                {"op": "contains", "col": 4, "val": "x"}
+            ]}
+        },
+        
+        "input": [
+            { "op": "csv read",
+              "options": {
+                  "file": "res/inputs/test1.csv",
+                  "types": ["INTEGER", "INTEGER", "TEXT", "INTEGER", "REAL"]
+              }
+            }
+        ]
+    }]
+}
+
+"#);
+
+        let err = byoo::try_compile(json).err().unwrap();
+        assert_eq!(err, "filter operator 1: column 4 has type REAL, but contains requires TEXT");
+    }
+
+    #[test]
+    fn group_key_error_plan() {
+        let json = String::from(r#"
+{"op": "hashed group by",
+ "options": {
+     "cols": [0, 5],
+     "aggregates": [{"op": "count", "col": 1}]
+ },
+ "input": [
+     { "op": "csv read",
+       "options": {
+           "file": "res/inputs/test1.csv",
+           "types": ["INTEGER", "INTEGER", "TEXT", "INTEGER", "REAL"]
+       }
+     }]
+}
+"#);
+
+        assert_eq!(byoo::try_compile(json).err().unwrap(),
+                   "hashed group by operator 0: group key column 5 out of range for 5 input columns");
+    }
+
+    #[test]
+    fn bad_predicate_plan() {
+        let json = String::from(r#"
+{"op": "filter",
+ "options": { "predicate": {"op": "bogus", "col": 0, "val": 1} },
+ "input": [
+     { "op": "csv read",
+       "options": {
+           "file": "res/inputs/test1.csv",
+           "types": ["INTEGER", "INTEGER", "TEXT", "INTEGER", "REAL"]
+       }
+     }]
+}
+"#);
+
+        assert_eq!(byoo::try_compile(json).err().unwrap(),
+                   "filter operator 0: unknown op bogus in predicate");
+    }
+
+    #[test]
+    fn missing_aggregates_plan() {
+        let json = String::from(r#"
+{"op": "hashed group by",
+ "options": { "col": 0 },
+ "input": [
+     { "op": "csv read",
+       "options": {
+           "file": "res/inputs/test1.csv",
+           "types": ["INTEGER", "INTEGER", "TEXT", "INTEGER", "REAL"]
+       }
+     }]
+}
+"#);
+
+        assert_eq!(byoo::try_compile(json).err().unwrap(),
+                   "hashed group by operator 0: group by requires an aggregates array");
+    }
+
+    #[test]
+    fn compute_type_error_plan() {
+        let json = String::from(r#"
+{"op": "compute",
+ "options": { "exprs": [{"expr": {"op": "gt", "children": [
+     {"op": "col", "col": 0},
+     {"op": "literal", "val": 0.5}]}}] },
+ "input": [
+     { "op": "csv read",
+       "options": {
+           "file": "res/inputs/test1.csv",
+           "types": ["INTEGER", "INTEGER", "TEXT", "INTEGER", "REAL"]
+       }
+     }]
+}
+"#);
+
+        assert_eq!(byoo::try_compile(json).err().unwrap(),
+                   "compute operator 0: Cannot compare types INTEGER and REAL in Gt expression");
+    }
+
+    #[test]
+    fn compute_bad_expression_plan() {
+        let json = String::from(r#"
+{"op": "compute",
+ "options": { "exprs": [{"expr": {"op": "cast", "type": "INTEGER"}}] },
+ "input": [
+     { "op": "csv read",
+       "options": {
+           "file": "res/inputs/test1.csv",
+           "types": ["INTEGER", "INTEGER", "TEXT", "INTEGER", "REAL"]
+       }
+     }]
+}
+"#);
+
+        assert_eq!(byoo::try_compile(json).err().unwrap(),
+                   "compute operator 0: Expression \"cast\" requires children");
+    }
+
+    #[test]
+    fn sum_mode_error_plan() {
+        let json = String::from(r#"
+{"op": "hashed group by",
+ "options": {
+     "col": 0,
+     "aggregates": [{"op": "sum distinct", "col": 1, "mode": "quick"}]
+ },
+ "input": [
+     { "op": "csv read",
+       "options": {
+           "file": "res/inputs/test1.csv",
+           "types": ["INTEGER", "INTEGER", "TEXT", "INTEGER", "REAL"]
+       }
+     }]
+}
+"#);
+
+        assert_eq!(byoo::try_compile(json).err().unwrap(),
+                   "hashed group by operator 0: Unknown sum mode quick (expected exact or fast)");
+    }
 }
@@ -0,0 +1,116 @@
+// < begin copyright > 
+// Copyright Ryan Marcus 2018
+// 
+// This file is part of byoo.
+// 
+// byoo is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// byoo is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with byoo.  If not, see <http://www.gnu.org/licenses/>.
+// 
+// < end copyright > 
+#![allow(clippy::needless_return)]
+
+extern crate byoo;
+
+#[macro_use]
+extern crate serde_json;
+
+#[cfg(test)]
+mod tests {
+    use byoo;
+    use byoo::Data;
+    use serde_json;
+
+    fn csv_read() -> serde_json::Value {
+        return json!(
+            { "op": "csv read",
+              "options": {
+                  "file": "res/inputs/agg_test.csv",
+                  "types": ["INTEGER", "INTEGER", "REAL"]
+              }
+            });
+    }
+
+    fn run_sorted(json: serde_json::Value) -> Vec<Vec<Data>> {
+        let root = byoo::compile(json.to_string());
+        let (read_buf, _) = root.start_save();
+
+        let mut data_vec = read_buf.into_vec();
+        data_vec.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        return data_vec;
+    }
+
+    #[test]
+    fn distinct_subset_plan() {
+        let hashed = run_sorted(json!(
+            { "op": "project",
+              "options": { "cols": [0] },
+              "input": [{ "op": "hashed distinct",
+                          "options": { "cols": [0] },
+                          "input": [csv_read()] }]
+            }));
+
+        let sorted = run_sorted(json!(
+            { "op": "project",
+              "options": { "cols": [0] },
+              "input": [{ "op": "sorted distinct",
+                          "options": { "cols": [0] },
+                          "input": [{ "op": "sort",
+                                      "options": { "cols": [0] },
+                                      "input": [csv_read()] }] }]
+            }));
+
+        let expected: Vec<Vec<Data>> = (0..5).map(|i| vec![Data::Integer(i)]).collect();
+        assert_eq!(hashed, expected);
+        assert_eq!(sorted, expected);
+    }
+
+    #[test]
+    fn distinct_all_cols_plan() {
+        // project away the unique third column, so there are duplicates
+        let projected = json!(
+            { "op": "project",
+              "options": { "cols": [1, 0] },
+              "input": [csv_read()] });
+
+        let in_memory = run_sorted(json!(
+            { "op": "hashed distinct",
+              "input": [projected.clone()] }));
+
+        let spilled = run_sorted(json!(
+            { "op": "hashed distinct",
+              "options": { "memory_budget": 64 * 1024 },
+              "input": [projected.clone()] }));
+
+        let sorted = run_sorted(json!(
+            { "op": "sorted distinct",
+              "input": [{ "op": "sort",
+                          "options": { "cols": [0, 1] },
+                          "input": [projected] }] }));
+
+        assert!(in_memory.len() < 50000);
+        in_memory.windows(2).for_each(|w| assert_ne!(w[0], w[1]));
+        assert_eq!(in_memory, spilled);
+        assert_eq!(in_memory, sorted);
+    }
+
+    #[test]
+    fn distinct_col_error_plan() {
+        let json = json!(
+            { "op": "hashed distinct",
+              "options": { "cols": [3] },
+              "input": [csv_read()] });
+
+        assert_eq!(byoo::try_compile(json.to_string()).err().unwrap(),
+                   "hashed distinct operator 0: distinct column 3 out of range for 3 input columns");
+    }
+}